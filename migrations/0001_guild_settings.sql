@@ -0,0 +1,10 @@
+-- Per-guild settings. Only settings that have been changed get a row, so
+-- resetting one to its default deletes the row.
+CREATE TABLE guild_settings (
+    guild_id   INTEGER NOT NULL, -- Discord guild ID
+    key        TEXT    NOT NULL, -- setting name, e.g. `visibility`
+    value      TEXT    NOT NULL, -- setting value, parsed by whoever reads it
+    updated_by INTEGER,          -- Discord user ID of whoever last changed it
+    updated_at INTEGER NOT NULL, -- unix seconds
+    PRIMARY KEY (guild_id, key)
+) STRICT;
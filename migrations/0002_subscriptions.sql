@@ -0,0 +1,27 @@
+-- Channels following a BeatSaver mapper's new uploads. A guild can have the
+-- same mapper in several channels.
+CREATE TABLE subscriptions (
+    guild_id    INTEGER NOT NULL, -- Discord guild ID
+    channel_id  INTEGER NOT NULL, -- Discord channel ID new maps get posted in
+    mapper_id   INTEGER NOT NULL, -- BeatSaver user ID
+    mapper_name TEXT    NOT NULL, -- BeatSaver username when subscribed, for showing
+    created_by  INTEGER NOT NULL, -- Discord user ID of whoever subscribed
+    created_at  INTEGER NOT NULL, -- unix seconds; maps published before this are never posted
+    PRIMARY KEY (channel_id, mapper_id)
+) STRICT;
+
+CREATE INDEX subscriptions_guild ON subscriptions (guild_id);
+CREATE INDEX subscriptions_mapper ON subscriptions (mapper_id);
+
+-- Maps that have already been posted to a channel, so each one only goes
+-- out once, restarts included. Posts that failed (e.g. missing permissions)
+-- get a few more tries, then stay claimed so a broken channel isn't retried
+-- forever.
+CREATE TABLE posted_maps (
+    channel_id INTEGER NOT NULL,           -- Discord channel ID
+    map_id     TEXT    NOT NULL,           -- BeatSaver map ID, lowercase
+    posted_at  INTEGER NOT NULL,           -- unix seconds
+    failures   INTEGER NOT NULL DEFAULT 0, -- failed sends so far
+    retry      INTEGER NOT NULL DEFAULT 0, -- 1 if it can be claimed again
+    PRIMARY KEY (channel_id, map_id)
+) STRICT;
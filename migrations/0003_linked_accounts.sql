@@ -0,0 +1,12 @@
+-- Discord users' ScoreSaber and BeatLeader accounts, at most one of each.
+CREATE TABLE linked_accounts (
+    user_id     INTEGER NOT NULL, -- Discord user ID
+    service     TEXT    NOT NULL, -- 'scoresaber' or 'beatleader'
+    player_id   TEXT    NOT NULL, -- the player's ID on that service
+    player_name TEXT    NOT NULL, -- their name when linked, for showing
+    verified    INTEGER NOT NULL, -- 1 if BeatLeader showed the Discord account as theirs
+    linked_at   INTEGER NOT NULL, -- unix seconds
+    PRIMARY KEY (user_id, service)
+) STRICT;
+
+CREATE INDEX linked_accounts_player ON linked_accounts (service, player_id);
@@ -0,0 +1,10 @@
+-- Servers where people have used the bot, so server leaderboards know who
+-- to include without the privileged member list. Only people with linked
+-- accounts get included, but anyone is noted so linking later counts.
+-- Whether they're still a member is checked when a leaderboard is built.
+CREATE TABLE linked_guild_members (
+    guild_id INTEGER NOT NULL, -- Discord guild ID
+    user_id  INTEGER NOT NULL, -- Discord user ID
+    seen_at  INTEGER NOT NULL, -- unix seconds, last time they used the bot there
+    PRIMARY KEY (guild_id, user_id)
+) STRICT;
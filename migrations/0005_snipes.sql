@@ -0,0 +1,44 @@
+-- Snipes that have been posted, so the same lead change only goes out once
+-- and each guild's hourly limit holds across restarts.
+CREATE TABLE snipes (
+    guild_id    INTEGER NOT NULL, -- Discord guild ID
+    service     TEXT    NOT NULL, -- 'scoresaber' or 'beatleader'
+    leaderboard TEXT    NOT NULL, -- '<hash>:<characteristic>:<difficulty>', hash lowercase
+    sniper_id   INTEGER NOT NULL, -- Discord user ID of whoever took the lead
+    victim_id   INTEGER NOT NULL, -- Discord user ID of whoever lost it
+    sniped_at   INTEGER NOT NULL  -- unix seconds
+) STRICT;
+
+CREATE INDEX snipes_guild_time ON snipes (guild_id, sniped_at);
+CREATE INDEX snipes_leaderboard ON snipes (guild_id, service, leaderboard);
+
+-- People who don't want snipes involving them posted in a guild, either at
+-- all or just against one person.
+CREATE TABLE snipe_mutes (
+    guild_id   INTEGER NOT NULL, -- Discord guild ID
+    user_id    INTEGER NOT NULL, -- Discord user ID of whoever muted
+    muted_id   INTEGER NOT NULL, -- Discord user ID they muted, 0 for everyone
+    created_at INTEGER NOT NULL, -- unix seconds
+    PRIMARY KEY (guild_id, user_id, muted_id)
+) STRICT;
+
+-- When each player's scores were last looked at, so scores from before are
+-- never treated as new, restarts included.
+CREATE TABLE snipe_checks (
+    service    TEXT    NOT NULL, -- 'scoresaber' or 'beatleader'
+    player_id  TEXT    NOT NULL, -- the player's ID on that service
+    checked_at INTEGER NOT NULL, -- unix seconds
+    PRIMARY KEY (service, player_id)
+) STRICT;
+
+-- Each watched player's best score on every leaderboard they've been seen
+-- playing, so a new score only counts as a snipe against someone the player
+-- wasn't already ahead of.
+CREATE TABLE snipe_bests (
+    service     TEXT    NOT NULL, -- 'scoresaber' or 'beatleader'
+    player_id   TEXT    NOT NULL, -- the player's ID on that service
+    leaderboard TEXT    NOT NULL, -- '<hash>:<characteristic>:<difficulty>', hash lowercase
+    score       INTEGER NOT NULL, -- the best score seen
+    set_at      INTEGER NOT NULL, -- unix seconds, when that score was set
+    PRIMARY KEY (service, player_id, leaderboard)
+) STRICT;
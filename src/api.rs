@@ -1,17 +1,26 @@
 use axum::{response::IntoResponse, Json, Router};
-use log::debug;
+use log::{debug, info};
 use serde_json::json;
-use std::net::SocketAddr;
+use std::{future::Future, net::SocketAddr};
 use tokio::net::TcpListener;
 
 pub async fn health() -> impl IntoResponse {
     Json(json!({"status": "ok"}))
 }
 
-pub async fn serve(app: Router, port: u16) {
+/// Serves the HTTP API until `shutdown` resolves.
+pub async fn serve<F>(app: Router, port: u16, shutdown: F) -> std::io::Result<()>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    let listener = TcpListener::bind(addr).await.unwrap();
-    debug!("listening on {}", listener.local_addr().unwrap());
+    let listener = TcpListener::bind(addr).await?;
+    debug!("listening on {}", listener.local_addr()?);
 
-    let _ = axum::serve(listener, app).await;
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown)
+        .await?;
+
+    info!("HTTP server stopped");
+    Ok(())
 }
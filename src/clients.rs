@@ -0,0 +1,7 @@
+pub mod beatleader;
+pub mod beatsaver;
+pub mod http;
+pub mod scoresaber;
+
+#[cfg(test)]
+mod mock;
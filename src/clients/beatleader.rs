@@ -0,0 +1,599 @@
+use std::time::Duration;
+
+use log::debug;
+use serde::Deserialize;
+
+use super::http::{Api, ApiError};
+use crate::{core::config::BeatLeaderConfig, utils::cache::TtlCache, Error};
+
+const PLAYER_CACHE: (usize, Duration) = (500, Duration::from_secs(5 * 60));
+const SONG_CACHE: (usize, Duration) = (500, Duration::from_secs(10 * 60));
+const LEADERBOARD_CACHE: (usize, Duration) = (500, Duration::from_secs(2 * 60));
+/// How many scores one leaderboard page shows.
+pub const PAGE_SIZE: u32 = 10;
+
+/// BeatLeader lookups with a short cache in front.
+pub struct BeatLeader {
+    api: Api,
+    players: TtlCache<String, Player>,
+    songs: TtlCache<String, SongLeaderboards>,
+    leaderboards: TtlCache<String, Leaderboard>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Player {
+    pub id: String,
+    pub name: String,
+    pub avatar: String,
+    pub country: String,
+    pub pp: f64,
+    /// 0 for inactive players.
+    pub rank: u32,
+    pub country_rank: u32,
+    #[serde(default)]
+    pub banned: bool,
+    #[serde(default)]
+    pub inactive: bool,
+    #[serde(default)]
+    pub clans: Vec<Clan>,
+    /// Accounts elsewhere the player has connected, Discord included.
+    #[serde(default)]
+    pub socials: Vec<Social>,
+    pub score_stats: Option<ScoreStats>,
+}
+
+impl Player {
+    /// The Discord user ID the player connected on BeatLeader, if any.
+    pub fn discord_id(&self) -> Option<u64> {
+        self.socials
+            .iter()
+            .find(|social| social.service.eq_ignore_ascii_case("discord"))
+            .and_then(|social| social.user_id.parse().ok())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Clan {
+    pub tag: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Social {
+    pub service: String,
+    #[serde(default)]
+    pub user_id: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScoreStats {
+    /// From 0 to 1.
+    pub average_ranked_accuracy: f64,
+    pub ranked_play_count: u32,
+    pub total_play_count: u32,
+    pub top_pp: f64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Score {
+    /// From 0 to 1.
+    pub accuracy: f64,
+    pub modified_score: u64,
+    pub pp: f64,
+    pub rank: u32,
+    #[serde(default)]
+    pub modifiers: String,
+    pub missed_notes: u32,
+    pub bad_cuts: u32,
+    pub full_combo: bool,
+    /// Unix seconds.
+    #[serde(default)]
+    pub timepost: i64,
+    /// Filled in on leaderboard scores.
+    pub player: Option<LeaderboardPlayer>,
+    /// Filled in on a player's scores.
+    pub leaderboard: Option<ScoreLeaderboard>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct LeaderboardPlayer {
+    pub id: String,
+    pub name: String,
+    pub country: String,
+}
+
+/// The leaderboard a player's score is on.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ScoreLeaderboard {
+    pub id: String,
+    pub song: Song,
+    pub difficulty: Difficulty,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Song {
+    pub hash: String,
+    pub name: String,
+    pub author: String,
+    pub mapper: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Difficulty {
+    /// BeatSaver's name, e.g. `ExpertPlus`.
+    pub difficulty_name: String,
+    /// BeatSaver's characteristic name, e.g. `Standard`.
+    pub mode_name: String,
+    pub status: LeaderboardStatus,
+    pub stars: Option<f64>,
+}
+
+impl Difficulty {
+    /// BeatSaver's name for the characteristic, for the few BeatLeader
+    /// shortens.
+    pub fn characteristic(&self) -> &str {
+        match self.mode_name.as_str() {
+            "90Degree" => "Rotation90Degrees",
+            "360Degree" => "Rotation360Degrees",
+            other => other,
+        }
+    }
+}
+
+/// Whether a difficulty is ranked, loved, etc.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(from = "i32")]
+pub enum LeaderboardStatus {
+    Unranked,
+    Nominated,
+    Qualified,
+    Ranked,
+    Unrankable,
+    Outdated,
+    Event,
+    Ost,
+    Loved,
+    Other(i32),
+}
+
+impl From<i32> for LeaderboardStatus {
+    fn from(status: i32) -> Self {
+        match status {
+            0 => Self::Unranked,
+            1 => Self::Nominated,
+            2 => Self::Qualified,
+            3 => Self::Ranked,
+            4 => Self::Unrankable,
+            5 => Self::Outdated,
+            6 => Self::Event,
+            7 => Self::Ost,
+            8 => Self::Loved,
+            other => Self::Other(other),
+        }
+    }
+}
+
+impl LeaderboardStatus {
+    /// Whether scores on it are worth PP.
+    pub fn has_pp(self) -> bool {
+        matches!(self, Self::Ranked | Self::Qualified | Self::Event)
+    }
+}
+
+/// Every leaderboard for one map version.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SongLeaderboards {
+    pub song: Song,
+    pub leaderboards: Vec<LeaderboardSummary>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct LeaderboardSummary {
+    pub id: String,
+    pub difficulty: Difficulty,
+}
+
+/// One page of a difficulty's leaderboard.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Leaderboard {
+    pub id: String,
+    pub song: Song,
+    pub difficulty: Difficulty,
+    pub scores: Vec<Score>,
+    /// How many scores there are in total.
+    #[serde(default)]
+    pub plays: u32,
+}
+
+impl Leaderboard {
+    pub fn page_count(&self) -> u32 {
+        self.plays.div_ceil(PAGE_SIZE).max(1)
+    }
+}
+
+#[derive(Deserialize)]
+struct Page<T> {
+    data: Vec<T>,
+}
+
+impl BeatLeader {
+    pub fn new(config: &BeatLeaderConfig) -> Self {
+        Self {
+            api: Api::new("BeatLeader", &config.api_url),
+            players: TtlCache::new(PLAYER_CACHE.0, PLAYER_CACHE.1),
+            songs: TtlCache::new(SONG_CACHE.0, SONG_CACHE.1),
+            leaderboards: TtlCache::new(LEADERBOARD_CACHE.0, LEADERBOARD_CACHE.1),
+        }
+    }
+
+    /// Finds a player by ID, profile link or name.
+    pub async fn player(&self, query: &str) -> Result<Player, Error> {
+        let id = match player_id(query) {
+            Some(id) => id,
+            None => self.search_player(query.trim()).await?.id,
+        };
+        if let Some(player) = self.players.get(&id) {
+            return Ok(player);
+        }
+
+        debug!("BeatLeader player cache miss for {id}");
+        self.fetch_player(&id).await
+    }
+
+    /// Gets a player by ID, skipping the cache, e.g. to see a Discord
+    /// account they connected a moment ago.
+    pub async fn fetch_player(&self, id: &str) -> Result<Player, Error> {
+        let player: Player = self
+            .api
+            .get(
+                &format!("/player/{id}"),
+                &[("stats", "true".to_owned())],
+                || format!("Player {id}"),
+            )
+            .await?;
+        self.players.insert(id.to_owned(), player.clone());
+
+        Ok(player)
+    }
+
+    /// The player whose name matches best, exact matches first.
+    async fn search_player(&self, name: &str) -> Result<Player, Error> {
+        let search: Page<Player> = self
+            .api
+            .get(
+                "/players",
+                &[("search", name.to_owned()), ("count", "10".to_owned())],
+                || format!("A player called {name}"),
+            )
+            .await?;
+
+        let mut players = search.data;
+        let exact = players
+            .iter()
+            .position(|player| player.name.eq_ignore_ascii_case(name));
+        match exact {
+            Some(idx) => Ok(players.swap_remove(idx)),
+            None if !players.is_empty() => Ok(players.swap_remove(0)),
+            None => Err(self.api.not_found(format!("A player called {name}")).into()),
+        }
+    }
+
+    /// A player's scores, best (`pp`) or most recent (`date`) first.
+    pub async fn scores(
+        &self,
+        player_id: &str,
+        sort_by: &str,
+        count: u32,
+    ) -> Result<Vec<Score>, Error> {
+        let scores: Page<Score> = self
+            .api
+            .get(
+                &format!("/player/{player_id}/scores"),
+                &[
+                    ("sortBy", sort_by.to_owned()),
+                    ("order", "desc".to_owned()),
+                    ("page", "1".to_owned()),
+                    ("count", count.to_string()),
+                ],
+                || format!("Player {player_id}"),
+            )
+            .await?;
+
+        Ok(scores.data)
+    }
+
+    /// One page (starting at 0) of a difficulty's leaderboard.
+    ///
+    /// `characteristic` and `difficulty` are BeatSaver's names, like
+    /// `Standard` and `ExpertPlus`.
+    pub async fn leaderboard(
+        &self,
+        hash: &str,
+        characteristic: &str,
+        difficulty: &str,
+        page: u32,
+    ) -> Result<Leaderboard, Error> {
+        let id = self
+            .song_leaderboards(hash)
+            .await?
+            .leaderboards
+            .into_iter()
+            .find(|leaderboard| {
+                leaderboard.difficulty.mode_name == mode_name(characteristic)
+                    && leaderboard.difficulty.difficulty_name == difficulty
+            })
+            .map(|leaderboard| leaderboard.id)
+            .ok_or_else(|| {
+                self.api.not_found(format!(
+                    "A {characteristic} {difficulty} leaderboard for that map"
+                ))
+            })?;
+
+        let key = format!("{id}:{page}");
+        if let Some(leaderboard) = self.leaderboards.get(&key) {
+            return Ok(leaderboard);
+        }
+
+        debug!("BeatLeader leaderboard cache miss for {key}");
+        let leaderboard: Leaderboard = self
+            .api
+            .get(
+                &format!("/leaderboard/{id}"),
+                &[
+                    ("page", (page + 1).to_string()),
+                    ("count", PAGE_SIZE.to_string()),
+                ],
+                || format!("Leaderboard {id}"),
+            )
+            .await?;
+        self.leaderboards.insert(key, leaderboard.clone());
+
+        Ok(leaderboard)
+    }
+
+    /// A player's score on one difficulty of a map version, if they have one.
+    pub async fn player_score(
+        &self,
+        player_id: &str,
+        hash: &str,
+        characteristic: &str,
+        difficulty: &str,
+    ) -> Result<Option<Score>, Error> {
+        let res = self
+            .api
+            .get(
+                &format!(
+                    "/score/{player_id}/{hash}/{difficulty}/{}",
+                    mode_name(characteristic)
+                ),
+                &[],
+                || format!("A score by player {player_id}"),
+            )
+            .await;
+
+        match res {
+            Ok(score) => Ok(Some(score)),
+            Err(err) if ApiError::is_not_found(&err) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Every leaderboard for a map version.
+    async fn song_leaderboards(&self, hash: &str) -> Result<SongLeaderboards, Error> {
+        let hash = hash.to_lowercase();
+        if let Some(song) = self.songs.get(&hash) {
+            return Ok(song);
+        }
+
+        let song: SongLeaderboards = self
+            .api
+            .get(&format!("/leaderboards/hash/{hash}"), &[], || {
+                "That map".to_owned()
+            })
+            .await?;
+        self.songs.insert(hash, song.clone());
+
+        Ok(song)
+    }
+}
+
+/// Pulls a player ID out of a bare ID or a `beatleader.com/u/<id>` link.
+fn player_id(query: &str) -> Option<String> {
+    let query = query.trim();
+    let id = match query.split_once("/u/") {
+        Some((_, rest)) => rest.split(['/', '?', '#']).next().unwrap_or_default(),
+        None => query,
+    };
+
+    (!id.is_empty() && id.chars().all(|c| c.is_ascii_digit())).then(|| id.to_owned())
+}
+
+/// BeatLeader's name for a characteristic, which is mostly BeatSaver's.
+fn mode_name(characteristic: &str) -> &str {
+    match characteristic {
+        "Rotation90Degrees" => "90Degree",
+        "Rotation360Degrees" => "360Degree",
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{
+        clients::mock::{MockServer, Reply},
+        core::error::ErrorKind,
+    };
+
+    const HASH: &str = "abc123";
+
+    fn client(server: &MockServer) -> BeatLeader {
+        BeatLeader::new(&BeatLeaderConfig {
+            api_url: server.url().to_owned(),
+        })
+    }
+
+    fn difficulty(name: &str, mode: &str) -> Value {
+        json!({"difficultyName": name, "modeName": mode, "status": 3, "stars": 7.5})
+    }
+
+    fn song() -> Value {
+        json!({"hash": HASH, "name": "Song", "author": "Artist", "mapper": "Mapper"})
+    }
+
+    fn score(player_id: &str, rank: u32) -> Value {
+        json!({
+            "accuracy": 0.95,
+            "modifiedScore": 1000 - rank,
+            "pp": 250.0,
+            "rank": rank,
+            "missedNotes": 1,
+            "badCuts": 0,
+            "fullCombo": false,
+            "timepost": 1_790_000_000,
+            "player": {"id": player_id, "name": "Someone", "country": "NL"}
+        })
+    }
+
+    #[tokio::test]
+    async fn player_is_parsed() {
+        let server = MockServer::start(|target| match target {
+            "/player/1?stats=true" => Reply::json(json!({
+                "id": "1",
+                "name": "Kin",
+                "avatar": "https://cdn.beatleader.com/1.png",
+                "country": "NL",
+                "pp": 9876.5,
+                "rank": 12,
+                "countryRank": 2,
+                "clans": [{"tag": "BSMG"}],
+                "socials": [
+                    {"service": "Twitch", "userId": "kin"},
+                    {"service": "Discord", "userId": "123456789"}
+                ],
+                "scoreStats": {"averageRankedAccuracy": 0.94, "topPp": 400.1}
+            })),
+            _ => Reply::status(404),
+        })
+        .await;
+
+        let player = client(&server)
+            .player("https://beatleader.com/u/1")
+            .await
+            .unwrap();
+        assert_eq!(player.name, "Kin");
+        assert_eq!(player.clans[0].tag, "BSMG");
+        assert_eq!(player.discord_id(), Some(123456789));
+        let stats = player.score_stats.unwrap();
+        assert_eq!(stats.top_pp, 400.1);
+        assert_eq!(stats.ranked_play_count, 0);
+    }
+
+    #[tokio::test]
+    async fn missing_players_are_not_found() {
+        let server = MockServer::start(|target| match target {
+            target if target.starts_with("/players?") => Reply::json(json!({"data": []})),
+            _ => Reply::status(404),
+        })
+        .await;
+        let beatleader = client(&server);
+
+        for query in ["1", "nobody"] {
+            let err = beatleader.player(query).await.unwrap_err();
+            assert_eq!(
+                ErrorKind::classify(&err),
+                ErrorKind::NotFound {
+                    service: "BeatLeader"
+                }
+            );
+        }
+        // a missing score is just no score
+        let score = beatleader
+            .player_score("1", HASH, "Standard", "Expert")
+            .await
+            .unwrap();
+        assert!(score.is_none());
+    }
+
+    #[tokio::test]
+    async fn rate_limits_say_when_to_retry() {
+        let server = MockServer::start(|_| Reply::status(429).header("Retry-After", "12")).await;
+
+        let err = client(&server).player("1").await.unwrap_err();
+        assert_eq!(
+            ErrorKind::classify(&err),
+            ErrorKind::RateLimited {
+                service: "BeatLeader",
+                retry_after: Some(Duration::from_secs(12)),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn leaderboard_pages() {
+        let server = MockServer::start(|target| match target {
+            "/leaderboards/hash/abc123" => Reply::json(json!({
+                "song": song(),
+                "leaderboards": [
+                    {"id": "lb-hard", "difficulty": difficulty("Hard", "Standard")},
+                    {"id": "lb-90", "difficulty": difficulty("Expert", "90Degree")}
+                ]
+            })),
+            target if target.starts_with("/leaderboard/lb-90?") => {
+                let page: u32 = target
+                    .split(['?', '&'])
+                    .find_map(|param| param.strip_prefix("page="))
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                let scores: Vec<Value> = (1..=PAGE_SIZE)
+                    .map(|n| score(&n.to_string(), (page - 1) * PAGE_SIZE + n))
+                    .collect();
+                Reply::json(json!({
+                    "id": "lb-90",
+                    "song": song(),
+                    "difficulty": difficulty("Expert", "90Degree"),
+                    "scores": scores,
+                    "plays": 25
+                }))
+            }
+            _ => Reply::status(404),
+        })
+        .await;
+        let beatleader = client(&server);
+
+        let second = beatleader
+            .leaderboard("ABC123", "Rotation90Degrees", "Expert", 1)
+            .await
+            .unwrap();
+        assert_eq!(second.page_count(), 3);
+        assert_eq!(second.scores[0].rank, 11);
+        assert_eq!(second.difficulty.characteristic(), "Rotation90Degrees");
+        assert!(second.difficulty.status.has_pp());
+
+        // the song's leaderboards and the page itself are cached
+        beatleader
+            .leaderboard("abc123", "Rotation90Degrees", "Expert", 1)
+            .await
+            .unwrap();
+        assert_eq!(
+            server.requests(),
+            [
+                "/leaderboards/hash/abc123",
+                "/leaderboard/lb-90?page=2&count=10"
+            ]
+        );
+
+        let err = beatleader
+            .leaderboard(HASH, "Standard", "ExpertPlus", 0)
+            .await
+            .unwrap_err();
+        assert!(ErrorKind::classify(&err).is_not_found());
+    }
+}
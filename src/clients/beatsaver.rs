@@ -0,0 +1,368 @@
+pub mod feed;
+pub mod query;
+
+use std::collections::HashMap;
+
+use anyhow::Context as _;
+use beatsaver_api::models::map::Map;
+use chrono::{DateTime, Utc};
+use log::debug;
+use serde::Deserialize;
+
+use self::query::SearchQuery;
+use super::http::{path_segment, Api, ApiError};
+use crate::{
+    core::config::{BeatSaverConfig, CacheConfig},
+    utils::cache::{CacheStats, TtlCache},
+    Error,
+};
+
+/// BeatSaver takes up to this many hashes in one request.
+const HASH_BATCH_SIZE: usize = 50;
+
+/// BeatSaver lookups with a cache in front.
+///
+/// Everything that talks to BeatSaver should go through this, so requests go
+/// to `beatsaver.api_url` and share one cache.
+pub struct BeatSaver {
+    api: Api,
+    maps: TtlCache<String, Map>,
+    hashes: TtlCache<String, Map>,
+    searches: TtlCache<String, Vec<Map>>,
+    mappers: TtlCache<String, Mapper>,
+}
+
+/// One page of `/search/text` results.
+#[derive(Deserialize)]
+struct SearchPage {
+    docs: Vec<Map>,
+}
+
+/// One page of `/maps/uploader` results, left as they came so the publish
+/// times can be read out too.
+#[derive(Deserialize)]
+struct UploadsPage {
+    docs: Vec<serde_json::Value>,
+}
+
+/// What the map model leaves out about publishing.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PublishTime {
+    last_published_at: Option<DateTime<Utc>>,
+}
+
+impl PublishTime {
+    /// Reads it out of a map's JSON, if it's there.
+    fn of(map: &serde_json::Value) -> Option<DateTime<Utc>> {
+        serde_json::from_value::<Self>(map.clone())
+            .ok()
+            .and_then(|time| time.last_published_at)
+    }
+}
+
+/// A map from a mapper's uploads.
+pub struct Upload {
+    pub map: Map,
+    /// See [`first_published`].
+    pub published_at: DateTime<Utc>,
+}
+
+/// Roughly when a map first went public, since maps can sit unpublished for
+/// ages after they're uploaded.
+///
+/// BeatSaver only says when it was last published, and publishing a new
+/// version moves that forward, so it only counts while there's one version.
+/// Otherwise it's when the map was uploaded.
+pub fn first_published(
+    versions: usize,
+    uploaded: DateTime<Utc>,
+    last_published_at: Option<DateTime<Utc>>,
+) -> DateTime<Utc> {
+    match (versions, last_published_at) {
+        (1, Some(published_at)) => published_at,
+        _ => uploaded,
+    }
+}
+
+/// A BeatSaver user's profile.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Mapper {
+    pub id: u32,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub avatar: String,
+    #[serde(default)]
+    pub verified_mapper: bool,
+    #[serde(default)]
+    pub curator: bool,
+    /// Left out for users who've never uploaded anything.
+    pub stats: Option<MapperStats>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MapperStats {
+    pub total_upvotes: u32,
+    pub total_downvotes: u32,
+    pub total_maps: u32,
+    pub ranked_maps: u32,
+    /// From 0 to 1.
+    pub avg_score: f64,
+    pub first_upload: Option<DateTime<Utc>>,
+    pub last_upload: Option<DateTime<Utc>>,
+}
+
+impl BeatSaver {
+    pub fn new(config: &BeatSaverConfig, cache: &CacheConfig) -> Self {
+        Self {
+            api: Api::new("BeatSaver", &config.api_url),
+            maps: TtlCache::new(cache.maps.capacity, cache.maps.ttl),
+            hashes: TtlCache::new(cache.hashes.capacity, cache.hashes.ttl),
+            searches: TtlCache::new(cache.searches.capacity, cache.searches.ttl),
+            // profiles change about as often as maps do
+            mappers: TtlCache::new(cache.maps.capacity, cache.maps.ttl),
+        }
+    }
+
+    /// Gets a map by its ID (the `!bsr` code).
+    pub async fn map(&self, id: &str) -> Result<Map, Error> {
+        let key = id.trim().to_lowercase();
+        if let Some(map) = self.maps.get(&key) {
+            return Ok(map);
+        }
+
+        debug!("Map cache miss for {key}");
+        let map: Map = self
+            .api
+            .get(&format!("/maps/id/{key}"), &[], || format!("Map {key}"))
+            .await?;
+        let map = self.published(map, || format!("Map {key}"))?;
+        self.maps.insert(key, map.clone());
+
+        Ok(map)
+    }
+
+    /// Gets a map by the hash of one of its versions.
+    pub async fn map_by_hash(&self, hash: &str) -> Result<Map, Error> {
+        let key = hash.trim().to_lowercase();
+        if let Some(map) = self.hashes.get(&key) {
+            return Ok(map);
+        }
+
+        debug!("Hash cache miss for {key}");
+        let map: Map = self
+            .api
+            .get(&format!("/maps/hash/{key}"), &[], || {
+                format!("Map hash {key}")
+            })
+            .await?;
+        let map = self.published(map, || format!("Map hash {key}"))?;
+        self.maps.insert(map.id.to_lowercase(), map.clone());
+        self.hashes.insert(key, map.clone());
+
+        Ok(map)
+    }
+
+    /// Gets the maps for several version hashes at once, keyed by hash.
+    ///
+    /// Hashes BeatSaver doesn't know about, or whose maps were deleted, are
+    /// left out.
+    pub async fn maps_by_hashes(&self, hashes: &[String]) -> Result<HashMap<String, Map>, Error> {
+        let mut found = HashMap::new();
+        let mut missing = vec![];
+
+        for hash in hashes {
+            let key = hash.trim().to_lowercase();
+            match self.hashes.get(&key) {
+                Some(map) => {
+                    found.insert(key, map);
+                }
+                None => missing.push(key),
+            }
+        }
+        missing.sort();
+        missing.dedup();
+
+        for batch in missing.chunks(HASH_BATCH_SIZE) {
+            // one hash gets the map back as-is instead of keyed by hash
+            if let [hash] = batch {
+                match self.map_by_hash(hash).await {
+                    Ok(map) => {
+                        found.insert(hash.clone(), map);
+                    }
+                    Err(err) if ApiError::is_not_found(&err) || ApiError::is_deleted(&err) => {}
+                    Err(err) => return Err(err),
+                }
+                continue;
+            }
+
+            debug!("Looking up {} map hashes", batch.len());
+            let maps: HashMap<String, Option<Map>> = self
+                .api
+                .get(&format!("/maps/hash/{}", batch.join(",")), &[], || {
+                    "Map hashes".to_owned()
+                })
+                .await?;
+            for (hash, map) in maps {
+                let hash = hash.to_lowercase();
+                let Some(map) = map else {
+                    continue;
+                };
+                let Ok(map) = self.published(map, || format!("Map hash {hash}")) else {
+                    continue;
+                };
+
+                self.maps.insert(map.id.to_lowercase(), map.clone());
+                self.hashes.insert(hash.clone(), map.clone());
+                found.insert(hash, map);
+            }
+        }
+
+        Ok(found)
+    }
+
+    /// Searches for maps, filters and all. `page` starts at 0.
+    pub async fn search(&self, query: &SearchQuery, page: u32) -> Result<Vec<Map>, Error> {
+        let mapper_id = match &query.mapper {
+            Some(name) => Some(self.mapper(name).await?.id),
+            None => None,
+        };
+        let params = query.to_params(mapper_id);
+
+        let key = format!(
+            "{page}?{}",
+            params
+                .iter()
+                .map(|(name, value)| format!("{name}={}", value.to_lowercase()))
+                .collect::<Vec<_>>()
+                .join("&")
+        );
+        if let Some(maps) = self.searches.get(&key) {
+            return Ok(maps);
+        }
+
+        debug!("Search cache miss for {key:?}");
+        let maps = self
+            .api
+            .get::<SearchPage>(&format!("/search/text/{page}"), &params, || {
+                "That search".to_owned()
+            })
+            .await?
+            .docs;
+        self.searches.insert(key, maps.clone());
+
+        Ok(maps)
+    }
+
+    /// Gets a user's profile by their name.
+    pub async fn mapper(&self, name: &str) -> Result<Mapper, Error> {
+        let key = name.trim().to_lowercase();
+        if let Some(mapper) = self.mappers.get(&key) {
+            return Ok(mapper);
+        }
+
+        // `.` and `..` would still be read as part of the path, encoded or not
+        if key.chars().all(|c| c == '.') {
+            return Err(self.api.not_found(format!("Mapper {}", name.trim())).into());
+        }
+
+        debug!("Mapper cache miss for {key}");
+        let mapper: Mapper = self
+            .api
+            .get(&format!("/users/name/{}", path_segment(&key)), &[], || {
+                format!("Mapper {}", name.trim())
+            })
+            .await?;
+        self.mappers.insert(key, mapper.clone());
+
+        Ok(mapper)
+    }
+
+    /// Gets a mapper's most recent uploads, newest first.
+    ///
+    /// Never cached, since this is how new uploads get noticed.
+    pub async fn latest_maps(&self, mapper_id: u32) -> Result<Vec<Upload>, Error> {
+        let docs = self
+            .api
+            .get::<UploadsPage>(&format!("/maps/uploader/{mapper_id}/0"), &[], || {
+                format!("Mapper {mapper_id}")
+            })
+            .await?
+            .docs;
+
+        let mut uploads = Vec::with_capacity(docs.len());
+        for doc in docs {
+            let last_published_at = PublishTime::of(&doc);
+            let map: Map = serde_json::from_value(doc)
+                .with_context(|| format!("couldn't parse BeatSaver uploads of {mapper_id}"))?;
+            self.maps.insert(map.id.to_lowercase(), map.clone());
+
+            uploads.push(Upload {
+                published_at: first_published(map.versions.len(), map.uploaded, last_published_at),
+                map,
+            });
+        }
+
+        Ok(uploads)
+    }
+
+    /// Unpublished and deleted maps come back without any versions, which
+    /// nothing that shows a map can do anything with.
+    fn published(&self, map: Map, what: impl FnOnce() -> String) -> Result<Map, ApiError> {
+        if map.versions.is_empty() {
+            return Err(self.api.deleted(what()));
+        }
+
+        Ok(map)
+    }
+
+    /// Downloads a map's cover image, for when we need the image itself.
+    pub async fn cover(&self, url: &str) -> Result<Vec<u8>, Error> {
+        self.api.download(url, || "That cover".to_owned()).await
+    }
+
+    // MARK: Cache management
+
+    /// Drops a map from the cache, by ID or hash.
+    ///
+    /// Returns how many entries were removed.
+    pub fn invalidate(&self, id_or_hash: &str) -> usize {
+        let key = id_or_hash.trim().to_lowercase();
+
+        // hash entries point at the map too, so drop those and remember the IDs
+        let mut ids = vec![key.clone()];
+        let removed_hashes = self.hashes.retain(|hash, map| {
+            let matches = hash == &key || map.id.eq_ignore_ascii_case(&key);
+            if matches {
+                ids.push(map.id.to_lowercase());
+            }
+            !matches
+        });
+
+        removed_hashes + ids.iter().filter(|id| self.maps.remove(*id)).count()
+    }
+
+    /// Drops every cached search result.
+    pub fn invalidate_searches(&self) -> usize {
+        self.searches.retain(|_, _| false)
+    }
+
+    pub fn clear(&self) {
+        self.maps.clear();
+        self.hashes.clear();
+        self.searches.clear();
+        self.mappers.clear();
+    }
+
+    pub fn stats(&self) -> [(&'static str, CacheStats); 4] {
+        [
+            ("Maps", self.maps.stats()),
+            ("Hashes", self.hashes.stats()),
+            ("Searches", self.searches.stats()),
+            ("Mappers", self.mappers.stats()),
+        ]
+    }
+}
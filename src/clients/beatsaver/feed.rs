@@ -0,0 +1,530 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context as _;
+use beatsaver_api::models::map::Map;
+use chrono::{DateTime, Utc};
+use futures::{SinkExt, StreamExt};
+use log::{debug, info, warn};
+use serde::Deserialize;
+use tokio::{sync::broadcast, time::Instant};
+use tokio_tungstenite::tungstenite::Message;
+
+use super::{first_published, PublishTime};
+use crate::{core::shutdown::Shutdown, utils::cache::TtlCache, Error};
+
+/// How many events a slow subscriber can fall behind before missing some.
+const EVENT_BUFFER: usize = 256;
+/// Maps we remember the last state of, to tell what changed.
+const STATE_CAPACITY: usize = 20_000;
+const STATE_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// Maps first seen this soon after being published count as newly published.
+const RECENT: chrono::TimeDelta = chrono::TimeDelta::minutes(10);
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+/// A connection that lasted this long was fine, so start backing off from scratch.
+const HEALTHY_CONNECTION: Duration = Duration::from_secs(60);
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+/// Reconnect if nothing at all (pongs included) arrives for this long.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Something that happened to a map on BeatSaver.
+#[derive(Clone, Debug)]
+pub enum FeedEvent {
+    /// A map went up for the first time, and when.
+    Published(Arc<Map>, DateTime<Utc>),
+    /// A new version, or its details changed.
+    Updated(Arc<Map>),
+    /// Deleted or unpublished, by map ID.
+    Deleted(String),
+    Curated(Arc<Map>),
+    /// Ranked on ScoreSaber or BeatLeader.
+    Ranked(Arc<Map>),
+}
+
+/// What we last saw of a map.
+#[derive(Clone, PartialEq, Eq)]
+struct MapState {
+    hash: String,
+    curated: bool,
+    ranked: bool,
+}
+
+impl MapState {
+    fn of(map: &Map) -> Option<Self> {
+        Some(Self {
+            hash: map.versions.first()?.hash.clone(),
+            curated: map.curated_at.is_some(),
+            ranked: map.ss_ranked || map.bl_ranked,
+        })
+    }
+}
+
+/// A message off the feed, e.g. `{"type": "MAP_UPDATE", "msg": {...}}`.
+#[derive(Deserialize)]
+struct FeedMessage {
+    #[serde(rename = "type")]
+    kind: String,
+    msg: serde_json::Value,
+}
+
+/// BeatSaver's live map feed, turned into [`FeedEvent`]s.
+///
+/// Call [`BeatSaverFeed::subscribe`] for a receiver, then run the feed with
+/// [`BeatSaverFeed::run`]. It reconnects by itself until shutdown.
+pub struct BeatSaverFeed {
+    url: String,
+    events: broadcast::Sender<FeedEvent>,
+    states: TtlCache<String, MapState>,
+}
+
+impl BeatSaverFeed {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_owned(),
+            events: broadcast::channel(EVENT_BUFFER).0,
+            states: TtlCache::new(STATE_CAPACITY, STATE_TTL),
+        }
+    }
+
+    /// Gets every event from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<FeedEvent> {
+        self.events.subscribe()
+    }
+
+    /// Stays connected until shutdown, backing off when the connection fails.
+    pub async fn run(self: Arc<Self>, shutdown: Shutdown) {
+        let mut backoff = MIN_BACKOFF;
+
+        loop {
+            let started = Instant::now();
+            let res = tokio::select! {
+                res = self.connect() => res,
+                _ = shutdown.cancelled() => break,
+            };
+            match res {
+                Ok(()) => info!("BeatSaver feed closed, reconnecting"),
+                Err(err) => warn!("BeatSaver feed failed: {err:#}"),
+            }
+
+            if started.elapsed() >= HEALTHY_CONNECTION {
+                backoff = MIN_BACKOFF;
+            }
+            debug!("Reconnecting to the BeatSaver feed in {backoff:?}");
+            tokio::select! {
+                _ = tokio::time::sleep(backoff) => {},
+                _ = shutdown.cancelled() => break,
+            }
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+
+        debug!("BeatSaver feed stopped");
+    }
+
+    /// One connection, until it closes or goes quiet.
+    async fn connect(&self) -> Result<(), Error> {
+        let (mut socket, _) = tokio_tungstenite::connect_async(self.url.as_str())
+            .await
+            .with_context(|| format!("couldn't connect to {}", self.url))?;
+        info!("Connected to the BeatSaver feed");
+
+        let mut ping = tokio::time::interval(PING_INTERVAL);
+        let mut last_heard = Instant::now();
+
+        loop {
+            let message = tokio::select! {
+                message = socket.next() => message,
+                _ = ping.tick() => {
+                    if last_heard.elapsed() >= IDLE_TIMEOUT {
+                        anyhow::bail!("nothing heard for {IDLE_TIMEOUT:?}");
+                    }
+                    socket.send(Message::Ping(Default::default())).await?;
+                    continue;
+                }
+            };
+            let Some(message) = message else {
+                return Ok(());
+            };
+            last_heard = Instant::now();
+
+            match message? {
+                Message::Text(text) => self.handle(text.as_str()),
+                Message::Close(_) => return Ok(()),
+                // pings are answered by tungstenite itself
+                _ => {}
+            }
+        }
+    }
+
+    fn handle(&self, text: &str) {
+        let message: FeedMessage = match serde_json::from_str(text) {
+            Ok(message) => message,
+            Err(err) => {
+                warn!("Couldn't parse BeatSaver feed message: {err}");
+                return;
+            }
+        };
+
+        let events = match message.kind.as_str() {
+            "MAP_UPDATE" => {
+                let published_at = PublishTime::of(&message.msg);
+                match serde_json::from_value::<Map>(message.msg) {
+                    Ok(map) => self.events_for(map, published_at, Utc::now()),
+                    Err(err) => {
+                        warn!("Couldn't parse map from the BeatSaver feed: {err}");
+                        return;
+                    }
+                }
+            }
+            "MAP_DELETE" => match message.msg.as_str() {
+                Some(id) => {
+                    self.states.remove(&id.to_lowercase());
+                    vec![FeedEvent::Deleted(id.to_lowercase())]
+                }
+                None => return,
+            },
+            kind => {
+                debug!("Ignoring {kind} from the BeatSaver feed");
+                return;
+            }
+        };
+
+        for event in events {
+            debug!("BeatSaver feed: {event:?}");
+            // no receivers just means nothing's listening yet
+            let _ = self.events.send(event);
+        }
+    }
+
+    /// Works out what happened to a map by comparing it to what we saw last.
+    ///
+    /// Maps we haven't seen before only count as published or curated if
+    /// that happened recently, and can't be told apart from being ranked.
+    /// `published_at` is when it was last published, if the update said.
+    fn events_for(
+        &self,
+        map: Map,
+        published_at: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Vec<FeedEvent> {
+        let id = map.id.to_lowercase();
+        let Some(state) = MapState::of(&map) else {
+            // unpublished maps come back without any versions
+            self.states.remove(&id);
+            return vec![FeedEvent::Deleted(id)];
+        };
+        let previous = self.states.get(&id);
+        self.states.insert(id, state.clone());
+
+        // new versions get published again, so only a map's first one counts
+        let first_published = first_published(map.versions.len(), map.uploaded, published_at);
+        let newly_published = map.versions.len() == 1 && now - first_published <= RECENT;
+
+        let map = Arc::new(map);
+        let mut events = vec![];
+        match &previous {
+            None if newly_published => {
+                events.push(FeedEvent::Published(map.clone(), first_published))
+            }
+            Some(previous) if previous.hash != state.hash => {
+                events.push(FeedEvent::Updated(map.clone()))
+            }
+            _ => {}
+        }
+
+        let newly_curated = match &previous {
+            Some(previous) => state.curated && !previous.curated,
+            None => map.curated_at.is_some_and(|at| now - at <= RECENT),
+        };
+        if newly_curated {
+            events.push(FeedEvent::Curated(map.clone()));
+        }
+        if previous.is_some_and(|previous| state.ranked && !previous.ranked) {
+            events.push(FeedEvent::Ranked(map.clone()));
+        }
+
+        if events.is_empty() {
+            events.push(FeedEvent::Updated(map));
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use serde_json::{json, Value};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(secs, 0).unwrap()
+    }
+
+    /// Roughly what BeatSaver sends for a map, newest version first.
+    fn map_json(hashes: &[&str], uploaded: i64, curated: bool, ranked: bool) -> Value {
+        let versions: Vec<Value> = hashes
+            .iter()
+            .map(|hash| {
+                json!({
+                    "hash": hash,
+                    "key": "25f",
+                    "state": "Published",
+                    "createdAt": at(uploaded),
+                    "sageScore": 0,
+                    "diffs": [{
+                        "njs": 16.0,
+                        "offset": 0.0,
+                        "notes": 500,
+                        "bombs": 0,
+                        "obstacles": 10,
+                        "nps": 4.2,
+                        "length": 256.0,
+                        "characteristic": "Standard",
+                        "difficulty": "Expert",
+                        "events": 1000,
+                        "chroma": false,
+                        "me": false,
+                        "ne": false,
+                        "cinema": false,
+                        "seconds": 120.0,
+                        "paritySummary": {"errors": 0, "warns": 0, "resets": 0},
+                        "maxScore": 450_000
+                    }],
+                    "downloadURL": format!("https://r2cdn.beatsaver.com/{hash}.zip"),
+                    "coverURL": format!("https://cdn.beatsaver.com/{hash}.jpg"),
+                    "previewURL": format!("https://cdn.beatsaver.com/{hash}.mp3")
+                })
+            })
+            .collect();
+
+        let mut map = json!({
+            "id": "25f",
+            "name": "Map",
+            "description": "",
+            "uploader": {
+                "id": 4284,
+                "name": "Mapper",
+                "hash": "5cff0b7298cd0e0001a5d8b2",
+                "avatar": "https://cdn.beatsaver.com/avatar/4284.png",
+                "type": "SIMPLE",
+                "admin": false,
+                "curator": false,
+                "verifiedMapper": false,
+                "playlistUrl": "https://api.beatsaver.com/users/id/4284/playlist"
+            },
+            "metadata": {
+                "bpm": 120.0,
+                "duration": 120,
+                "songName": "Song",
+                "songSubName": "",
+                "songAuthorName": "Artist",
+                "levelAuthorName": "Mapper"
+            },
+            "stats": {
+                "plays": 0,
+                "downloads": 0,
+                "upvotes": 1,
+                "downvotes": 0,
+                "score": 0.5,
+                "reviews": 0,
+                "sentiment": "PENDING"
+            },
+            "uploaded": at(uploaded),
+            "automapper": false,
+            "ranked": ranked,
+            "ssRanked": ranked,
+            "qualified": false,
+            "blRanked": false,
+            "blQualified": false,
+            "versions": versions,
+            "createdAt": at(uploaded),
+            "updatedAt": at(uploaded),
+            "tags": [],
+            "declaredAi": "None"
+        });
+        if curated {
+            map["curatedAt"] = json!(at(uploaded));
+        }
+        map
+    }
+
+    fn map(hashes: &[&str], uploaded: i64) -> Map {
+        serde_json::from_value(map_json(hashes, uploaded, false, false)).unwrap()
+    }
+
+    fn kinds(events: &[FeedEvent]) -> Vec<&'static str> {
+        events
+            .iter()
+            .map(|event| match event {
+                FeedEvent::Published(..) => "published",
+                FeedEvent::Updated(_) => "updated",
+                FeedEvent::Deleted(_) => "deleted",
+                FeedEvent::Curated(_) => "curated",
+                FeedEvent::Ranked(_) => "ranked",
+            })
+            .collect()
+    }
+
+    const NOW: i64 = 1_800_000_000;
+
+    #[test]
+    fn publish() {
+        let feed = BeatSaverFeed::new("ws://unused");
+
+        let events = feed.events_for(map(&["a"], NOW - 60), None, at(NOW));
+        assert_eq!(kinds(&events), ["published"]);
+        let FeedEvent::Published(_, published_at) = &events[0] else {
+            unreachable!();
+        };
+        assert_eq!(*published_at, at(NOW - 60));
+
+        // the second time it's not news
+        let events = feed.events_for(map(&["a"], NOW - 60), None, at(NOW));
+        assert_eq!(kinds(&events), ["updated"]);
+    }
+
+    #[test]
+    fn wip_published_later_counts_as_published() {
+        let feed = BeatSaverFeed::new("ws://unused");
+        let uploaded = NOW - 30 * 24 * 60 * 60;
+
+        let events = feed.events_for(map(&["a"], uploaded), Some(at(NOW - 60)), at(NOW));
+        assert_eq!(kinds(&events), ["published"]);
+        let FeedEvent::Published(_, published_at) = &events[0] else {
+            unreachable!();
+        };
+        assert_eq!(*published_at, at(NOW - 60));
+    }
+
+    #[test]
+    fn old_or_republished_maps_are_just_updates() {
+        let feed = BeatSaverFeed::new("ws://unused");
+        let long_ago = NOW - 30 * 24 * 60 * 60;
+
+        // published long ago, first seen now
+        let events = feed.events_for(map(&["a"], long_ago), Some(at(long_ago)), at(NOW));
+        assert_eq!(kinds(&events), ["updated"]);
+
+        // a second version gets published again, but the map isn't new
+        let events = feed.events_for(map(&["c", "b"], long_ago), Some(at(NOW - 60)), at(NOW));
+        assert_eq!(kinds(&events), ["updated"]);
+    }
+
+    #[test]
+    fn update() {
+        let feed = BeatSaverFeed::new("ws://unused");
+        let long_ago = NOW - 30 * 24 * 60 * 60;
+        feed.events_for(map(&["a"], long_ago), None, at(NOW));
+
+        let events = feed.events_for(map(&["b", "a"], long_ago), Some(at(NOW)), at(NOW));
+        assert_eq!(kinds(&events), ["updated"]);
+    }
+
+    #[test]
+    fn delete() {
+        let feed = BeatSaverFeed::new("ws://unused");
+        feed.events_for(map(&["a"], NOW - 60), None, at(NOW));
+
+        // unpublishing leaves the map without versions
+        let events = feed.events_for(map(&[], NOW - 60), None, at(NOW));
+        assert_eq!(kinds(&events), ["deleted"]);
+
+        let mut events = feed.subscribe();
+        feed.handle(&json!({"type": "MAP_DELETE", "msg": "25F"}).to_string());
+        match events.try_recv().unwrap() {
+            FeedEvent::Deleted(id) => assert_eq!(id, "25f"),
+            event => panic!("expected a delete, got {event:?}"),
+        }
+    }
+
+    #[test]
+    fn curate() {
+        let feed = BeatSaverFeed::new("ws://unused");
+        let long_ago = NOW - 30 * 24 * 60 * 60;
+        let curated = |curated| -> Map {
+            serde_json::from_value(map_json(&["a"], long_ago, curated, false)).unwrap()
+        };
+
+        // curated ages ago, so that's old news
+        let events = feed.events_for(curated(true), None, at(NOW));
+        assert_eq!(kinds(&events), ["updated"]);
+
+        let feed = BeatSaverFeed::new("ws://unused");
+        feed.events_for(curated(false), None, at(NOW));
+        let events = feed.events_for(curated(true), None, at(NOW));
+        assert_eq!(kinds(&events), ["curated"]);
+    }
+
+    #[test]
+    fn rank() {
+        let feed = BeatSaverFeed::new("ws://unused");
+        let long_ago = NOW - 30 * 24 * 60 * 60;
+        let ranked = |ranked| -> Map {
+            serde_json::from_value(map_json(&["a"], long_ago, false, ranked)).unwrap()
+        };
+
+        // can't tell when an unseen map got ranked
+        let events = feed.events_for(ranked(true), None, at(NOW));
+        assert_eq!(kinds(&events), ["updated"]);
+
+        let feed = BeatSaverFeed::new("ws://unused");
+        feed.events_for(ranked(false), None, at(NOW));
+        let events = feed.events_for(ranked(true), None, at(NOW));
+        assert_eq!(kinds(&events), ["ranked"]);
+    }
+
+    #[test]
+    fn publish_time_comes_from_the_update() {
+        let feed = BeatSaverFeed::new("ws://unused");
+        let mut events = feed.subscribe();
+
+        let uploaded = Utc::now().timestamp() - 30 * 24 * 60 * 60;
+        let mut msg = map_json(&["a"], uploaded, false, false);
+        msg["lastPublishedAt"] = json!(Utc::now());
+        feed.handle(&json!({"type": "MAP_UPDATE", "msg": msg}).to_string());
+
+        assert!(matches!(events.try_recv(), Ok(FeedEvent::Published(..))));
+    }
+
+    #[tokio::test]
+    async fn reconnects_after_the_server_drops() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        // each connection gets one delete and is then closed
+        tokio::spawn(async move {
+            for id in ["1", "2"] {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+                socket.send(Message::text("not json")).await.unwrap();
+                let delete = json!({"type": "MAP_DELETE", "msg": id});
+                socket
+                    .send(Message::text(delete.to_string()))
+                    .await
+                    .unwrap();
+                socket.close(None).await.unwrap();
+                while let Some(Ok(_)) = socket.next().await {}
+            }
+        });
+
+        let feed = Arc::new(BeatSaverFeed::new(&url));
+        let mut events = feed.subscribe();
+        let shutdown = Shutdown::new();
+        tokio::spawn(feed.run(shutdown.clone()));
+
+        for expected in ["1", "2"] {
+            let event = tokio::time::timeout(Duration::from_secs(10), events.recv())
+                .await
+                .expect("the feed should reconnect")
+                .unwrap();
+            match event {
+                FeedEvent::Deleted(id) => assert_eq!(id, expected),
+                event => panic!("expected a delete, got {event:?}"),
+            }
+        }
+
+        shutdown.trigger();
+    }
+}
@@ -0,0 +1,684 @@
+use chrono::NaiveDate;
+
+/// Filters that can be written into a search, e.g. `nps:>8 tag:tech camellia`.
+pub const FILTER_HELP: &str = "`mapper:name` `nps:>8` `bpm:150..200` `duration:<2:30` \
+    `rating:>80` `ranked:true` `curated:true` `verified:true` `tag:tech` `-tag:dance` \
+    `after:2024-01-01` `before:2024-06-01` `sort:rating` `-automapper`";
+
+/// Something wrong with a search query, worded for whoever typed it.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{message}")]
+pub struct QueryError {
+    pub message: String,
+}
+
+impl QueryError {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+/// An inclusive range, either end of which can be left open.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Range {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+/// How BeatSaver should order results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Relevance,
+    Latest,
+    Rating,
+    Curated,
+    Duration,
+    Random,
+}
+
+impl SortOrder {
+    const ALL: [(&'static str, Self); 6] = [
+        ("relevance", Self::Relevance),
+        ("latest", Self::Latest),
+        ("rating", Self::Rating),
+        ("curated", Self::Curated),
+        ("duration", Self::Duration),
+        ("random", Self::Random),
+    ];
+
+    /// The name BeatSaver's API uses.
+    fn api_name(self) -> &'static str {
+        match self {
+            Self::Relevance => "Relevance",
+            Self::Latest => "Latest",
+            Self::Rating => "Rating",
+            Self::Curated => "Curated",
+            Self::Duration => "Duration",
+            Self::Random => "Random",
+        }
+    }
+}
+
+/// Which leaderboards a map has to be ranked on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Leaderboard {
+    Any,
+    ScoreSaber,
+    BeatLeader,
+}
+
+/// A parsed search: free text plus filters.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    pub text: String,
+    pub mapper: Option<String>,
+    pub nps: Range,
+    pub bpm: Range,
+    /// In seconds.
+    pub duration: Range,
+    /// From 0 to 1.
+    pub rating: Range,
+    pub ranked: Option<Leaderboard>,
+    pub curated: Option<bool>,
+    pub verified: Option<bool>,
+    pub automapper: Option<bool>,
+    pub tags: Vec<String>,
+    pub excluded_tags: Vec<String>,
+    pub after: Option<NaiveDate>,
+    pub before: Option<NaiveDate>,
+    pub sort: Option<SortOrder>,
+}
+
+impl SearchQuery {
+    /// Parses a query like `camellia nps:>8 sort:rating`.
+    ///
+    /// Words that aren't filters (including ones with colons, like
+    /// `Re:Zero`) end up in the free text.
+    pub fn parse(input: &str) -> Result<Self, QueryError> {
+        let mut query = Self::default();
+        let mut text: Vec<String> = vec![];
+
+        for token in tokenize(input)? {
+            // quoted bits are always text
+            if token.starts_with('"') {
+                let quoted = token.trim_matches('"');
+                if !quoted.is_empty() {
+                    text.push(quoted.to_owned());
+                }
+                continue;
+            }
+
+            let (negated, body) = match token.strip_prefix('-') {
+                Some(body) if !body.is_empty() => (true, body),
+                _ => (false, token.as_str()),
+            };
+
+            // bare flags
+            match (negated, body.to_lowercase().as_str()) {
+                (true, "automapper") => {
+                    query.automapper = Some(false);
+                    continue;
+                }
+                (false, "+automapper") => {
+                    query.automapper = Some(true);
+                    continue;
+                }
+                _ => {}
+            }
+
+            let Some((key, value)) = body.split_once(':') else {
+                text.push(token);
+                continue;
+            };
+            let key = key.to_lowercase();
+
+            if negated {
+                match key.as_str() {
+                    "tag" => {
+                        query.excluded_tags.push(parse_tag(value)?);
+                        continue;
+                    }
+                    "ranked" | "curated" | "verified" | "automapper" => {
+                        return Err(QueryError::new(format!(
+                            "`-{key}:` isn't a thing, use `{key}:false` instead"
+                        )));
+                    }
+                    _ => {
+                        text.push(token);
+                        continue;
+                    }
+                }
+            }
+
+            match key.as_str() {
+                "mapper" => query.mapper = Some(parse_mapper(value)?),
+                "nps" => query.nps = parse_range(&key, value, parse_number)?,
+                "bpm" => query.bpm = parse_range(&key, value, parse_number)?,
+                "duration" | "length" => query.duration = parse_range(&key, value, parse_duration)?,
+                "rating" => query.rating = parse_range(&key, value, parse_rating)?,
+                "ranked" => query.ranked = parse_ranked(value)?,
+                "curated" => query.curated = Some(parse_bool(&key, value)?),
+                "verified" => query.verified = Some(parse_bool(&key, value)?),
+                "automapper" => query.automapper = Some(parse_bool(&key, value)?),
+                "tag" => query.tags.push(parse_tag(value)?),
+                "after" | "from" => query.after = Some(parse_date(&key, value)?),
+                "before" | "to" => query.before = Some(parse_date(&key, value)?),
+                "sort" | "order" => query.sort = Some(parse_sort(value)?),
+                // not a filter, probably part of a title
+                _ => text.push(token),
+            }
+        }
+
+        if let (Some(after), Some(before)) = (query.after, query.before) {
+            if after > before {
+                return Err(QueryError::new(format!(
+                    "`after:{after}` is later than `before:{before}`, so nothing can match"
+                )));
+            }
+        }
+
+        query.text = text.join(" ");
+        Ok(query)
+    }
+
+    /// Turns this into query parameters for BeatSaver's `/search/text` endpoint.
+    ///
+    /// The mapper has to be looked up separately, since BeatSaver wants IDs.
+    pub fn to_params(&self, mapper_id: Option<u32>) -> Vec<(&'static str, String)> {
+        let mut params: Vec<(&'static str, String)> = vec![];
+
+        if !self.text.is_empty() {
+            params.push(("q", self.text.clone()));
+        }
+        if let Some(mapper_id) = mapper_id {
+            params.push(("mappers", mapper_id.to_string()));
+        }
+
+        push_range(&mut params, ("minNps", "maxNps"), self.nps, |n| {
+            n.to_string()
+        });
+        push_range(&mut params, ("minBpm", "maxBpm"), self.bpm, |n| {
+            n.to_string()
+        });
+        push_range(
+            &mut params,
+            ("minDuration", "maxDuration"),
+            self.duration,
+            |n| (n.round() as i64).to_string(),
+        );
+        push_range(&mut params, ("minRating", "maxRating"), self.rating, |n| {
+            n.to_string()
+        });
+
+        if let Some(leaderboard) = self.ranked {
+            let value = match leaderboard {
+                Leaderboard::Any => "Ranked",
+                Leaderboard::ScoreSaber => "ScoreSaber",
+                Leaderboard::BeatLeader => "BeatLeader",
+            };
+            params.push(("leaderboard", value.to_owned()));
+        }
+        if let Some(curated) = self.curated {
+            params.push(("curated", curated.to_string()));
+        }
+        if let Some(verified) = self.verified {
+            params.push(("verified", verified.to_string()));
+        }
+        if let Some(automapper) = self.automapper {
+            params.push(("automapper", automapper.to_string()));
+        }
+
+        let tags: Vec<String> = self
+            .tags
+            .iter()
+            .cloned()
+            .chain(self.excluded_tags.iter().map(|tag| format!("!{tag}")))
+            .collect();
+        if !tags.is_empty() {
+            params.push(("tags", tags.join(",")));
+        }
+
+        if let Some(after) = self.after {
+            params.push(("from", format!("{after}T00:00:00+00:00")));
+        }
+        if let Some(before) = self.before {
+            params.push(("to", format!("{before}T00:00:00+00:00")));
+        }
+        if let Some(sort) = self.sort {
+            params.push(("order", sort.api_name().to_owned()));
+        }
+
+        params
+    }
+}
+
+// MARK: Token parsers
+
+/// Splits on whitespace, keeping `"quoted bits"` together (quotes included).
+fn tokenize(input: &str) -> Result<Vec<String>, QueryError> {
+    let mut tokens = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in input.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(QueryError::new("there's a `\"` without a closing one"));
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    Ok(tokens)
+}
+
+fn non_empty<'a>(key: &str, value: &'a str) -> Result<&'a str, QueryError> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(QueryError::new(format!("`{key}:` needs a value")));
+    }
+
+    Ok(value)
+}
+
+fn parse_range(
+    key: &str,
+    value: &str,
+    parse: fn(&str) -> Option<f64>,
+) -> Result<Range, QueryError> {
+    let invalid = || {
+        QueryError::new(format!(
+            "`{key}:{value}` isn't a valid range, try `{key}:>8`, `{key}:<8` or `{key}:6..10`"
+        ))
+    };
+    let value = non_empty(key, value)?;
+
+    let range = if let Some(min) = value.strip_prefix(">=").or(value.strip_prefix('>')) {
+        Range {
+            min: Some(parse(min).ok_or_else(invalid)?),
+            max: None,
+        }
+    } else if let Some(max) = value.strip_prefix("<=").or(value.strip_prefix('<')) {
+        Range {
+            min: None,
+            max: Some(parse(max).ok_or_else(invalid)?),
+        }
+    } else if let Some((min, max)) = value.split_once("..") {
+        let min = match min {
+            "" => None,
+            min => Some(parse(min).ok_or_else(invalid)?),
+        };
+        let max = match max {
+            "" => None,
+            max => Some(parse(max).ok_or_else(invalid)?),
+        };
+        Range { min, max }
+    } else {
+        let exact = parse(value).ok_or_else(invalid)?;
+        Range {
+            min: Some(exact),
+            max: Some(exact),
+        }
+    };
+
+    if let (Some(min), Some(max)) = (range.min, range.max) {
+        if min > max {
+            return Err(QueryError::new(format!(
+                "`{key}:{value}` goes from high to low, flip it around"
+            )));
+        }
+    }
+
+    Ok(range)
+}
+
+fn parse_number(value: &str) -> Option<f64> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite() && *n >= 0.0)
+}
+
+/// Seconds, or `m:ss`.
+fn parse_duration(value: &str) -> Option<f64> {
+    match value.split_once(':') {
+        Some((minutes, seconds)) => {
+            let minutes = minutes.parse::<u32>().ok()?;
+            let seconds = seconds.parse::<u32>().ok().filter(|s| *s < 60)?;
+            // absurd lengths are just invalid, not a panic
+            let total = minutes.checked_mul(60)?.checked_add(seconds)?;
+            Some(f64::from(total))
+        }
+        None => parse_number(value),
+    }
+}
+
+/// A percentage, `80` or `80%`, turned into 0 to 1.
+fn parse_rating(value: &str) -> Option<f64> {
+    parse_number(value.trim_end_matches('%'))
+        .filter(|n| *n <= 100.0)
+        .map(|n| n / 100.0)
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, QueryError> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "y" | "1" => Ok(true),
+        "false" | "no" | "n" | "0" => Ok(false),
+        _ => Err(QueryError::new(format!(
+            "`{key}:` takes `true` or `false`, not `{value}`"
+        ))),
+    }
+}
+
+fn parse_ranked(value: &str) -> Result<Option<Leaderboard>, QueryError> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "y" | "1" => Ok(Some(Leaderboard::Any)),
+        "ss" | "scoresaber" => Ok(Some(Leaderboard::ScoreSaber)),
+        "bl" | "beatleader" => Ok(Some(Leaderboard::BeatLeader)),
+        "false" | "no" | "n" | "0" => Err(QueryError::new(
+            "BeatSaver can't search for unranked maps only, leave out `ranked:` instead",
+        )),
+        _ => Err(QueryError::new(format!(
+            "`ranked:` takes `true`, `scoresaber` or `beatleader`, not `{value}`"
+        ))),
+    }
+}
+
+fn parse_mapper(value: &str) -> Result<String, QueryError> {
+    let name = non_empty("mapper", value)?;
+    // BeatSaver usernames are limited to these
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+    {
+        return Err(QueryError::new(format!(
+            "`{name}` can't be a BeatSaver username, they only have letters, numbers, `.`, `_` and `-`"
+        )));
+    }
+
+    Ok(name.to_owned())
+}
+
+fn parse_tag(value: &str) -> Result<String, QueryError> {
+    let tag = non_empty("tag", value)?.to_lowercase();
+    if !tag
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(QueryError::new(format!(
+            "`{value}` doesn't look like a BeatSaver tag"
+        )));
+    }
+
+    Ok(tag)
+}
+
+fn parse_date(key: &str, value: &str) -> Result<NaiveDate, QueryError> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+        QueryError::new(format!(
+            "`{key}:{value}` isn't a date, write it like `{key}:2024-01-31`"
+        ))
+    })
+}
+
+fn parse_sort(value: &str) -> Result<SortOrder, QueryError> {
+    SortOrder::ALL
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(value))
+        .map(|(_, order)| *order)
+        .ok_or_else(|| {
+            let names: Vec<&str> = SortOrder::ALL.iter().map(|(name, _)| *name).collect();
+            QueryError::new(format!(
+                "`sort:{value}` isn't an option, try one of {}",
+                names.join(", ")
+            ))
+        })
+}
+
+fn push_range(
+    params: &mut Vec<(&'static str, String)>,
+    (min_key, max_key): (&'static str, &'static str),
+    range: Range,
+    format: fn(f64) -> String,
+) {
+    if let Some(min) = range.min {
+        params.push((min_key, format(min)));
+    }
+    if let Some(max) = range.max {
+        params.push((max_key, format(max)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> SearchQuery {
+        SearchQuery::parse(input).unwrap_or_else(|err| panic!("{input:?} failed: {err}"))
+    }
+
+    fn error(input: &str) -> String {
+        match SearchQuery::parse(input) {
+            Ok(query) => panic!("{input:?} parsed as {query:?}"),
+            Err(err) => err.message,
+        }
+    }
+
+    fn range(min: Option<f64>, max: Option<f64>) -> Range {
+        Range { min, max }
+    }
+
+    #[test]
+    fn plain_text() {
+        let query = parse("  camellia   ghost ");
+        assert_eq!(query.text, "camellia ghost");
+        assert_eq!(
+            query,
+            SearchQuery {
+                text: "camellia ghost".to_owned(),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn every_filter() {
+        let query = parse(
+            "camellia mapper:some.one nps:>8 bpm:150..200 duration:<2:30 rating:>=80% \
+             ranked:bl curated:yes verified:false -automapper tag:Tech -tag:dance \
+             after:2024-01-01 before:2024-06-01 sort:Rating",
+        );
+
+        assert_eq!(query.text, "camellia");
+        assert_eq!(query.mapper.as_deref(), Some("some.one"));
+        assert_eq!(query.nps, range(Some(8.0), None));
+        assert_eq!(query.bpm, range(Some(150.0), Some(200.0)));
+        assert_eq!(query.duration, range(None, Some(150.0)));
+        assert_eq!(query.rating, range(Some(0.8), None));
+        assert_eq!(query.ranked, Some(Leaderboard::BeatLeader));
+        assert_eq!(query.curated, Some(true));
+        assert_eq!(query.verified, Some(false));
+        assert_eq!(query.automapper, Some(false));
+        assert_eq!(query.tags, ["tech"]);
+        assert_eq!(query.excluded_tags, ["dance"]);
+        assert_eq!(query.after, NaiveDate::from_ymd_opt(2024, 1, 1));
+        assert_eq!(query.before, NaiveDate::from_ymd_opt(2024, 6, 1));
+        assert_eq!(query.sort, Some(SortOrder::Rating));
+    }
+
+    #[test]
+    fn filter_aliases() {
+        let query = parse("length:90 from:2024-01-01 to:2024-02-01 order:random +automapper");
+        assert_eq!(query.duration, range(Some(90.0), Some(90.0)));
+        assert_eq!(query.after, NaiveDate::from_ymd_opt(2024, 1, 1));
+        assert_eq!(query.before, NaiveDate::from_ymd_opt(2024, 2, 1));
+        assert_eq!(query.sort, Some(SortOrder::Random));
+        assert_eq!(query.automapper, Some(true));
+
+        assert_eq!(parse("ranked:true").ranked, Some(Leaderboard::Any));
+        assert_eq!(parse("ranked:ss").ranked, Some(Leaderboard::ScoreSaber));
+        assert_eq!(parse("automapper:1").automapper, Some(true));
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(parse("nps:>=8").nps, range(Some(8.0), None));
+        assert_eq!(parse("nps:<=8").nps, range(None, Some(8.0)));
+        assert_eq!(parse("nps:<8.5").nps, range(None, Some(8.5)));
+        assert_eq!(parse("nps:6..").nps, range(Some(6.0), None));
+        assert_eq!(parse("nps:..10").nps, range(None, Some(10.0)));
+        assert_eq!(parse("nps:7").nps, range(Some(7.0), Some(7.0)));
+        assert_eq!(parse("bpm:150..150").bpm, range(Some(150.0), Some(150.0)));
+        assert_eq!(
+            parse("duration:1:05..150").duration,
+            range(Some(65.0), Some(150.0))
+        );
+        assert_eq!(parse("rating:50..90").rating, range(Some(0.5), Some(0.9)));
+    }
+
+    #[test]
+    fn quoted_text() {
+        let query = parse(r#""tag:not a filter" nps:>8 "" "two  spaces""#);
+        assert_eq!(query.text, "tag:not a filter two  spaces");
+        assert!(query.tags.is_empty());
+        assert_eq!(query.nps, range(Some(8.0), None));
+    }
+
+    #[test]
+    fn unknown_filters_are_text() {
+        let query = parse("Re:Zero -foo:bar - -tag:tech");
+        assert_eq!(query.text, "Re:Zero -foo:bar -");
+        assert_eq!(query.excluded_tags, ["tech"]);
+    }
+
+    #[test]
+    fn error_messages() {
+        assert_eq!(error(r#"say "hi"#), "there's a `\"` without a closing one");
+        assert_eq!(error("nps:"), "`nps:` needs a value");
+        assert_eq!(
+            error("nps:fast"),
+            "`nps:fast` isn't a valid range, try `nps:>8`, `nps:<8` or `nps:6..10`"
+        );
+        assert_eq!(
+            error("nps:-1"),
+            "`nps:-1` isn't a valid range, try `nps:>8`, `nps:<8` or `nps:6..10`"
+        );
+        assert_eq!(
+            error("bpm:200..150"),
+            "`bpm:200..150` goes from high to low, flip it around"
+        );
+        assert_eq!(
+            error("duration:1:60"),
+            "`duration:1:60` isn't a valid range, try `duration:>8`, `duration:<8` or `duration:6..10`"
+        );
+        assert_eq!(
+            error("rating:>120"),
+            "`rating:>120` isn't a valid range, try `rating:>8`, `rating:<8` or `rating:6..10`"
+        );
+        assert_eq!(
+            error("curated:maybe"),
+            "`curated:` takes `true` or `false`, not `maybe`"
+        );
+        assert_eq!(
+            error("-ranked:true"),
+            "`-ranked:` isn't a thing, use `ranked:false` instead"
+        );
+        assert_eq!(
+            error("ranked:false"),
+            "BeatSaver can't search for unranked maps only, leave out `ranked:` instead"
+        );
+        assert_eq!(
+            error("ranked:osu"),
+            "`ranked:` takes `true`, `scoresaber` or `beatleader`, not `osu`"
+        );
+        assert_eq!(
+            error("mapper:a@b"),
+            "`a@b` can't be a BeatSaver username, they only have letters, numbers, `.`, `_` and `-`"
+        );
+        assert_eq!(
+            error("tag:tech!"),
+            "`tech!` doesn't look like a BeatSaver tag"
+        );
+        assert_eq!(
+            error("after:yesterday"),
+            "`after:yesterday` isn't a date, write it like `after:2024-01-31`"
+        );
+        assert_eq!(
+            error("after:2024-06-01 before:2024-01-01"),
+            "`after:2024-06-01` is later than `before:2024-01-01`, so nothing can match"
+        );
+        assert_eq!(
+            error("sort:best"),
+            "`sort:best` isn't an option, try one of relevance, latest, rating, curated, duration, random"
+        );
+    }
+
+    #[test]
+    fn huge_durations_are_invalid() {
+        assert!(error("duration:>99999999:00").contains("isn't a valid range"));
+        assert!(error("duration:4294967295:59").contains("isn't a valid range"));
+        assert_eq!(parse_duration("71582788:15"), Some(4_294_967_295.0));
+    }
+
+    #[test]
+    fn params_for_every_filter() {
+        let query = parse(
+            "camellia mapper:someone nps:>8 bpm:150..200 duration:<2:30 rating:>=80% \
+             ranked:bl curated:true verified:no -automapper tag:Tech -tag:dance \
+             after:2024-01-01 before:2024-06-01 sort:latest",
+        );
+        let params = query.to_params(Some(42));
+        let params: Vec<(&str, &str)> = params
+            .iter()
+            .map(|(name, value)| (*name, value.as_str()))
+            .collect();
+
+        assert_eq!(
+            params,
+            [
+                ("q", "camellia"),
+                ("mappers", "42"),
+                ("minNps", "8"),
+                ("minBpm", "150"),
+                ("maxBpm", "200"),
+                ("maxDuration", "150"),
+                ("minRating", "0.8"),
+                ("leaderboard", "BeatLeader"),
+                ("curated", "true"),
+                ("verified", "false"),
+                ("automapper", "false"),
+                ("tags", "tech,!dance"),
+                ("from", "2024-01-01T00:00:00+00:00"),
+                ("to", "2024-06-01T00:00:00+00:00"),
+                ("order", "Latest"),
+            ]
+        );
+    }
+
+    #[test]
+    fn params_leave_out_what_isnt_set() {
+        assert!(SearchQuery::default().to_params(None).is_empty());
+
+        // the mapper only goes in once it's been looked up
+        let query = parse("mapper:someone ranked:true");
+        assert_eq!(
+            query.to_params(None),
+            [("leaderboard", "Ranked".to_owned())]
+        );
+    }
+}
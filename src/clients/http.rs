@@ -0,0 +1,219 @@
+use std::time::Duration;
+
+use anyhow::Context as _;
+use reqwest::{header::RETRY_AFTER, Response, StatusCode};
+use serde::de::DeserializeOwned;
+
+use crate::Error;
+
+/// How long a whole request can take, so a stalled upstream can't hang
+/// commands or the background tasks.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Failures we can tell apart when talking to BeatSaver, ScoreSaber or
+/// BeatLeader.
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    #[error("{what} doesn't exist on {service}")]
+    NotFound { service: &'static str, what: String },
+    #[error("{what} has been deleted from {service}")]
+    Deleted { service: &'static str, what: String },
+    #[error("{service} is rate limiting requests")]
+    RateLimited {
+        service: &'static str,
+        retry_after: Option<Duration>,
+    },
+    #[error("{service} returned {status}")]
+    Status {
+        service: &'static str,
+        status: StatusCode,
+    },
+    #[error("couldn't reach {service}")]
+    Unreachable {
+        service: &'static str,
+        #[source]
+        source: reqwest::Error,
+    },
+}
+
+impl ApiError {
+    /// Whether an error is something missing, for lookups where that's fine.
+    pub fn is_not_found(error: &Error) -> bool {
+        matches!(error.downcast_ref(), Some(Self::NotFound { .. }))
+    }
+
+    pub fn is_deleted(error: &Error) -> bool {
+        matches!(error.downcast_ref(), Some(Self::Deleted { .. }))
+    }
+}
+
+/// Percent-encodes text for use as one segment of a path, so things people
+/// typed can't end up requesting some other endpoint.
+pub fn path_segment(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    encoded
+}
+
+/// One site's JSON API, with its failures turned into [`ApiError`]s.
+pub struct Api {
+    http: reqwest::Client,
+    service: &'static str,
+    base_url: String,
+}
+
+impl Api {
+    pub fn new(service: &'static str, base_url: &str) -> Self {
+        Self::with_timeout(service, base_url, REQUEST_TIMEOUT)
+    }
+
+    /// Gives up on requests after `timeout`, as [`ApiError::Unreachable`].
+    pub fn with_timeout(service: &'static str, base_url: &str, timeout: Duration) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(timeout)
+            .connect_timeout(CONNECT_TIMEOUT.min(timeout))
+            .build()
+            .expect("couldn't set up the HTTP client");
+
+        Self {
+            http,
+            service,
+            base_url: base_url.to_owned(),
+        }
+    }
+
+    /// `what` doesn't exist here, e.g. `Player 1`.
+    pub fn not_found(&self, what: impl Into<String>) -> ApiError {
+        ApiError::NotFound {
+            service: self.service,
+            what: what.into(),
+        }
+    }
+
+    pub fn deleted(&self, what: impl Into<String>) -> ApiError {
+        ApiError::Deleted {
+            service: self.service,
+            what: what.into(),
+        }
+    }
+
+    /// Gets `path` under the base URL and parses it. `what` names whatever
+    /// was asked for, in case it doesn't exist.
+    pub async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+        what: impl FnOnce() -> String,
+    ) -> Result<T, Error> {
+        let body = self
+            .fetch(&format!("{}{}", self.base_url, path), query, what)
+            .await?;
+
+        serde_json::from_slice(&body)
+            .with_context(|| format!("couldn't parse {} {path}", self.service))
+    }
+
+    /// Downloads a whole URL as it is, e.g. an image the API linked to.
+    pub async fn download(
+        &self,
+        url: &str,
+        what: impl FnOnce() -> String,
+    ) -> Result<Vec<u8>, Error> {
+        Ok(self.fetch(url, &[], what).await?)
+    }
+
+    async fn fetch(
+        &self,
+        url: &str,
+        query: &[(&str, String)],
+        what: impl FnOnce() -> String,
+    ) -> Result<Vec<u8>, ApiError> {
+        let unreachable = |source| ApiError::Unreachable {
+            service: self.service,
+            source,
+        };
+
+        let res = self
+            .http
+            .get(url)
+            .query(query)
+            .send()
+            .await
+            .map_err(unreachable)?;
+        let res = self.check_status(res, what)?;
+
+        Ok(res.bytes().await.map_err(unreachable)?.to_vec())
+    }
+
+    fn check_status(
+        &self,
+        res: Response,
+        what: impl FnOnce() -> String,
+    ) -> Result<Response, ApiError> {
+        let service = self.service;
+        match res.status() {
+            status if status.is_success() => Ok(res),
+            StatusCode::NOT_FOUND => Err(self.not_found(what())),
+            StatusCode::TOO_MANY_REQUESTS => Err(ApiError::RateLimited {
+                service,
+                retry_after: res
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse().ok())
+                    .map(Duration::from_secs),
+            }),
+            status => Err(ApiError::Status { service, status }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn stalled_requests_time_out() {
+        // takes the connection, then never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut connections = vec![];
+            while let Ok((stream, _)) = listener.accept().await {
+                connections.push(stream);
+            }
+        });
+
+        let api = Api::with_timeout("BeatSaver", &url, Duration::from_millis(200));
+        let error = api
+            .get::<serde_json::Value>("/maps/id/1", &[], || "Map 1".to_owned())
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(ApiError::Unreachable {
+                service: "BeatSaver",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn path_segments_stay_one_segment() {
+        assert_eq!(path_segment("Joetastic"), "Joetastic");
+        assert_eq!(path_segment("a/b?c#d"), "a%2Fb%3Fc%23d");
+        assert_eq!(path_segment("../x y"), "..%2Fx%20y");
+        assert_eq!(path_segment("ñ"), "%C3%B1");
+    }
+}
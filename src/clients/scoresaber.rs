@@ -0,0 +1,590 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use log::debug;
+use serde::Deserialize;
+
+use super::http::{Api, ApiError};
+use crate::{core::config::ScoreSaberConfig, utils::cache::TtlCache, Error};
+
+const PLAYER_CACHE: (usize, Duration) = (500, Duration::from_secs(5 * 60));
+const LEADERBOARD_CACHE: (usize, Duration) = (500, Duration::from_secs(2 * 60));
+/// How many scores a leaderboard shows.
+pub const LEADERBOARD_SIZE: usize = 10;
+/// Pages of name matches looked through for a player's score before giving
+/// up, for names lots of others on the leaderboard share.
+const MAX_SEARCH_PAGES: u32 = 10;
+
+/// ScoreSaber lookups with a short cache in front.
+pub struct ScoreSaber {
+    api: Api,
+    players: TtlCache<String, Player>,
+    leaderboards: TtlCache<String, Leaderboard>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Player {
+    pub id: String,
+    pub name: String,
+    pub profile_picture: String,
+    pub country: String,
+    pub pp: f64,
+    /// 0 for inactive players.
+    pub rank: u32,
+    pub country_rank: u32,
+    #[serde(default)]
+    pub inactive: bool,
+    #[serde(default)]
+    pub banned: bool,
+    pub score_stats: Option<ScoreStats>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScoreStats {
+    pub total_score: u64,
+    pub total_ranked_score: u64,
+    /// A percentage, e.g. 95.4.
+    pub average_ranked_accuracy: f64,
+    pub total_play_count: u32,
+    pub ranked_play_count: u32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Score {
+    pub rank: u32,
+    pub base_score: u64,
+    pub modified_score: u64,
+    pub pp: f64,
+    #[serde(default)]
+    pub modifiers: String,
+    pub missed_notes: u32,
+    pub bad_cuts: u32,
+    pub full_combo: bool,
+    pub time_set: DateTime<Utc>,
+    /// Only filled in on leaderboard scores.
+    pub leaderboard_player_info: Option<LeaderboardPlayer>,
+}
+
+impl Score {
+    /// From 0 to 1, or `None` if the leaderboard has no max score (unranked).
+    pub fn accuracy(&self, max_score: u64) -> Option<f64> {
+        (max_score > 0).then(|| self.base_score as f64 / max_score as f64)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardPlayer {
+    pub id: String,
+    pub name: String,
+    pub country: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardInfo {
+    pub id: u64,
+    pub song_hash: String,
+    pub song_name: String,
+    pub song_author_name: String,
+    pub level_author_name: String,
+    pub difficulty: LeaderboardDifficulty,
+    pub max_score: u64,
+    pub ranked: bool,
+    pub stars: f64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardDifficulty {
+    /// 1, 3, 5, 7 or 9 for Easy to Expert+.
+    pub difficulty: u8,
+    /// e.g. `SoloStandard`.
+    pub game_mode: String,
+}
+
+impl LeaderboardDifficulty {
+    /// BeatSaver's name for the difficulty, e.g. `ExpertPlus`.
+    pub fn name(&self) -> &'static str {
+        match self.difficulty {
+            1 => "Easy",
+            3 => "Normal",
+            5 => "Hard",
+            7 => "Expert",
+            9 => "ExpertPlus",
+            _ => "Unknown",
+        }
+    }
+
+    /// BeatSaver's name for the characteristic, e.g. `Standard`.
+    pub fn characteristic(&self) -> &str {
+        let mode = self.game_mode.strip_prefix("Solo");
+        match mode.unwrap_or(&self.game_mode) {
+            "90Degree" => "Rotation90Degrees",
+            "360Degree" => "Rotation360Degrees",
+            other => other,
+        }
+    }
+}
+
+/// A score on someone's profile, with the leaderboard it's on.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerScore {
+    pub score: Score,
+    pub leaderboard: LeaderboardInfo,
+}
+
+/// The top of one difficulty's leaderboard.
+#[derive(Clone, Debug)]
+pub struct Leaderboard {
+    pub info: LeaderboardInfo,
+    pub scores: Vec<Score>,
+}
+
+#[derive(Deserialize)]
+struct PlayerSearch {
+    players: Vec<Player>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlayerScores {
+    player_scores: Vec<PlayerScore>,
+}
+
+#[derive(Deserialize)]
+struct LeaderboardScores {
+    scores: Vec<Score>,
+    metadata: Option<PageInfo>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    total: u32,
+    items_per_page: u32,
+}
+
+impl ScoreSaber {
+    pub fn new(config: &ScoreSaberConfig) -> Self {
+        Self {
+            api: Api::new("ScoreSaber", &config.api_url),
+            players: TtlCache::new(PLAYER_CACHE.0, PLAYER_CACHE.1),
+            leaderboards: TtlCache::new(LEADERBOARD_CACHE.0, LEADERBOARD_CACHE.1),
+        }
+    }
+
+    /// Finds a player by ID, profile link or name.
+    pub async fn player(&self, query: &str) -> Result<Player, Error> {
+        let id = match player_id(query) {
+            Some(id) => id,
+            None => self.search_player(query.trim()).await?.id,
+        };
+        if let Some(player) = self.players.get(&id) {
+            return Ok(player);
+        }
+
+        debug!("ScoreSaber player cache miss for {id}");
+        let player: Player = self
+            .api
+            .get(&format!("/player/{id}/full"), &[], || {
+                format!("Player {id}")
+            })
+            .await?;
+        self.players.insert(id, player.clone());
+
+        Ok(player)
+    }
+
+    /// The player whose name matches best, exact matches first.
+    async fn search_player(&self, name: &str) -> Result<Player, Error> {
+        let search: PlayerSearch = self
+            .api
+            .get("/players", &[("search", name.to_owned())], || {
+                format!("A player called {name}")
+            })
+            .await?;
+
+        let mut players = search.players;
+        let exact = players
+            .iter()
+            .position(|player| player.name.eq_ignore_ascii_case(name));
+        match exact {
+            Some(idx) => Ok(players.swap_remove(idx)),
+            None if !players.is_empty() => Ok(players.swap_remove(0)),
+            None => Err(self.api.not_found(format!("A player called {name}")).into()),
+        }
+    }
+
+    /// A player's best scores by PP.
+    pub async fn top_scores(
+        &self,
+        player_id: &str,
+        limit: usize,
+    ) -> Result<Vec<PlayerScore>, Error> {
+        self.scores(player_id, "top", limit).await
+    }
+
+    /// A player's most recent scores, newest first.
+    pub async fn recent_scores(
+        &self,
+        player_id: &str,
+        limit: usize,
+    ) -> Result<Vec<PlayerScore>, Error> {
+        self.scores(player_id, "recent", limit).await
+    }
+
+    async fn scores(
+        &self,
+        player_id: &str,
+        sort: &str,
+        limit: usize,
+    ) -> Result<Vec<PlayerScore>, Error> {
+        let scores: PlayerScores = self
+            .api
+            .get(
+                &format!("/player/{player_id}/scores"),
+                &[
+                    ("sort", sort.to_owned()),
+                    ("limit", limit.to_string()),
+                    ("page", "1".to_owned()),
+                ],
+                || format!("Player {player_id}"),
+            )
+            .await?;
+
+        Ok(scores.player_scores)
+    }
+
+    /// The top scores for one difficulty of a map version.
+    ///
+    /// `characteristic` and `difficulty` are BeatSaver's names, like
+    /// `Standard` and `ExpertPlus`.
+    pub async fn leaderboard(
+        &self,
+        hash: &str,
+        characteristic: &str,
+        difficulty: &str,
+    ) -> Result<Leaderboard, Error> {
+        let hash = hash.to_uppercase();
+        let key = format!("{hash}:{characteristic}:{difficulty}");
+        if let Some(leaderboard) = self.leaderboards.get(&key) {
+            return Ok(leaderboard);
+        }
+
+        let what = || format!("A {characteristic} {difficulty} leaderboard for that map");
+        let difficulty = difficulty_number(difficulty).ok_or_else(|| self.api.not_found(what()))?;
+        let params = [
+            ("difficulty", difficulty.to_string()),
+            ("gameMode", game_mode(characteristic)),
+        ];
+
+        debug!("ScoreSaber leaderboard cache miss for {key}");
+        let info: LeaderboardInfo = self
+            .api
+            .get(&format!("/leaderboard/by-hash/{hash}/info"), &params, what)
+            .await?;
+        let mut scores: LeaderboardScores = self
+            .api
+            .get(
+                &format!("/leaderboard/by-hash/{hash}/scores"),
+                &params,
+                what,
+            )
+            .await?;
+        scores.scores.truncate(LEADERBOARD_SIZE);
+
+        let leaderboard = Leaderboard {
+            info,
+            scores: scores.scores,
+        };
+        self.leaderboards.insert(key, leaderboard.clone());
+
+        Ok(leaderboard)
+    }
+
+    /// A player's score on one difficulty of a map version, if they have one.
+    pub async fn player_score(
+        &self,
+        player_id: &str,
+        hash: &str,
+        characteristic: &str,
+        difficulty: &str,
+    ) -> Result<Option<Score>, Error> {
+        let player = self.player(player_id).await?;
+        let hash = hash.to_uppercase();
+        let what = || format!("A {characteristic} {difficulty} leaderboard for that map");
+        let difficulty = difficulty_number(difficulty).ok_or_else(|| self.api.not_found(what()))?;
+
+        // scores can only be searched by name, so pick theirs out of the
+        // matches, which can take a few pages for a common name
+        for page in 1..=MAX_SEARCH_PAGES {
+            let res = self
+                .api
+                .get::<LeaderboardScores>(
+                    &format!("/leaderboard/by-hash/{hash}/scores"),
+                    &[
+                        ("difficulty", difficulty.to_string()),
+                        ("gameMode", game_mode(characteristic)),
+                        ("search", player.name.clone()),
+                        ("page", page.to_string()),
+                    ],
+                    what,
+                )
+                .await;
+            let page_scores = match res {
+                Ok(scores) => scores,
+                // no matches at all comes back as a 404
+                Err(err) if ApiError::is_not_found(&err) => return Ok(None),
+                Err(err) => return Err(err),
+            };
+
+            let last_page = page_scores.scores.is_empty()
+                || page_scores
+                    .metadata
+                    .as_ref()
+                    .is_none_or(|info| page * info.items_per_page >= info.total);
+            let theirs = page_scores.scores.into_iter().find(|score| {
+                score
+                    .leaderboard_player_info
+                    .as_ref()
+                    .is_some_and(|info| info.id == player.id)
+            });
+            if theirs.is_some() || last_page {
+                return Ok(theirs);
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Pulls a player ID out of a bare ID or a `scoresaber.com/u/<id>` link.
+fn player_id(query: &str) -> Option<String> {
+    let query = query.trim();
+    let id = match query.split_once("/u/") {
+        Some((_, rest)) => rest.split(['/', '?', '#']).next().unwrap_or_default(),
+        None => query,
+    };
+
+    (!id.is_empty() && id.chars().all(|c| c.is_ascii_digit())).then(|| id.to_owned())
+}
+
+/// ScoreSaber's number for a difficulty.
+fn difficulty_number(difficulty: &str) -> Option<u8> {
+    match difficulty {
+        "Easy" => Some(1),
+        "Normal" => Some(3),
+        "Hard" => Some(5),
+        "Expert" => Some(7),
+        "ExpertPlus" => Some(9),
+        _ => None,
+    }
+}
+
+/// ScoreSaber's name for a characteristic, e.g. `SoloStandard`.
+fn game_mode(characteristic: &str) -> String {
+    let characteristic = match characteristic {
+        "Rotation90Degrees" => "90Degree",
+        "Rotation360Degrees" => "360Degree",
+        other => other,
+    };
+    format!("Solo{characteristic}")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{
+        clients::mock::{MockServer, Reply},
+        core::error::ErrorKind,
+    };
+
+    const HASH: &str = "ABC123";
+
+    fn client(server: &MockServer) -> ScoreSaber {
+        ScoreSaber::new(&ScoreSaberConfig {
+            api_url: server.url().to_owned(),
+        })
+    }
+
+    fn player(id: &str, name: &str) -> Value {
+        json!({
+            "id": id,
+            "name": name,
+            "profilePicture": "https://cdn.scoresaber.com/avatars/1.jpg",
+            "country": "NL",
+            "pp": 12345.67,
+            "rank": 42,
+            "countryRank": 3,
+            "scoreStats": {
+                "totalScore": 1000,
+                "totalRankedScore": 500,
+                "averageRankedAccuracy": 95.4,
+                "totalPlayCount": 20,
+                "rankedPlayCount": 10
+            }
+        })
+    }
+
+    fn score(player_id: &str, base_score: u64) -> Value {
+        json!({
+            "rank": 1,
+            "baseScore": base_score,
+            "modifiedScore": base_score,
+            "pp": 300.5,
+            "missedNotes": 0,
+            "badCuts": 0,
+            "fullCombo": true,
+            "timeSet": "2026-10-01T12:00:00Z",
+            "leaderboardPlayerInfo": {"id": player_id, "name": "Someone", "country": "NL"}
+        })
+    }
+
+    #[tokio::test]
+    async fn player_is_parsed() {
+        let server = MockServer::start(|target| match target {
+            "/player/1/full" => Reply::json(player("1", "Kin")),
+            _ => Reply::status(404),
+        })
+        .await;
+
+        let player = client(&server)
+            .player("https://scoresaber.com/u/1?page=2")
+            .await
+            .unwrap();
+        assert_eq!(player.name, "Kin");
+        assert_eq!(player.rank, 42);
+        assert!(!player.banned);
+        let stats = player.score_stats.unwrap();
+        assert_eq!(stats.average_ranked_accuracy, 95.4);
+    }
+
+    #[tokio::test]
+    async fn missing_players_are_not_found() {
+        let server = MockServer::start(|target| match target {
+            target if target.starts_with("/players?") => Reply::json(json!({"players": []})),
+            _ => Reply::status(404),
+        })
+        .await;
+        let scoresaber = client(&server);
+
+        for query in ["1", "nobody"] {
+            let err = scoresaber.player(query).await.unwrap_err();
+            assert_eq!(
+                ErrorKind::classify(&err),
+                ErrorKind::NotFound {
+                    service: "ScoreSaber"
+                }
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn rate_limits_say_when_to_retry() {
+        let server = MockServer::start(|_| Reply::status(429).header("Retry-After", "30")).await;
+
+        let err = client(&server).player("1").await.unwrap_err();
+        assert_eq!(
+            ErrorKind::classify(&err),
+            ErrorKind::RateLimited {
+                service: "ScoreSaber",
+                retry_after: Some(Duration::from_secs(30)),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn player_score_pages_through_name_matches() {
+        let server = MockServer::start(|target| match target {
+            "/player/1/full" => Reply::json(player("1", "Kin")),
+            target if target.contains("page=1") => Reply::json(json!({
+                "scores": [score("2", 900), score("3", 800)],
+                "metadata": {"total": 3, "page": 1, "itemsPerPage": 2}
+            })),
+            target if target.contains("page=2") => Reply::json(json!({
+                "scores": [score("1", 700)],
+                "metadata": {"total": 3, "page": 2, "itemsPerPage": 2}
+            })),
+            _ => Reply::status(404),
+        })
+        .await;
+
+        let score = client(&server)
+            .player_score("1", HASH, "Standard", "ExpertPlus")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(score.base_score, 700);
+
+        let searches: Vec<String> = server
+            .requests()
+            .into_iter()
+            .filter(|target| target.starts_with("/leaderboard/by-hash/ABC123/scores?"))
+            .collect();
+        assert_eq!(searches.len(), 2);
+        assert!(searches[0].contains("difficulty=9"));
+        assert!(searches[0].contains("gameMode=SoloStandard"));
+        assert!(searches[0].contains("search=Kin"));
+    }
+
+    #[tokio::test]
+    async fn player_score_stops_after_the_last_page() {
+        let server = MockServer::start(|target| match target {
+            "/player/1/full" => Reply::json(player("1", "Kin")),
+            target if target.contains("page=1") => Reply::json(json!({
+                "scores": [score("2", 900)],
+                "metadata": {"total": 1, "page": 1, "itemsPerPage": 2}
+            })),
+            _ => Reply::status(404),
+        })
+        .await;
+
+        let score = client(&server)
+            .player_score("1", HASH, "Standard", "Expert")
+            .await
+            .unwrap();
+        assert!(score.is_none());
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn leaderboard_only_keeps_the_top() {
+        let server = MockServer::start(|target| match target {
+            target if target.starts_with("/leaderboard/by-hash/ABC123/info?") => {
+                Reply::json(json!({
+                    "id": 7,
+                    "songHash": HASH,
+                    "songName": "Song",
+                    "songAuthorName": "Artist",
+                    "levelAuthorName": "Mapper",
+                    "difficulty": {"difficulty": 7, "gameMode": "SoloStandard"},
+                    "maxScore": 1000,
+                    "ranked": true,
+                    "stars": 8.5
+                }))
+            }
+            target if target.starts_with("/leaderboard/by-hash/ABC123/scores?") => {
+                let scores: Vec<Value> = (0..12).map(|n| score(&n.to_string(), 990 - n)).collect();
+                Reply::json(json!({"scores": scores}))
+            }
+            _ => Reply::status(404),
+        })
+        .await;
+
+        let leaderboard = client(&server)
+            .leaderboard("abc123", "Standard", "Expert")
+            .await
+            .unwrap();
+        assert_eq!(leaderboard.info.difficulty.name(), "Expert");
+        assert_eq!(leaderboard.info.difficulty.characteristic(), "Standard");
+        assert_eq!(leaderboard.scores.len(), LEADERBOARD_SIZE);
+        assert_eq!(leaderboard.scores[0].accuracy(1000), Some(0.99));
+    }
+}
@@ -1,2 +1,2 @@
-pub mod beatsaber;
-pub mod misc;
+pub mod beatsaber;
+pub mod misc;
@@ -1 +1,7 @@
-pub mod beatsaver;
\ No newline at end of file
+pub mod beatleader;
+pub mod beatsaver;
+pub mod link;
+pub mod playlist;
+pub mod scoresaber;
+pub mod snipes;
+pub mod subscriptions;
\ No newline at end of file
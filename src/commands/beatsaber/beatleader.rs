@@ -0,0 +1,54 @@
+use poise::{self, CreateReply};
+
+use crate::commands::beatsaber::link::player_or_linked;
+use crate::core::settings::GuildSettings;
+use crate::core::storage::linked_accounts::Service;
+use crate::ui::beatleaderprofile::build_player_embed;
+use crate::{Context, Error};
+
+/// How many of a player's best and latest scores to show.
+const PLAYS: u32 = 5;
+
+/// Looks things up on BeatLeader.
+#[poise::command(
+    slash_command,
+    install_context = "Guild|User",
+    interaction_context = "Guild|BotDm|PrivateChannel",
+    subcommands("player"),
+    subcommand_required
+)]
+pub async fn beatleader(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Shows a BeatLeader player's rank, PP, clans, and top and recent plays.
+#[poise::command(
+    slash_command,
+    install_context = "Guild|User",
+    interaction_context = "Guild|BotDm|PrivateChannel"
+)]
+pub async fn player(
+    ctx: Context<'_>,
+    #[description = "A player ID, profile link or name. Leave out for your linked account."]
+    #[max_length = 100]
+    player: Option<String>,
+) -> Result<(), Error> {
+    let settings = GuildSettings::for_context(ctx).await?;
+    let Some(player) = player_or_linked(ctx, Service::BeatLeader, player).await? else {
+        return Ok(());
+    };
+    let beatleader = &ctx.data().beatleader;
+    let player = beatleader.player(&player).await?;
+    let (top_plays, recent_plays) = tokio::try_join!(
+        beatleader.scores(&player.id, "pp", PLAYS),
+        beatleader.scores(&player.id, "date", PLAYS),
+    )?;
+
+    let builder = CreateReply::default()
+        .embed(build_player_embed(&player, &top_plays, &recent_plays))
+        .ephemeral(settings.ephemeral());
+
+    ctx.send(builder).await?;
+
+    Ok(())
+}
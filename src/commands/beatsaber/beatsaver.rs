@@ -1,75 +1,161 @@
-use crate::ui::mapembed::MapEmbed;
-use crate::utils::discord::autocomplete::beatsaver::autocomplete_map;
-use crate::utils::discord::autocomplete::beatsaver::find_bsr;
-use crate::{Context, Error};
-use beatsaver_api::models::map::Map;
-use log::info;
-use poise::{
-    self,
-    serenity_prelude::{
-        self as serenity, CreateInteractionResponse, CreateInteractionResponseMessage,
-    },
-    CreateReply,
-};
-
-/// Searches a Beat Saber custom map from BeatSaver.   
-#[poise::command(
-    slash_command,
-    install_context = "Guild|User",
-    interaction_context = "Guild|BotDm|PrivateChannel"
-)]
-pub async fn bsr(
-    ctx: Context<'_>,
-    #[description = "The map ID, a link to a BeatSaver map, or a BeatSaver search query."]
-    #[autocomplete = "autocomplete_map"]
-    query: String,
-) -> Result<(), Error> {
-    // if user doesn't use the autocomplete functions
-    let code = match find_bsr(ctx, &query) {
-        Some(bsr) => bsr,
-        None => query,
-    };
-
-    let map: Map = ctx.data().beatsaver_client.map(&code).await?;
-    let mut map_embed: MapEmbed = MapEmbed::new(map);
-
-    let builder: CreateReply = CreateReply::default()
-        .embed(map_embed.build_embeds()[0].clone()) // just the metadata
-        .components(map_embed.build_embed_components());
-
-    // general metadata message
-    let reply = ctx.send(builder).await?;
-
-    // collector for difficulty/metadata selection
-    while let Some(mci) = serenity::ComponentInteractionCollector::new(ctx)
-        .author_id(ctx.author().id)
-        .channel_id(ctx.channel_id())
-        .message_id(reply.message().await?.id)
-        .timeout(std::time::Duration::from_secs(15 * 60))
-        .filter(move |mci| mci.data.custom_id == "diffsel")
-        .await
-    {
-        info!(
-            "Difficulty request for !bsr {} requested by {}",
-            code,
-            ctx.author().name
-        );
-
-        let diff_key = match &mci.data.kind {
-            serenity::ComponentInteractionDataKind::StringSelect { values } => &values[0],
-            _ => panic!("unexpected interaction data kind"),
-        };
-
-        map_embed.set_index(diff_key);
-
-        let diff_builder = CreateInteractionResponseMessage::new()
-            .embeds(map_embed.build_embeds())
-            .components(map_embed.build_embed_components());
-
-        let new_message = CreateInteractionResponse::UpdateMessage(diff_builder);
-
-        mci.create_response(ctx, new_message).await?;
-    }
-
-    Ok(())
-}
+use crate::clients::beatsaver::query::SearchQuery;
+use crate::clients::http::ApiError;
+use crate::core::settings::GuildSettings;
+use crate::ui::mapembed::MapEmbed;
+use crate::ui::maplist::MapList;
+use crate::ui::mapperprofile::build_profile_embed;
+use crate::ui::searchresults::SearchResults;
+use crate::utils::discord::autocomplete::beatsaver::{autocomplete_map, autocomplete_mapper};
+use crate::utils::discord::autocomplete::beatsaver::{find_bsr, MapQuery};
+use crate::{Context, Error};
+use beatsaver_api::models::map::Map;
+use poise::{self, CreateReply};
+
+/// Searches a Beat Saber custom map from BeatSaver.   
+#[poise::command(
+    slash_command,
+    install_context = "Guild|User",
+    interaction_context = "Guild|BotDm|PrivateChannel"
+)]
+pub async fn bsr(
+    ctx: Context<'_>,
+    #[description = "A map ID, link, hashes, or a search like `camellia nps:>8 sort:rating`."]
+    #[autocomplete = "autocomplete_map"]
+    query: String,
+    #[description = "A version number (1 is the first upload) or version hash."]
+    version: Option<String>,
+) -> Result<(), Error> {
+    let settings = GuildSettings::for_context(ctx).await?;
+
+    // if user doesn't use the autocomplete functions
+    let mut map_embed: MapEmbed = match find_bsr(ctx, &query) {
+        Some(MapQuery::Hashes(hashes)) if hashes.len() > 1 => {
+            return send_hash_list(ctx, &settings, hashes).await;
+        }
+        Some(MapQuery::Hashes(hashes)) => {
+            let map: Map = ctx.data().beatsaver.map_by_hash(&hashes[0]).await?;
+            MapEmbed::new(map).with_hash(&hashes[0])
+        }
+        Some(MapQuery::Id(code)) => MapEmbed::new(ctx.data().beatsaver.map(&code).await?),
+        // typed out a search without picking a result, so go with the top one
+        None => {
+            let search = SearchQuery::parse(&query)?;
+            let map = ctx.data().beatsaver.search(&search, 0).await?.into_iter().next();
+            match map {
+                Some(map) => MapEmbed::new(map),
+                None => {
+                    return Err(ApiError::NotFound {
+                        service: "BeatSaver",
+                        what: "A map matching that search".to_owned(),
+                    }
+                    .into())
+                }
+            }
+        }
+    }
+    .with_settings(&settings);
+
+    if let Some(version) = version {
+        if !map_embed.set_version(&version) {
+            return Err(ApiError::NotFound {
+                service: "BeatSaver",
+                what: format!("Version {} of map {}", version, map_embed.map.id),
+            }
+            .into());
+        }
+    }
+
+    // general metadata message, selections are handled by the interaction router
+    let builder: CreateReply = CreateReply::default()
+        .embed(map_embed.build_embeds()[0].clone()) // just the metadata
+        .components(map_embed.build_embed_components())
+        .ephemeral(settings.ephemeral());
+
+    ctx.send(builder).await?;
+
+    Ok(())
+}
+
+/// Searches BeatSaver and lists the results, ten at a time.
+#[poise::command(
+    slash_command,
+    install_context = "Guild|User",
+    interaction_context = "Guild|BotDm|PrivateChannel"
+)]
+pub async fn search(
+    ctx: Context<'_>,
+    #[description = "What to search for, filters like `nps:>8` or `sort:rating` included."]
+    #[max_length = 200]
+    query: String,
+) -> Result<(), Error> {
+    let settings = GuildSettings::for_context(ctx).await?;
+    let results = SearchResults::fetch(&ctx.data().beatsaver, query.trim(), 0).await?;
+
+    let builder = CreateReply::default()
+        .embed(results.build_embed())
+        .components(results.build_components())
+        .ephemeral(settings.ephemeral());
+
+    ctx.send(builder).await?;
+
+    Ok(())
+}
+
+/// Shows a BeatSaver mapper's profile and their maps.
+#[poise::command(
+    slash_command,
+    install_context = "Guild|User",
+    interaction_context = "Guild|BotDm|PrivateChannel"
+)]
+pub async fn mapper(
+    ctx: Context<'_>,
+    #[description = "The mapper's BeatSaver username."]
+    #[autocomplete = "autocomplete_mapper"]
+    name: String,
+) -> Result<(), Error> {
+    let settings = GuildSettings::for_context(ctx).await?;
+    let mapper = ctx.data().beatsaver.mapper(&name).await?;
+    let maps = SearchResults::fetch(
+        &ctx.data().beatsaver,
+        &format!("mapper:{} sort:latest", mapper.name),
+        0,
+    )
+    .await?;
+
+    // the map list goes last so its buttons can find the query
+    let builder = CreateReply::default()
+        .embed(build_profile_embed(&mapper))
+        .embed(maps.build_embed())
+        .components(maps.build_components())
+        .ephemeral(settings.ephemeral());
+
+    ctx.send(builder).await?;
+
+    Ok(())
+}
+
+/// Lists the maps for a bunch of hashes at once, e.g. pasted from a playlist.
+async fn send_hash_list(
+    ctx: Context<'_>,
+    settings: &GuildSettings,
+    hashes: Vec<String>,
+) -> Result<(), Error> {
+    let mut maps = ctx.data().beatsaver.maps_by_hashes(&hashes).await?;
+
+    let mut list = MapList::new(format!("Maps for {} hashes", hashes.len()));
+    for hash in hashes {
+        match maps.remove(&hash) {
+            Some(map) => list.push(map, Some(hash)),
+            None => list.push_missing(hash),
+        }
+    }
+
+    let builder = CreateReply::default()
+        .embed(list.build_embed())
+        .components(list.build_components())
+        .ephemeral(settings.ephemeral());
+
+    ctx.send(builder).await?;
+
+    Ok(())
+}
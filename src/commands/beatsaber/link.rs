@@ -0,0 +1,256 @@
+use log::info;
+use poise::{
+    self,
+    serenity_prelude::{Colour, CreateEmbed, CreateEmbedFooter, User},
+    CreateReply,
+};
+
+use crate::core::error::ErrorKind;
+use crate::core::settings::GuildSettings;
+use crate::core::storage::linked_accounts::Service;
+use crate::utils::text::{sanitise, EmbedBudget};
+use crate::{Context, Error};
+
+/// Links your Discord account to a player, so score commands know who you are.
+#[poise::command(
+    slash_command,
+    install_context = "Guild|User",
+    interaction_context = "Guild|BotDm|PrivateChannel",
+    subcommands("scoresaber", "beatleader"),
+    subcommand_required
+)]
+pub async fn link(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Links your ScoreSaber account.
+#[poise::command(
+    slash_command,
+    install_context = "Guild|User",
+    interaction_context = "Guild|BotDm|PrivateChannel"
+)]
+pub async fn scoresaber(
+    ctx: Context<'_>,
+    #[description = "Your player ID, profile link or name."]
+    #[max_length = 100]
+    player: String,
+    #[description = "Check it's you through the Discord account connected on BeatLeader."]
+    verify: Option<bool>,
+) -> Result<(), Error> {
+    let player = ctx.data().scoresaber.player(&player).await?;
+    link_player(
+        ctx,
+        Service::ScoreSaber,
+        &player.id,
+        &player.name,
+        verify.unwrap_or(false),
+    )
+    .await
+}
+
+/// Links your BeatLeader account.
+#[poise::command(
+    slash_command,
+    install_context = "Guild|User",
+    interaction_context = "Guild|BotDm|PrivateChannel"
+)]
+pub async fn beatleader(
+    ctx: Context<'_>,
+    #[description = "Your player ID, profile link or name."]
+    #[max_length = 100]
+    player: String,
+    #[description = "Check it's you through the Discord account connected on BeatLeader."]
+    verify: Option<bool>,
+) -> Result<(), Error> {
+    let player = ctx.data().beatleader.player(&player).await?;
+    link_player(
+        ctx,
+        Service::BeatLeader,
+        &player.id,
+        &player.name,
+        verify.unwrap_or(false),
+    )
+    .await
+}
+
+/// Saves the link, once the player's been verified if asked to.
+///
+/// ScoreSaber and BeatLeader share player IDs, so both get verified through
+/// BeatLeader.
+async fn link_player(
+    ctx: Context<'_>,
+    service: Service,
+    player_id: &str,
+    player_name: &str,
+    verify: bool,
+) -> Result<(), Error> {
+    let author = ctx.author();
+
+    if verify {
+        let discord_id = match ctx.data().beatleader.fetch_player(player_id).await {
+            Ok(player) => player.discord_id(),
+            Err(err) if ErrorKind::classify(&err).is_not_found() => None,
+            Err(err) => return Err(err),
+        };
+        if discord_id != Some(author.id.get()) {
+            let message = format!(
+                "Couldn't verify that **{}** is you. Connect your Discord account on your \
+                 [BeatLeader profile](https://beatleader.com/settings) and try again, \
+                 or link without verifying.",
+                sanitise(player_name)
+            );
+            ctx.send(CreateReply::default().content(message).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    }
+
+    ctx.data()
+        .storage
+        .linked_accounts()
+        .link(author.id, service, player_id, player_name, verify)
+        .await?;
+    info!(
+        "{} linked {} player {} (verified: {})",
+        author.name,
+        service.name(),
+        player_id,
+        verify
+    );
+
+    let message = format!(
+        "Linked to [{}](<{}>){} on {}. Score commands will use it when you don't name a player.",
+        sanitise(player_name),
+        service.profile_url(player_id),
+        if verify { ", verified" } else { "" },
+        service.name()
+    );
+    ctx.send(CreateReply::default().content(message).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+/// Unlinks a ScoreSaber or BeatLeader account.
+#[poise::command(
+    slash_command,
+    install_context = "Guild|User",
+    interaction_context = "Guild|BotDm|PrivateChannel"
+)]
+pub async fn unlink(
+    ctx: Context<'_>,
+    #[description = "Which account to unlink."] service: Service,
+) -> Result<(), Error> {
+    let removed = ctx
+        .data()
+        .storage
+        .linked_accounts()
+        .unlink(ctx.author().id, service)
+        .await?;
+
+    let message = match removed {
+        true => {
+            info!(
+                "{} unlinked their {} account",
+                ctx.author().name,
+                service.name()
+            );
+            format!("Your {} account isn't linked anymore.", service.name())
+        }
+        false => format!("You don't have a {} account linked.", service.name()),
+    };
+    ctx.send(CreateReply::default().content(message).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+/// Shows the ScoreSaber and BeatLeader accounts someone has linked.
+#[poise::command(
+    slash_command,
+    install_context = "Guild|User",
+    interaction_context = "Guild|BotDm|PrivateChannel"
+)]
+pub async fn whois(
+    ctx: Context<'_>,
+    #[description = "Who to look up. Leave out for yourself."] user: Option<User>,
+) -> Result<(), Error> {
+    let settings = GuildSettings::for_context(ctx).await?;
+    let user = user.as_ref().unwrap_or(ctx.author());
+    let accounts = ctx
+        .data()
+        .storage
+        .linked_accounts()
+        .for_user(user.id)
+        .await?;
+
+    let mut budget = EmbedBudget::new();
+    let mut embed = CreateEmbed::new()
+        .title(budget.title(&sanitise(user.display_name())))
+        .thumbnail(user.face())
+        .colour(Colour::from_rgb(166, 149, 255))
+        .footer(CreateEmbedFooter::new(
+            budget.footer("Link your own accounts with /link"),
+        ));
+
+    if accounts.is_empty() {
+        embed = embed.description(
+            budget.description(&format!("<@{}> hasn't linked any accounts.", user.id)),
+        );
+    }
+    for account in &accounts {
+        let value = format!(
+            "[{}]({}){}\nLinked <t:{}:R>",
+            sanitise(&account.player_name),
+            account.service.profile_url(&account.player_id),
+            if account.verified { " ✅" } else { "" },
+            account.linked_at
+        );
+        embed = embed.field(
+            budget.field_name(account.service.name()),
+            budget.field_value(&value),
+            true,
+        );
+    }
+
+    ctx.send(
+        CreateReply::default()
+            .embed(embed)
+            .ephemeral(settings.ephemeral()),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// The player a score command should look up: whoever was named, or else the
+/// caller's linked account.
+///
+/// Replies and returns `None` if neither is there.
+pub async fn player_or_linked(
+    ctx: Context<'_>,
+    service: Service,
+    player: Option<String>,
+) -> Result<Option<String>, Error> {
+    if let Some(player) = player {
+        return Ok(Some(player));
+    }
+
+    let linked = ctx
+        .data()
+        .storage
+        .linked_accounts()
+        .get(ctx.author().id, service)
+        .await?;
+    if linked.is_none() {
+        let message = format!(
+            "Name a player, or link your own {} account with `/link {}` first.",
+            service.name(),
+            service.key()
+        );
+        ctx.send(CreateReply::default().content(message).ephemeral(true))
+            .await?;
+    }
+
+    Ok(linked.map(|account| account.player_id))
+}
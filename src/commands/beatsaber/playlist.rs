@@ -0,0 +1,417 @@
+use std::collections::HashMap;
+
+use beatsaver_api::models::map::Map;
+use log::warn;
+use poise::{
+    self,
+    serenity_prelude::{Attachment, Colour, CreateAttachment, CreateEmbed},
+    CreateReply,
+};
+
+use crate::core::settings::GuildSettings;
+use crate::ui::difficulty::{CharacteristicInfo, DifficultyInfo};
+use crate::ui::playlistview::PlaylistView;
+use crate::utils::discord::autocomplete::beatsaver::{find_bsr, MapQuery};
+use crate::utils::playlist::{Playlist, PlaylistDifficulty, PlaylistSong};
+use crate::utils::text::{neutralise_mentions, sanitise, truncate, EmbedBudget};
+use crate::{Context, Error};
+
+/// More than this and resolving everything takes too long.
+const MAX_SONGS: usize = 100;
+/// Big enough for a few thousand songs with a cover.
+const MAX_PLAYLIST_SIZE: u32 = 8 * 1024 * 1024;
+/// Discord lets us upload more, but it all ends up in the playlist file.
+const MAX_COVER_SIZE: u32 = 1024 * 1024;
+
+/// Makes and reads `.bplist` playlists.
+#[poise::command(
+    slash_command,
+    install_context = "Guild|User",
+    interaction_context = "Guild|BotDm|PrivateChannel",
+    subcommands("create", "inspect"),
+    subcommand_required
+)]
+pub async fn playlist(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Makes a playlist out of map codes, hashes or links.
+#[poise::command(
+    slash_command,
+    install_context = "Guild|User",
+    interaction_context = "Guild|BotDm|PrivateChannel"
+)]
+pub async fn create(
+    ctx: Context<'_>,
+    #[description = "The playlist's name."]
+    #[max_length = 100]
+    title: String,
+    #[description = "Map codes, hashes or links, split by spaces. Highlight with e.g. `25f@Expert+`."]
+    maps: String,
+    #[description = "What the playlist is for."] description: Option<String>,
+    #[description = "A cover image. Defaults to the first map's cover."] cover: Option<Attachment>,
+) -> Result<(), Error> {
+    let settings = GuildSettings::for_context(ctx).await?;
+
+    // resolving a long list can take a while
+    defer(ctx, &settings).await?;
+
+    let mut playlist = Playlist {
+        playlist_title: title,
+        playlist_author: Some(ctx.author().name.clone()),
+        playlist_description: description,
+        ..Default::default()
+    };
+    let mut failed: Vec<String> = vec![];
+
+    let mut entries = split_entries(&maps);
+    if entries.len() > MAX_SONGS {
+        failed.push(format!(
+            "Only the first {MAX_SONGS} entries were added, {} were left out",
+            entries.len() - MAX_SONGS
+        ));
+        entries.truncate(MAX_SONGS);
+    }
+
+    // hashes get looked up together rather than one request each
+    let hashes: Vec<String> = entries
+        .iter()
+        .filter_map(|entry| match find_bsr(ctx, target(entry)) {
+            Some(MapQuery::Hashes(hashes)) => Some(hashes[0].clone()),
+            _ => None,
+        })
+        .collect();
+    let hash_maps = match hashes.is_empty() {
+        true => Ok(HashMap::new()),
+        false => ctx
+            .data()
+            .beatsaver
+            .maps_by_hashes(&hashes)
+            .await
+            .map_err(|err| err.to_string()),
+    };
+
+    let mut first_cover: Option<String> = None;
+    for entry in &entries {
+        match resolve_entry(ctx, entry, &hash_maps).await {
+            Ok(resolved) => {
+                first_cover = first_cover.or(resolved.cover_url);
+                failed.extend(
+                    resolved
+                        .notes
+                        .into_iter()
+                        .map(|note| format!("`{entry}`: {note}")),
+                );
+                playlist.songs.push(resolved.song);
+            }
+            Err(note) => failed.push(format!("`{entry}`: {note}")),
+        }
+    }
+
+    if playlist.songs.is_empty() {
+        let embed = CreateEmbed::new()
+            .title("Couldn't make that playlist")
+            .description(truncate(&neutralise_mentions(&failed.join("\n")), 4096, "..."))
+            .colour(Colour::from_rgb(68, 68, 68));
+        ctx.send(
+            CreateReply::default()
+                .embed(embed)
+                .ephemeral(settings.ephemeral()),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    // a broken cover shouldn't sink the whole playlist
+    match cover {
+        Some(cover) => match load_cover(&cover).await {
+            Ok((bytes, mime)) => playlist.set_image(&bytes, &mime),
+            Err(note) => failed.push(format!("Cover: {note}")),
+        },
+        None => {
+            if let Some(url) = first_cover {
+                match ctx.data().beatsaver.cover(&url).await {
+                    Ok(bytes) => playlist.set_image(&bytes, "image/jpeg"),
+                    Err(err) => warn!("Couldn't download playlist cover {url}: {err:#}"),
+                }
+            }
+        }
+    }
+
+    let mut budget = EmbedBudget::new();
+    let mut embed = CreateEmbed::new()
+        .title(budget.title(&sanitise(&playlist.playlist_title)))
+        .description(budget.description(&format!(
+            "{} song(s), by {}",
+            playlist.songs.len(),
+            sanitise(&ctx.author().name)
+        )))
+        .colour(Colour::from_rgb(166, 149, 255));
+    if !failed.is_empty() {
+        embed = embed.field(
+            budget.field_name(&format!("Problems ({})", failed.len())),
+            budget.field_value(&neutralise_mentions(&failed.join("\n"))),
+            false,
+        );
+    }
+
+    let file = CreateAttachment::bytes(playlist.to_json()?, playlist.file_name());
+    let builder = CreateReply::default()
+        .embed(embed)
+        .attachment(file)
+        .ephemeral(settings.ephemeral());
+    ctx.send(builder).await?;
+
+    Ok(())
+}
+
+/// Looks up every song in a `.bplist` and shows what's in it.
+#[poise::command(
+    slash_command,
+    install_context = "Guild|User",
+    interaction_context = "Guild|BotDm|PrivateChannel"
+)]
+pub async fn inspect(
+    ctx: Context<'_>,
+    #[description = "The .bplist file."] file: Attachment,
+) -> Result<(), Error> {
+    let settings = GuildSettings::for_context(ctx).await?;
+    let unreadable = |why: &str| {
+        CreateReply::default()
+            .embed(
+                CreateEmbed::new()
+                    .title("Couldn't read that playlist")
+                    .description(why)
+                    .colour(Colour::from_rgb(68, 68, 68)),
+            )
+            .ephemeral(settings.ephemeral())
+    };
+
+    if file.size > MAX_PLAYLIST_SIZE {
+        ctx.send(unreadable("It's over 8 MB, which is a lot more than a playlist should be."))
+            .await?;
+        return Ok(());
+    }
+
+    defer(ctx, &settings).await?;
+
+    let bytes = file.download().await?;
+    let playlist = match Playlist::from_json(&bytes) {
+        Ok(playlist) => playlist,
+        Err(err) => {
+            warn!("Couldn't parse playlist {}: {err:#}", file.filename);
+            ctx.send(unreadable("It doesn't look like a `.bplist` file."))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let view = PlaylistView::resolve(&ctx.data().beatsaver, playlist).await?;
+
+    // the buttons read the playlist back from this attachment
+    let file_name = match file.filename.ends_with(".bplist") {
+        true => file.filename.clone(),
+        false => format!("{}.bplist", file.filename),
+    };
+    let builder = CreateReply::default()
+        .embed(view.build_embed(0))
+        .components(view.build_components(0))
+        .attachment(CreateAttachment::bytes(bytes, file_name))
+        .ephemeral(settings.ephemeral());
+
+    ctx.send(builder).await?;
+
+    Ok(())
+}
+
+/// Defers so the eventual reply has the server's visibility.
+async fn defer(ctx: Context<'_>, settings: &GuildSettings) -> Result<(), Error> {
+    match settings.ephemeral() {
+        true => ctx.defer_ephemeral().await?,
+        false => ctx.defer().await?,
+    }
+    Ok(())
+}
+
+/// Splits the maps option into entries, keeping a pasted `!bsr 25f` together.
+fn split_entries(maps: &str) -> Vec<String> {
+    let mut entries = vec![];
+    let mut tokens = maps
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|token| !token.is_empty());
+
+    while let Some(token) = tokens.next() {
+        let entry = match token {
+            "!bsr" => match tokens.next() {
+                Some(code) => format!("!bsr {code}"),
+                None => token.to_owned(),
+            },
+            _ => token.to_owned(),
+        };
+        entries.push(entry);
+    }
+
+    entries
+}
+
+/// The part of an entry naming the map, without any highlights.
+fn target(entry: &str) -> &str {
+    entry.split('@').next().unwrap_or_default()
+}
+
+/// One entry that made it into the playlist.
+struct ResolvedEntry {
+    song: PlaylistSong,
+    cover_url: Option<String>,
+    /// Things that didn't work out but didn't stop it from being added.
+    notes: Vec<String>,
+}
+
+/// Finds the map for one entry, like `25f`, a hash, or `25f@OneSaber/Expert`.
+///
+/// Hashes come from `hash_maps`, looked up beforehand in one go. Failures
+/// come back as a note for the reply rather than an error.
+async fn resolve_entry(
+    ctx: Context<'_>,
+    entry: &str,
+    hash_maps: &Result<HashMap<String, Map>, String>,
+) -> Result<ResolvedEntry, String> {
+    let parts = entry.split('@').skip(1);
+
+    let (map, hash) = match find_bsr(ctx, target(entry)) {
+        Some(MapQuery::Id(id)) => {
+            let map = ctx
+                .data()
+                .beatsaver
+                .map(&id)
+                .await
+                .map_err(|err| err.to_string())?;
+            let hash = map
+                .versions
+                .first()
+                .map(|version| version.hash.clone())
+                .ok_or("has no versions")?;
+            (map, hash)
+        }
+        Some(MapQuery::Hashes(hashes)) => {
+            let hash = hashes[0].clone();
+            let map = hash_maps
+                .as_ref()
+                .map_err(Clone::clone)?
+                .get(&hash)
+                .cloned()
+                .ok_or("doesn't exist on BeatSaver")?;
+            (map, hash)
+        }
+        None => return Err("isn't a map code, hash or BeatSaver link".to_owned()),
+    };
+
+    let mut notes = vec![];
+    let mut difficulties = vec![];
+    for highlight in parts {
+        match find_difficulty(&map, &hash, highlight) {
+            Ok(difficulty) if !difficulties.contains(&difficulty) => difficulties.push(difficulty),
+            Ok(_) => {}
+            Err(note) => notes.push(format!("{note}, added without that highlight")),
+        }
+    }
+
+    let version = map.versions.iter().find(|version| version.hash.eq_ignore_ascii_case(&hash));
+
+    Ok(ResolvedEntry {
+        cover_url: version.map(|version| version.cover_url.clone()),
+        song: PlaylistSong {
+            key: Some(map.id.clone()),
+            hash,
+            song_name: Some(map.name.clone()),
+            level_author_name: Some(
+                map.metadata
+                    .level_author_name
+                    .clone()
+                    .unwrap_or(map.uploader.name.clone()),
+            ),
+            difficulties,
+        },
+        notes,
+    })
+}
+
+/// Checks a highlight like `Expert+` or `OneSaber/Expert` against the version
+/// being added.
+fn find_difficulty(map: &Map, hash: &str, highlight: &str) -> Result<PlaylistDifficulty, String> {
+    let (characteristic, difficulty) = highlight
+        .split_once('/')
+        .unwrap_or(("Standard", highlight));
+
+    let characteristic = CharacteristicInfo::find(characteristic)
+        .ok_or_else(|| format!("`{characteristic}` isn't a characteristic"))?;
+    let difficulty = DifficultyInfo::find(difficulty)
+        .ok_or_else(|| format!("`{difficulty}` isn't a difficulty"))?;
+
+    let exists = map
+        .versions
+        .iter()
+        .filter(|version| version.hash.eq_ignore_ascii_case(hash))
+        .flat_map(|version| &version.diffs)
+        .any(|diff| {
+            let diff_characteristic = diff.characteristic.to_string();
+            characteristic
+                .names
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(&diff_characteristic))
+                && diff.difficulty.eq_ignore_ascii_case(difficulty.name)
+        });
+    if !exists {
+        return Err(format!(
+            "there's no {} {}",
+            characteristic.display_name, difficulty.display_name
+        ));
+    }
+
+    Ok(PlaylistDifficulty {
+        characteristic: characteristic.names[0].to_owned(),
+        name: difficulty.name.to_owned(),
+    })
+}
+
+/// Checks and downloads an attached cover, returning it with its MIME type.
+async fn load_cover(cover: &Attachment) -> Result<(Vec<u8>, String), String> {
+    let mime = cover
+        .content_type
+        .clone()
+        .filter(|mime| mime.starts_with("image/"))
+        .ok_or("isn't an image, so it was left out")?;
+    if cover.size > MAX_COVER_SIZE {
+        return Err("is over 1 MB, so it was left out".to_owned());
+    }
+
+    let bytes = cover.download().await.map_err(|err| {
+        warn!("Couldn't download playlist cover {}: {err}", cover.url);
+        "couldn't be downloaded, so it was left out".to_owned()
+    })?;
+
+    Ok((bytes, mime))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_on_spaces_and_commas() {
+        assert_eq!(
+            split_entries("25f, 1a2b\n  3c@Expert+"),
+            ["25f", "1a2b", "3c@Expert+"]
+        );
+    }
+
+    #[test]
+    fn keeps_bsr_commands_together() {
+        assert_eq!(
+            split_entries("!bsr 25f !bsr 1a2b@Hard 3c"),
+            ["!bsr 25f", "!bsr 1a2b@Hard", "3c"]
+        );
+        // nothing to join it to, so it's left to fail on its own
+        assert_eq!(split_entries("25f !bsr"), ["25f", "!bsr"]);
+    }
+}
@@ -0,0 +1,54 @@
+use poise::{self, CreateReply};
+
+use crate::commands::beatsaber::link::player_or_linked;
+use crate::core::settings::GuildSettings;
+use crate::core::storage::linked_accounts::Service;
+use crate::ui::scoresaberprofile::build_player_embed;
+use crate::{Context, Error};
+
+/// How many of a player's best scores to show.
+const TOP_PLAYS: usize = 5;
+
+/// Looks things up on ScoreSaber.
+#[poise::command(
+    slash_command,
+    install_context = "Guild|User",
+    interaction_context = "Guild|BotDm|PrivateChannel",
+    subcommands("player"),
+    subcommand_required
+)]
+pub async fn scoresaber(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Shows a ScoreSaber player's rank, PP and top plays.
+#[poise::command(
+    slash_command,
+    install_context = "Guild|User",
+    interaction_context = "Guild|BotDm|PrivateChannel"
+)]
+pub async fn player(
+    ctx: Context<'_>,
+    #[description = "A player ID, profile link or name. Leave out for your linked account."]
+    #[max_length = 100]
+    player: Option<String>,
+) -> Result<(), Error> {
+    let settings = GuildSettings::for_context(ctx).await?;
+    let Some(player) = player_or_linked(ctx, Service::ScoreSaber, player).await? else {
+        return Ok(());
+    };
+    let player = ctx.data().scoresaber.player(&player).await?;
+    let top_plays = ctx
+        .data()
+        .scoresaber
+        .top_scores(&player.id, TOP_PLAYS)
+        .await?;
+
+    let builder = CreateReply::default()
+        .embed(build_player_embed(&player, &top_plays))
+        .ephemeral(settings.ephemeral());
+
+    ctx.send(builder).await?;
+
+    Ok(())
+}
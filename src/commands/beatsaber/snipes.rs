@@ -0,0 +1,163 @@
+use anyhow::anyhow;
+use log::info;
+use poise::{
+    self,
+    serenity_prelude::{Colour, CreateEmbed, CreateEmbedFooter, User},
+    ChoiceParameter, CreateReply,
+};
+
+use crate::core::settings::GuildSettings;
+use crate::utils::text::EmbedBudget;
+use crate::{Context, Error};
+
+/// Controls the snipes posted when linked members beat each other's scores.
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("mute", "unmute", "status"),
+    subcommand_required
+)]
+pub async fn snipes(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Stops posting snipes between you and someone, or you and anyone.
+#[poise::command(slash_command, guild_only)]
+pub async fn mute(
+    ctx: Context<'_>,
+    #[description = "Who to mute. Leave out to mute everyone."] user: Option<User>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or_else(|| anyhow!("not in a guild"))?;
+    let muted = user.as_ref().map(|user| user.id);
+    if muted == Some(ctx.author().id) {
+        ctx.send(
+            CreateReply::default()
+                .content("You can't snipe yourself, so there's nothing to mute.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let added = ctx
+        .data()
+        .storage
+        .snipes()
+        .mute(guild, ctx.author().id, muted)
+        .await?;
+
+    let who = match muted {
+        Some(id) => format!("you and <@{id}>"),
+        None => "you and anyone".to_owned(),
+    };
+    let message = match added {
+        true => {
+            info!(
+                "{} muted snipes with {:?} in guild {}",
+                ctx.author().name,
+                muted,
+                guild
+            );
+            format!("Snipes between {who} won't be posted here anymore.")
+        }
+        false => format!("Snipes between {who} were already muted."),
+    };
+    ctx.send(CreateReply::default().content(message).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+/// Posts snipes between you and someone, or anyone, again.
+#[poise::command(slash_command, guild_only)]
+pub async fn unmute(
+    ctx: Context<'_>,
+    #[description = "Who to unmute. Leave out to undo muting everyone."] user: Option<User>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or_else(|| anyhow!("not in a guild"))?;
+    let muted = user.as_ref().map(|user| user.id);
+
+    let removed = ctx
+        .data()
+        .storage
+        .snipes()
+        .unmute(guild, ctx.author().id, muted)
+        .await?;
+
+    let message = match (removed, muted) {
+        (true, Some(id)) => format!("Snipes between you and <@{id}> can be posted again."),
+        (true, None) => "You're no longer muted for everyone. Anyone you muted on their own \
+                         stays muted."
+            .to_owned(),
+        (false, Some(id)) => format!("You hadn't muted <@{id}>."),
+        (false, None) => "You hadn't muted everyone.".to_owned(),
+    };
+    if removed {
+        info!(
+            "{} unmuted snipes with {:?} in guild {}",
+            ctx.author().name,
+            muted,
+            guild
+        );
+    }
+    ctx.send(CreateReply::default().content(message).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+/// Shows whether snipes are on here, and who you've muted.
+#[poise::command(slash_command, guild_only)]
+pub async fn status(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or_else(|| anyhow!("not in a guild"))?;
+    let settings = GuildSettings::load(&ctx.data().storage, Some(guild)).await?;
+    let mutes = ctx
+        .data()
+        .storage
+        .snipes()
+        .mutes(guild, ctx.author().id)
+        .await?;
+
+    let posting = match settings.snipe_channel {
+        Some(channel) => format!(
+            "Posted in <#{channel}>, up to {} an hour, from {} scores.",
+            settings.snipe_limit,
+            settings.leaderboard.name()
+        ),
+        None => "Off. A server manager can turn them on with `/settings snipe_channel`.".to_owned(),
+    };
+    let muted = match mutes.is_empty() {
+        true => "Nobody".to_owned(),
+        false => mutes
+            .iter()
+            .map(|muted| match muted {
+                Some(id) => format!("<@{id}>"),
+                None => "Everyone".to_owned(),
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+
+    let mut budget = EmbedBudget::new();
+    let embed = CreateEmbed::new()
+        .title(budget.title("Snipes"))
+        .field(
+            budget.field_name("Snipes here"),
+            budget.field_value(&posting),
+            false,
+        )
+        .field(
+            budget.field_name("You've muted"),
+            budget.field_value(&muted),
+            false,
+        )
+        .colour(Colour::from_rgb(68, 68, 68))
+        .footer(CreateEmbedFooter::new(
+            budget.footer("Link an account with /link to take part"),
+        ));
+
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,169 @@
+use anyhow::anyhow;
+use log::info;
+use poise::{
+    self,
+    serenity_prelude::{Colour, CreateEmbed, CreateEmbedFooter, GuildChannel},
+    CreateReply,
+};
+
+use crate::utils::discord::autocomplete::beatsaver::autocomplete_mapper;
+use crate::utils::discord::autocomplete::subscriptions::autocomplete_subscription;
+use crate::utils::text::{sanitise, EmbedBudget};
+use crate::{Context, Error};
+
+/// Every followed mapper is another request to BeatSaver each poll.
+const MAX_SUBSCRIPTIONS: usize = 25;
+
+/// Posts a mapper's new maps to a channel as they're uploaded.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_GUILD",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn subscribe(
+    ctx: Context<'_>,
+    #[description = "The mapper's BeatSaver username."]
+    #[autocomplete = "autocomplete_mapper"]
+    mapper: String,
+    #[description = "Where to post their new maps."]
+    #[channel_types("Text", "News")]
+    channel: GuildChannel,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or_else(|| anyhow!("not in a guild"))?;
+    let repo = ctx.data().storage.subscriptions();
+
+    if repo.for_guild(guild).await?.len() >= MAX_SUBSCRIPTIONS {
+        let message = format!(
+            "This server already follows {MAX_SUBSCRIPTIONS} mappers, unsubscribe from one first."
+        );
+        ctx.send(CreateReply::default().content(message).ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+
+    let mapper = ctx.data().beatsaver.mapper(&mapper).await?;
+    let message = match repo
+        .add(guild, channel.id, mapper.id, &mapper.name, ctx.author().id)
+        .await?
+    {
+        true => {
+            info!(
+                "{} subscribed channel {} to mapper {} ({})",
+                ctx.author().name,
+                channel.id,
+                mapper.name,
+                mapper.id
+            );
+            format!(
+                "New maps from **{}** will be posted in <#{}>.",
+                sanitise(&mapper.name),
+                channel.id
+            )
+        }
+        false => format!(
+            "<#{}> already gets new maps from **{}**.",
+            channel.id,
+            sanitise(&mapper.name)
+        ),
+    };
+
+    ctx.send(CreateReply::default().content(message).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+/// Stops posting a mapper's new maps.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_GUILD",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn unsubscribe(
+    ctx: Context<'_>,
+    #[description = "The mapper, as shown in /subscriptions."]
+    #[autocomplete = "autocomplete_subscription"]
+    mapper: String,
+    #[description = "Only stop posting in this channel. Leave out for every channel."]
+    #[channel_types("Text", "News")]
+    channel: Option<GuildChannel>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or_else(|| anyhow!("not in a guild"))?;
+    let removed = ctx
+        .data()
+        .storage
+        .subscriptions()
+        .remove(guild, mapper.trim(), channel.as_ref().map(|c| c.id))
+        .await?;
+
+    let message = match (removed, &channel) {
+        (0, Some(channel)) => format!(
+            "<#{}> isn't subscribed to **{}**.",
+            channel.id,
+            sanitise(&mapper)
+        ),
+        (0, None) => format!("This server isn't subscribed to **{}**.", sanitise(&mapper)),
+        (_, Some(channel)) => format!(
+            "New maps from **{}** won't be posted in <#{}> anymore.",
+            sanitise(&mapper),
+            channel.id
+        ),
+        (count, None) => format!(
+            "New maps from **{}** won't be posted anymore ({count} channel(s)).",
+            sanitise(&mapper)
+        ),
+    };
+    if removed > 0 {
+        info!("{} unsubscribed guild {} from {}", ctx.author().name, guild, mapper);
+    }
+
+    ctx.send(CreateReply::default().content(message).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+/// Lists the mappers this server follows.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_GUILD",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn subscriptions(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or_else(|| anyhow!("not in a guild"))?;
+    let subscriptions = ctx.data().storage.subscriptions().for_guild(guild).await?;
+
+    let description = match subscriptions.is_empty() {
+        true => "Nothing yet. Follow a mapper with `/subscribe`.".to_owned(),
+        false => subscriptions
+            .iter()
+            .map(|subscription| {
+                format!(
+                    "**{}** in <#{}>, since <t:{}:d>",
+                    sanitise(&subscription.mapper_name),
+                    subscription.channel_id,
+                    subscription.created_at
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+
+    let mut budget = EmbedBudget::new();
+    let embed = CreateEmbed::new()
+        .title(format!(
+            "Subscriptions ({}/{MAX_SUBSCRIPTIONS})",
+            subscriptions.len()
+        ))
+        .description(budget.description(&description))
+        .colour(Colour::from_rgb(166, 149, 255))
+        .footer(CreateEmbedFooter::new("New maps are checked every few minutes"));
+
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
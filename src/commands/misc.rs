@@ -1,2 +1,4 @@
+pub mod cache;
+pub mod settings;
 pub mod status;
 pub mod sync;
@@ -0,0 +1,60 @@
+use poise::{self, serenity_prelude::CreateEmbed, CreateReply};
+
+use crate::{Context, Error};
+
+/// Manages the BeatSaver cache.
+#[poise::command(
+    prefix_command,
+    owners_only,
+    subcommands("stats", "clear", "invalidate"),
+    subcommand_required
+)]
+pub async fn cache(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Shows cache sizes and hit rates.
+#[poise::command(prefix_command, owners_only)]
+pub async fn stats(ctx: Context<'_>) -> Result<(), Error> {
+    let mut embed = CreateEmbed::new().title("BeatSaver cache");
+
+    for (name, stats) in ctx.data().beatsaver.stats() {
+        embed = embed.field(
+            name,
+            format!(
+                "{}/{} entries, {}s TTL\n{} hits, {} misses ({:.1}%)",
+                stats.entries,
+                stats.capacity,
+                stats.ttl.as_secs(),
+                stats.hits,
+                stats.misses,
+                stats.hit_rate() * 100.0
+            ),
+            false,
+        );
+    }
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Empties the whole cache.
+#[poise::command(prefix_command, owners_only)]
+pub async fn clear(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.data().beatsaver.clear();
+    ctx.say("Cleared the BeatSaver cache.").await?;
+    Ok(())
+}
+
+/// Drops one map (by ID or hash), or every search with `searches`.
+#[poise::command(prefix_command, owners_only)]
+pub async fn invalidate(ctx: Context<'_>, key: String) -> Result<(), Error> {
+    let removed = if key == "searches" {
+        ctx.data().beatsaver.invalidate_searches()
+    } else {
+        ctx.data().beatsaver.invalidate(&key)
+    };
+
+    ctx.say(format!("Removed {removed} cache entries.")).await?;
+    Ok(())
+}
@@ -0,0 +1,275 @@
+use anyhow::anyhow;
+use log::{info, warn};
+use poise::{
+    self,
+    serenity_prelude::{
+        ChannelId, Colour, CreateEmbed, CreateEmbedFooter, CreateMessage, GuildChannel,
+    },
+    ChoiceParameter, CreateReply,
+};
+
+use crate::{
+    core::settings::{choice_value, GuildSettings, Layout, Leaderboard, SettingName, Visibility},
+    Context, Error,
+};
+
+/// Changes how the bot behaves in this server.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_GUILD",
+    required_permissions = "MANAGE_GUILD",
+    subcommands(
+        "show",
+        "visibility",
+        "restrict_selections",
+        "leaderboard",
+        "layout",
+        "log_channel",
+        "snipe_channel",
+        "snipe_limit",
+        "reset"
+    ),
+    subcommand_required
+)]
+pub async fn settings(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Shows this server's settings.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub async fn show(ctx: Context<'_>) -> Result<(), Error> {
+    let settings = GuildSettings::load(&ctx.data().storage, ctx.guild_id()).await?;
+
+    let embed = CreateEmbed::new()
+        .title("Server settings")
+        .fields([
+            ("Reply visibility", settings.visibility.name().to_owned(), false),
+            (
+                "Restrict selections",
+                match settings.restrict_selections {
+                    true => "Only whoever used `/bsr` can use its menus".to_owned(),
+                    false => "Anyone can use `/bsr` menus".to_owned(),
+                },
+                false,
+            ),
+            ("Leaderboard", settings.leaderboard.name().to_owned(), true),
+            ("Layout", settings.layout.name().to_owned(), true),
+            (
+                "Log channel",
+                match settings.log_channel {
+                    Some(channel) => format!("<#{channel}>"),
+                    None => "None".to_owned(),
+                },
+                true,
+            ),
+            (
+                "Snipe channel",
+                match settings.snipe_channel {
+                    Some(channel) => format!("<#{channel}>"),
+                    None => "None (snipes are off)".to_owned(),
+                },
+                true,
+            ),
+            (
+                "Snipe limit",
+                format!("{} per hour", settings.snipe_limit),
+                true,
+            ),
+        ])
+        .colour(Colour::from_rgb(68, 68, 68))
+        .footer(CreateEmbedFooter::new("Change these with /settings"));
+
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+/// Sets who can see the bot's replies.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub async fn visibility(
+    ctx: Context<'_>,
+    #[description = "Who sees replies to commands."] visibility: Visibility,
+) -> Result<(), Error> {
+    save(
+        ctx,
+        SettingName::Visibility,
+        &choice_value(visibility),
+        visibility.name(),
+    )
+    .await
+}
+
+/// Sets whether only whoever used `/bsr` can use its menus.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub async fn restrict_selections(
+    ctx: Context<'_>,
+    #[description = "Only let whoever used the command change what it shows."]
+    restrict: bool,
+) -> Result<(), Error> {
+    let shown = match restrict {
+        true => "On",
+        false => "Off",
+    };
+    save(
+        ctx,
+        SettingName::RestrictSelections,
+        &restrict.to_string(),
+        shown,
+    )
+    .await
+}
+
+/// Sets which leaderboard scores come from.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub async fn leaderboard(
+    ctx: Context<'_>,
+    #[description = "Where scores come from."] leaderboard: Leaderboard,
+) -> Result<(), Error> {
+    save(
+        ctx,
+        SettingName::Leaderboard,
+        &choice_value(leaderboard),
+        leaderboard.name(),
+    )
+    .await
+}
+
+/// Sets how much map embeds show.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub async fn layout(
+    ctx: Context<'_>,
+    #[description = "Compact leaves out the description and extra details."] layout: Layout,
+) -> Result<(), Error> {
+    save(ctx, SettingName::Layout, &choice_value(layout), layout.name()).await
+}
+
+/// Sets where setting changes and errors get posted.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub async fn log_channel(
+    ctx: Context<'_>,
+    #[description = "A text channel the bot can post in."]
+    #[channel_types("Text")]
+    channel: GuildChannel,
+) -> Result<(), Error> {
+    save(
+        ctx,
+        SettingName::LogChannel,
+        &channel.id.to_string(),
+        &format!("<#{}>", channel.id),
+    )
+    .await
+}
+
+/// Sets where linked members sniping each other's scores gets posted.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub async fn snipe_channel(
+    ctx: Context<'_>,
+    #[description = "A text channel the bot can post in."]
+    #[channel_types("Text")]
+    channel: GuildChannel,
+) -> Result<(), Error> {
+    save(
+        ctx,
+        SettingName::SnipeChannel,
+        &channel.id.to_string(),
+        &format!("<#{}>", channel.id),
+    )
+    .await
+}
+
+/// Sets how many snipes can be posted an hour.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub async fn snipe_limit(
+    ctx: Context<'_>,
+    #[description = "Snipes per hour. Any past that are skipped."]
+    #[min = 1]
+    #[max = 60]
+    limit: u32,
+) -> Result<(), Error> {
+    save(
+        ctx,
+        SettingName::SnipeLimit,
+        &limit.to_string(),
+        &format!("{limit} per hour"),
+    )
+    .await
+}
+
+/// Puts a setting, or all of them, back to the default.
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub async fn reset(
+    ctx: Context<'_>,
+    #[description = "Which setting to reset. Leave out to reset everything."]
+    setting: Option<SettingName>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or_else(|| anyhow!("not in a guild"))?;
+    let repo = ctx.data().storage.guild_settings();
+
+    // read before resetting, so the log channel still gets told
+    let before = GuildSettings::load(&ctx.data().storage, Some(guild)).await?;
+
+    let message = match setting {
+        Some(setting) => match repo.reset(guild, setting.key()).await? {
+            true => format!("{} is back to the default.", setting.name()),
+            false => format!("{} was already the default.", setting.name()),
+        },
+        None => {
+            let count = repo.reset_all(guild).await?;
+            format!("Reset {count} setting(s) to the defaults.")
+        }
+    };
+
+    info!("{} in guild {}: {}", ctx.author().name, guild, message);
+    ctx.send(CreateReply::default().content(&message).ephemeral(true))
+        .await?;
+    notify_log_channel(ctx, before.log_channel, &message).await;
+
+    Ok(())
+}
+
+/// Stores a setting and lets everyone concerned know.
+async fn save(
+    ctx: Context<'_>,
+    setting: SettingName,
+    value: &str,
+    shown: &str,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or_else(|| anyhow!("not in a guild"))?;
+
+    ctx.data()
+        .storage
+        .guild_settings()
+        .set(guild, setting.key(), value, ctx.author().id)
+        .await?;
+
+    let message = format!("{} is now {}.", setting.name(), shown);
+    info!("{} in guild {}: {}", ctx.author().name, guild, message);
+    ctx.send(CreateReply::default().content(&message).ephemeral(true))
+        .await?;
+
+    let settings = GuildSettings::load(&ctx.data().storage, Some(guild)).await?;
+    notify_log_channel(ctx, settings.log_channel, &message).await;
+
+    Ok(())
+}
+
+/// Posts a settings change to the log channel, if there is one.
+async fn notify_log_channel(ctx: Context<'_>, channel: Option<ChannelId>, message: &str) {
+    let Some(channel) = channel else {
+        return;
+    };
+
+    let embed = CreateEmbed::new()
+        .title("Settings changed")
+        .description(format!("{message}\nChanged by <@{}>", ctx.author().id))
+        .colour(Colour::from_rgb(68, 68, 68));
+
+    if let Err(err) = channel
+        .send_message(ctx, CreateMessage::new().embed(embed))
+        .await
+    {
+        warn!("Couldn't post to log channel {channel}: {err}");
+    }
+}
@@ -18,7 +18,7 @@ pub async fn status(ctx: Context<'_>) -> Result<(), Error> {
     let status_embed = CreateEmbed::new()
         .title(format!("Mafuyu v{}", env!("CARGO_PKG_VERSION")))
         .description("A (not-so) general purpose Discord application.")
-        .field("Source", "https://github.com/mercurialworld/mafuyu", false)
+        .field("Source", &ctx.data().config.status.source_url, false)
         .footer(CreateEmbedFooter::new(
             "Made by @mercurial_world on Discord",
         ));
@@ -1,8 +1,8 @@
-use poise;
-use crate::{Context, Error};
-
-#[poise::command(prefix_command, owners_only)]
-pub async fn sync(ctx: Context<'_>) -> Result<(), Error> {
-    poise::builtins::register_application_commands_buttons(ctx).await?;
-    Ok(())
+use poise;
+use crate::{Context, Error};
+
+#[poise::command(prefix_command, owners_only)]
+pub async fn sync(ctx: Context<'_>) -> Result<(), Error> {
+    poise::builtins::register_application_commands_buttons(ctx).await?;
+    Ok(())
 }
\ No newline at end of file
@@ -1 +1,10 @@
-pub mod bot;
+pub mod bot;
+pub mod config;
+pub mod error;
+pub mod interactions;
+pub mod serverleaderboard;
+pub mod settings;
+pub mod shutdown;
+pub mod snipes;
+pub mod storage;
+pub mod subscriptions;
@@ -1,104 +1,172 @@
-use beatsaver_api::client::BeatSaverClient;
-use log::{debug, info, warn};
-use poise::{
-    serenity_prelude::{self as serenity, CreateEmbed, GatewayIntents},
-    CreateReply,
-};
-use regex::Regex;
-
-use crate::{commands, Data};
-
-pub struct Mafuyu {
-    pub client: serenity::Client,
-}
-
-impl Mafuyu {
-    pub async fn new(token: &str, intents: GatewayIntents) -> Self {
-        let framework = poise::Framework::builder()
-            .options(poise::FrameworkOptions {
-                commands: vec![
-                    commands::beatsaber::beatsaver::bsr(),
-                    commands::misc::status::status(),
-                    commands::misc::sync::sync(),
-                ],
-                pre_command: |ctx| {
-                    Box::pin(async move {
-                        let author = ctx.author();
-
-                        match ctx {
-                            poise::Context::Application(app_ctx) => info!(
-                                "{} used app command {} with options {:?}",
-                                author.name,
-                                app_ctx.interaction.data.name,
-                                app_ctx.interaction.data.options
-                            ),
-                            poise::Context::Prefix(pfx_ctx) => {
-                                info!(
-                                    "{} used prefix command {}",
-                                    author.name, pfx_ctx.msg.content
-                                )
-                            }
-                        }
-                    })
-                },
-                on_error: |error| {
-                    Box::pin(async move {
-                        warn!("{:?}", error.to_string());
-
-                        match error {
-                            poise::FrameworkError::Command { error, ctx, .. } => {
-                                let mut error_description: String = "".to_string();
-
-                                error.chain().skip(1).for_each(|cause| {
-                                    warn!("because: {cause:?}");
-                                    error_description.push_str(&format!("because: {cause}\n"));
-                                });
-
-                                let embed = CreateEmbed::new()
-                                    .title(format!("Error: {error}"))
-                                    .description(error_description);
-
-                                let builder = CreateReply::default().embed(embed).ephemeral(true);
-                                let _ = ctx.send(builder).await;
-                            }
-                            other => poise::builtins::on_error(other).await.unwrap(),
-                        }
-                    })
-                },
-                ..Default::default()
-            })
-            .setup(|ctx, _ready, framework| {
-                Box::pin(async move {
-                    poise::builtins::register_globally(ctx, &framework.options().commands).await?;
-
-                    debug!("Setting activity text");
-                    ctx.set_activity(Some(serenity::ActivityData::custom(format!(
-                        "v{}",
-                        env!("CARGO_PKG_VERSION")
-                    ))));
-
-                    let beatsaver_client = BeatSaverClient::default();
-                    let bsr_link_regex = Regex::new(
-                        r"(?:https?://)?(?:www\.)?beatsaver\.com/maps/(?P<bsr>[a-fA-F0-9]+)",
-                    )
-                    .unwrap();
-                    let hexstring_regex = Regex::new(r"^[a-fA-F0-9]+$").unwrap();
-
-                    info!("Mafuyu started!");
-                    Ok(Data {
-                        beatsaver_client,
-                        bsr_link_regex,
-                        hexstring_regex,
-                    })
-                })
-            })
-            .build();
-
-        let client = serenity::ClientBuilder::new(token, intents)
-            .framework(framework)
-            .await
-            .expect("Unable to create framework");
-
-        Self { client }
-    }
-}
+use std::sync::Arc;
+
+use log::{debug, info, warn};
+use poise::{
+    serenity_prelude::{self as serenity},
+    CreateReply,
+};
+
+use crate::{
+    clients::{
+        beatleader::BeatLeader,
+        beatsaver::{feed::BeatSaverFeed, BeatSaver},
+        scoresaber::ScoreSaber,
+    },
+    commands,
+    core::{
+        config::Config, error::ErrorReport, interactions, serverleaderboard::ServerLeaderboards,
+        shutdown::Shutdown, snipes::SnipeWatcher, storage::Storage,
+        subscriptions::SubscriptionPoller,
+    },
+    utils::discord::debounce::Debouncer,
+    Data,
+};
+
+pub struct Mafuyu {
+    pub client: serenity::Client,
+}
+
+impl Mafuyu {
+    pub async fn new(config: Config, shutdown: Shutdown, storage: Storage) -> Self {
+        let token = config.discord.token.clone();
+        let intents = config.discord.intents;
+
+        let framework = poise::Framework::builder()
+            .options(poise::FrameworkOptions {
+                commands: vec![
+                    commands::beatsaber::beatleader::beatleader(),
+                    commands::beatsaber::beatsaver::bsr(),
+                    commands::beatsaber::beatsaver::search(),
+                    commands::beatsaber::beatsaver::mapper(),
+                    commands::beatsaber::link::link(),
+                    commands::beatsaber::link::unlink(),
+                    commands::beatsaber::link::whois(),
+                    commands::beatsaber::playlist::playlist(),
+                    commands::beatsaber::scoresaber::scoresaber(),
+                    commands::beatsaber::snipes::snipes(),
+                    commands::beatsaber::subscriptions::subscribe(),
+                    commands::beatsaber::subscriptions::unsubscribe(),
+                    commands::beatsaber::subscriptions::subscriptions(),
+                    commands::misc::cache::cache(),
+                    commands::misc::settings::settings(),
+                    commands::misc::status::status(),
+                    commands::misc::sync::sync(),
+                ],
+                pre_command: |ctx| {
+                    Box::pin(async move {
+                        // poise drops this after on_error, however the command ends
+                        ctx.set_invocation_data(ctx.data().shutdown.command_guard())
+                            .await;
+                        let author = ctx.author();
+
+                        interactions::note_seen(ctx.data(), ctx.guild_id(), author);
+
+                        match ctx {
+                            poise::Context::Application(app_ctx) => info!(
+                                "{} used app command {} with options {:?}",
+                                author.name,
+                                app_ctx.interaction.data.name,
+                                app_ctx.interaction.data.options
+                            ),
+                            poise::Context::Prefix(pfx_ctx) => {
+                                info!(
+                                    "{} used prefix command {}",
+                                    author.name, pfx_ctx.msg.content
+                                )
+                            }
+                        }
+                    })
+                },
+                on_error: |error| {
+                    Box::pin(async move {
+                        warn!("{:?}", error.to_string());
+
+                        match error {
+                            poise::FrameworkError::Command { error, ctx, .. } => {
+                                let report = ErrorReport::new(&error);
+                                report.log(&error);
+
+                                let builder =
+                                    CreateReply::default().embed(report.embed()).ephemeral(true);
+                                let _ = ctx.send(builder).await;
+
+                                report
+                                    .notify_log_channel(
+                                        ctx,
+                                        &ctx.data().storage,
+                                        ctx.guild_id(),
+                                        &format!("/{}", ctx.command().qualified_name),
+                                    )
+                                    .await;
+                            }
+                            other => poise::builtins::on_error(other).await.unwrap(),
+                        }
+                    })
+                },
+                event_handler: |ctx, event, framework, data| {
+                    Box::pin(interactions::event_handler(ctx, event, framework, data))
+                },
+                ..Default::default()
+            })
+            .setup(move |ctx, _ready, framework| {
+                Box::pin(async move {
+                    poise::builtins::register_globally(ctx, &framework.options().commands).await?;
+
+                    debug!("Setting activity text");
+                    ctx.set_activity(Some(serenity::ActivityData::custom(format!(
+                        "v{}",
+                        env!("CARGO_PKG_VERSION")
+                    ))));
+
+                    let beatsaver = Arc::new(BeatSaver::new(&config.beatsaver, &config.cache));
+                    let scoresaber = ScoreSaber::new(&config.scoresaber);
+                    let beatleader = BeatLeader::new(&config.beatleader);
+
+                    let feed = Arc::new(BeatSaverFeed::new(&config.beatsaver.feed_url));
+
+                    let poller = SubscriptionPoller::new(
+                        ctx.http.clone(),
+                        beatsaver.clone(),
+                        storage.clone(),
+                        shutdown.clone(),
+                        config.subscriptions.poll_interval,
+                        feed.subscribe(),
+                    );
+                    tokio::spawn(poller.run());
+                    tokio::spawn(feed.clone().run(shutdown.clone()));
+
+                    let watcher = SnipeWatcher::new(
+                        ctx.http.clone(),
+                        ScoreSaber::new(&config.scoresaber),
+                        BeatLeader::new(&config.beatleader),
+                        beatsaver.clone(),
+                        storage.clone(),
+                        shutdown.clone(),
+                        config.snipes.poll_interval,
+                    );
+                    tokio::spawn(watcher.run());
+
+                    info!("Mafuyu started!");
+                    Ok(Data {
+                        config,
+                        shutdown,
+                        beatsaver,
+                        scoresaber,
+                        beatleader,
+                        server_leaderboards: ServerLeaderboards::new(),
+                        debouncer: Debouncer::new(),
+                        storage,
+                        feed,
+                    })
+                })
+            })
+            .build();
+
+        let client = serenity::ClientBuilder::new(&token, intents)
+            .framework(framework)
+            .await
+            .expect("Unable to create framework");
+
+        Self { client }
+    }
+}
@@ -1 +1,668 @@
-
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use log::{debug, info};
+use poise::serenity_prelude::GatewayIntents;
+use regex::Regex;
+use serde::Deserialize;
+
+/// Config file that gets picked up if `MAFUYU_CONFIG` isn't set.
+const DEFAULT_CONFIG_PATH: &str = "mafuyu.toml";
+
+const DEFAULT_HTTP_PORT: u16 = 5000;
+const DEFAULT_BSR_LINK_PATTERN: &str =
+    r"(?:https?://)?(?:www\.)?beatsaver\.com/maps/(?P<bsr>[a-fA-F0-9]+)";
+const DEFAULT_BSR_CODE_PATTERN: &str = r"^[a-fA-F0-9]+$";
+const DEFAULT_BSR_HASH_PATTERN: &str = r"\b[a-fA-F0-9]{40}\b";
+const DEFAULT_SOURCE_URL: &str = "https://github.com/mercurialworld/mafuyu";
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_BEATSAVER_API_URL: &str = "https://api.beatsaver.com";
+const DEFAULT_BEATSAVER_FEED_URL: &str = "wss://ws.beatsaver.com/maps";
+const DEFAULT_SCORESABER_API_URL: &str = "https://scoresaber.com/api";
+const DEFAULT_BEATLEADER_API_URL: &str = "https://api.beatleader.com";
+const DEFAULT_DATABASE: &str = "mafuyu.db";
+const DEFAULT_POLL_INTERVAL_SECS: u64 = 5 * 60;
+/// BeatSaver gets one request per followed mapper every poll, so don't go wild.
+const MIN_POLL_INTERVAL_SECS: u64 = 60;
+const DEFAULT_SNIPES_POLL_INTERVAL_SECS: u64 = 10 * 60;
+/// Every poll looks up each linked member's recent scores, and then everyone
+/// else's score on whatever they played.
+const MIN_SNIPES_POLL_INTERVAL_SECS: u64 = 60;
+const DEFAULT_CACHE_MAPS: CacheLimits = CacheLimits::new(1000, 10 * 60);
+const DEFAULT_CACHE_HASHES: CacheLimits = CacheLimits::new(1000, 60 * 60);
+const DEFAULT_CACHE_SEARCHES: CacheLimits = CacheLimits::new(500, 2 * 60);
+
+/// Bot configuration, checked once at startup.
+///
+/// Values are read from (in increasing priority) the built-in defaults, the
+/// TOML config file, and environment variables.
+#[derive(Clone)]
+pub struct Config {
+    pub discord: DiscordConfig,
+    pub http: HttpConfig,
+    pub beatsaver: BeatSaverConfig,
+    pub scoresaber: ScoreSaberConfig,
+    pub beatleader: BeatLeaderConfig,
+    pub status: StatusConfig,
+    pub shutdown: ShutdownConfig,
+    pub cache: CacheConfig,
+    pub storage: StorageConfig,
+    pub subscriptions: SubscriptionsConfig,
+    pub snipes: SnipesConfig,
+}
+
+#[derive(Clone)]
+pub struct DiscordConfig {
+    pub token: String,
+    pub intents: GatewayIntents,
+}
+
+#[derive(Clone)]
+pub struct HttpConfig {
+    pub port: u16,
+}
+
+#[derive(Clone)]
+pub struct BeatSaverConfig {
+    /// Base URL for requests the BeatSaver client doesn't cover.
+    pub api_url: String,
+    /// WebSocket that pushes map updates as they happen.
+    pub feed_url: String,
+    /// Matches BeatSaver map links, with the map ID in the `bsr` group.
+    pub link_regex: Regex,
+    /// Matches a bare map ID.
+    pub code_regex: Regex,
+    /// Matches map version hashes anywhere in the input.
+    pub hash_regex: Regex,
+}
+
+#[derive(Clone)]
+pub struct ScoreSaberConfig {
+    /// Base URL for the API, e.g. `https://scoresaber.com/api`.
+    pub api_url: String,
+}
+
+#[derive(Clone)]
+pub struct BeatLeaderConfig {
+    /// Base URL for the API, e.g. `https://api.beatleader.com`.
+    pub api_url: String,
+}
+
+#[derive(Clone)]
+pub struct StatusConfig {
+    pub source_url: String,
+}
+
+#[derive(Clone)]
+pub struct ShutdownConfig {
+    /// How long to wait for running commands before disconnecting anyway.
+    pub timeout: Duration,
+}
+
+#[derive(Clone)]
+pub struct CacheConfig {
+    pub maps: CacheLimits,
+    pub hashes: CacheLimits,
+    pub searches: CacheLimits,
+}
+
+#[derive(Clone)]
+pub struct StorageConfig {
+    /// Path to the SQLite database, or `:memory:` to keep nothing on disk.
+    pub database: String,
+}
+
+#[derive(Clone)]
+pub struct SubscriptionsConfig {
+    /// How often to check followed mappers for new uploads.
+    pub poll_interval: Duration,
+}
+
+#[derive(Clone)]
+pub struct SnipesConfig {
+    /// How often to check linked members for new scores.
+    pub poll_interval: Duration,
+}
+
+/// Size and lifetime of one kind of cached entry.
+#[derive(Clone, Copy)]
+pub struct CacheLimits {
+    pub capacity: usize,
+    pub ttl: Duration,
+}
+
+impl CacheLimits {
+    const fn new(capacity: usize, ttl_secs: u64) -> Self {
+        Self {
+            capacity,
+            ttl: Duration::from_secs(ttl_secs),
+        }
+    }
+}
+
+// MARK: Raw config file
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    discord: DiscordSection,
+    http: HttpSection,
+    beatsaver: BeatSaverSection,
+    scoresaber: ScoreSaberSection,
+    beatleader: BeatLeaderSection,
+    status: StatusSection,
+    shutdown: ShutdownSection,
+    cache: CacheSection,
+    storage: StorageSection,
+    subscriptions: SubscriptionsSection,
+    snipes: SnipesSection,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DiscordSection {
+    token: Option<String>,
+    intents: Option<Vec<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct HttpSection {
+    port: Option<u16>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BeatSaverSection {
+    api_url: Option<String>,
+    feed_url: Option<String>,
+    link_pattern: Option<String>,
+    code_pattern: Option<String>,
+    hash_pattern: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ScoreSaberSection {
+    api_url: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BeatLeaderSection {
+    api_url: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct StatusSection {
+    source_url: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ShutdownSection {
+    timeout_secs: Option<u64>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CacheSection {
+    maps: CacheLimitsSection,
+    hashes: CacheLimitsSection,
+    searches: CacheLimitsSection,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct StorageSection {
+    database: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SubscriptionsSection {
+    poll_interval_secs: Option<u64>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SnipesSection {
+    poll_interval_secs: Option<u64>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CacheLimitsSection {
+    capacity: Option<usize>,
+    ttl_secs: Option<u64>,
+}
+
+impl ConfigFile {
+    fn read(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("couldn't read config file {}", path.display()))?;
+
+        toml::from_str(&contents)
+            .with_context(|| format!("couldn't parse config file {}", path.display()))
+    }
+}
+
+// MARK: Loading
+
+impl Config {
+    /// Loads the config from the config file and environment.
+    ///
+    /// The config file is `MAFUYU_CONFIG` if set (and must exist), otherwise
+    /// `mafuyu.toml` in the working directory if there is one.
+    pub fn load() -> Result<Self> {
+        let file = match env_var("MAFUYU_CONFIG") {
+            Some(path) => {
+                let path = PathBuf::from(path);
+                info!("Loading config from {}", path.display());
+                ConfigFile::read(&path)?
+            }
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                info!("Loading config from {DEFAULT_CONFIG_PATH}");
+                ConfigFile::read(Path::new(DEFAULT_CONFIG_PATH))?
+            }
+            None => {
+                debug!("No config file found, using environment only");
+                ConfigFile::default()
+            }
+        };
+
+        Self::from_file(file, env_var).context("invalid configuration")
+    }
+
+    /// Builds the config from a parsed file, with `env` looking up
+    /// environment variables on top.
+    fn from_file(file: ConfigFile, env: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let token = env("DISCORD_TOKEN")
+            .or(file.discord.token)
+            .filter(|token| !token.trim().is_empty())
+            .ok_or_else(|| anyhow!("missing Discord token (set DISCORD_TOKEN or discord.token)"))?;
+
+        let intents = match env("MAFUYU_DISCORD_INTENTS") {
+            Some(list) => parse_intents(list.split(','))
+                .context("MAFUYU_DISCORD_INTENTS is invalid")?,
+            None => match file.discord.intents {
+                Some(list) => {
+                    parse_intents(list.iter()).context("discord.intents is invalid")?
+                }
+                None => GatewayIntents::non_privileged(),
+            },
+        };
+
+        let port = match env("MAFUYU_HTTP_PORT") {
+            Some(port) => parse_value("MAFUYU_HTTP_PORT", &port)?,
+            None => file.http.port.unwrap_or(DEFAULT_HTTP_PORT),
+        };
+        if port == 0 {
+            bail!("HTTP port must be between 1 and 65535");
+        }
+
+        let link_regex = compile_regex(
+            "beatsaver.link_pattern",
+            env("MAFUYU_BSR_LINK_PATTERN")
+                .or(file.beatsaver.link_pattern)
+                .as_deref()
+                .unwrap_or(DEFAULT_BSR_LINK_PATTERN),
+        )?;
+        if !link_regex.capture_names().any(|name| name == Some("bsr")) {
+            bail!("beatsaver.link_pattern must have a capture group named `bsr`");
+        }
+
+        let api_url = env("MAFUYU_BEATSAVER_API_URL")
+            .or(file.beatsaver.api_url)
+            .unwrap_or(DEFAULT_BEATSAVER_API_URL.to_owned());
+        check_url("beatsaver.api_url", &api_url)?;
+        let api_url = api_url.trim_end_matches('/').to_owned();
+
+        let feed_url = env("MAFUYU_BEATSAVER_FEED_URL")
+            .or(file.beatsaver.feed_url)
+            .unwrap_or(DEFAULT_BEATSAVER_FEED_URL.to_owned());
+        check_ws_url("beatsaver.feed_url", &feed_url)?;
+
+        let scoresaber_api_url = env("MAFUYU_SCORESABER_API_URL")
+            .or(file.scoresaber.api_url)
+            .unwrap_or(DEFAULT_SCORESABER_API_URL.to_owned());
+        check_url("scoresaber.api_url", &scoresaber_api_url)?;
+
+        let beatleader_api_url = env("MAFUYU_BEATLEADER_API_URL")
+            .or(file.beatleader.api_url)
+            .unwrap_or(DEFAULT_BEATLEADER_API_URL.to_owned());
+        check_url("beatleader.api_url", &beatleader_api_url)?;
+
+        let code_regex = compile_regex(
+            "beatsaver.code_pattern",
+            env("MAFUYU_BSR_CODE_PATTERN")
+                .or(file.beatsaver.code_pattern)
+                .as_deref()
+                .unwrap_or(DEFAULT_BSR_CODE_PATTERN),
+        )?;
+
+        let hash_regex = compile_regex(
+            "beatsaver.hash_pattern",
+            env("MAFUYU_BSR_HASH_PATTERN")
+                .or(file.beatsaver.hash_pattern)
+                .as_deref()
+                .unwrap_or(DEFAULT_BSR_HASH_PATTERN),
+        )?;
+
+        let source_url = env("MAFUYU_SOURCE_URL")
+            .or(file.status.source_url)
+            .unwrap_or(DEFAULT_SOURCE_URL.to_owned());
+        check_url("status.source_url", &source_url)?;
+
+        let shutdown_timeout_secs = match env("MAFUYU_SHUTDOWN_TIMEOUT_SECS") {
+            Some(secs) => parse_value("MAFUYU_SHUTDOWN_TIMEOUT_SECS", &secs)?,
+            None => file
+                .shutdown
+                .timeout_secs
+                .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_SECS),
+        };
+
+        let cache = CacheConfig {
+            maps: cache_limits(&env, "maps", file.cache.maps, DEFAULT_CACHE_MAPS)?,
+            hashes: cache_limits(&env, "hashes", file.cache.hashes, DEFAULT_CACHE_HASHES)?,
+            searches: cache_limits(
+                &env,
+                "searches",
+                file.cache.searches,
+                DEFAULT_CACHE_SEARCHES,
+            )?,
+        };
+
+        let database = env("MAFUYU_DATABASE")
+            .or(file.storage.database)
+            .unwrap_or(DEFAULT_DATABASE.to_owned());
+
+        let poll_interval_secs = match env("MAFUYU_SUBSCRIPTIONS_POLL_INTERVAL_SECS") {
+            Some(secs) => parse_value("MAFUYU_SUBSCRIPTIONS_POLL_INTERVAL_SECS", &secs)?,
+            None => file
+                .subscriptions
+                .poll_interval_secs
+                .unwrap_or(DEFAULT_POLL_INTERVAL_SECS),
+        };
+        if poll_interval_secs < MIN_POLL_INTERVAL_SECS {
+            bail!("subscriptions.poll_interval_secs must be at least {MIN_POLL_INTERVAL_SECS}");
+        }
+
+        let snipes_poll_interval_secs = match env("MAFUYU_SNIPES_POLL_INTERVAL_SECS") {
+            Some(secs) => parse_value("MAFUYU_SNIPES_POLL_INTERVAL_SECS", &secs)?,
+            None => file
+                .snipes
+                .poll_interval_secs
+                .unwrap_or(DEFAULT_SNIPES_POLL_INTERVAL_SECS),
+        };
+        if snipes_poll_interval_secs < MIN_SNIPES_POLL_INTERVAL_SECS {
+            bail!("snipes.poll_interval_secs must be at least {MIN_SNIPES_POLL_INTERVAL_SECS}");
+        }
+
+        Ok(Self {
+            discord: DiscordConfig { token, intents },
+            http: HttpConfig { port },
+            beatsaver: BeatSaverConfig {
+                api_url,
+                feed_url,
+                link_regex,
+                code_regex,
+                hash_regex,
+            },
+            scoresaber: ScoreSaberConfig {
+                api_url: scoresaber_api_url.trim_end_matches('/').to_owned(),
+            },
+            beatleader: BeatLeaderConfig {
+                api_url: beatleader_api_url.trim_end_matches('/').to_owned(),
+            },
+            status: StatusConfig { source_url },
+            shutdown: ShutdownConfig {
+                timeout: Duration::from_secs(shutdown_timeout_secs),
+            },
+            cache,
+            storage: StorageConfig { database },
+            subscriptions: SubscriptionsConfig {
+                poll_interval: Duration::from_secs(poll_interval_secs),
+            },
+            snipes: SnipesConfig {
+                poll_interval: Duration::from_secs(snipes_poll_interval_secs),
+            },
+        })
+    }
+}
+
+// MARK: Helpers
+
+/// Reads an environment variable, treating empty values as unset.
+fn env_var(key: &str) -> Option<String> {
+    dotenvy::var(key).ok().filter(|value| !value.trim().is_empty())
+}
+
+fn parse_value<T>(name: &str, value: &str) -> Result<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    value
+        .trim()
+        .parse()
+        .with_context(|| format!("{name} has an invalid value `{value}`"))
+}
+
+/// Parses a list of intent names like `GUILD_MESSAGES`.
+///
+/// `NON_PRIVILEGED` expands to every intent that doesn't need approval.
+fn parse_intents<I, S>(names: I) -> Result<GatewayIntents>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut intents = GatewayIntents::empty();
+
+    for name in names {
+        let name = name.as_ref().trim().to_uppercase();
+        if name.is_empty() {
+            continue;
+        }
+
+        intents |= match name.as_str() {
+            "NON_PRIVILEGED" => GatewayIntents::non_privileged(),
+            _ => GatewayIntents::from_name(&name)
+                .ok_or_else(|| anyhow!("unknown gateway intent `{name}`"))?,
+        };
+    }
+
+    if intents.is_empty() {
+        bail!("at least one gateway intent is required");
+    }
+
+    Ok(intents)
+}
+
+/// Reads the limits for one cache, e.g. `cache.maps` / `MAFUYU_CACHE_MAPS_*`.
+fn cache_limits(
+    env: impl Fn(&str) -> Option<String>,
+    kind: &str,
+    section: CacheLimitsSection,
+    default: CacheLimits,
+) -> Result<CacheLimits> {
+    let prefix = format!("MAFUYU_CACHE_{}", kind.to_uppercase());
+
+    let capacity_key = format!("{prefix}_CAPACITY");
+    let capacity = match env(&capacity_key) {
+        Some(capacity) => parse_value(&capacity_key, &capacity)?,
+        None => section.capacity.unwrap_or(default.capacity),
+    };
+
+    let ttl_key = format!("{prefix}_TTL_SECS");
+    let ttl_secs = match env(&ttl_key) {
+        Some(secs) => parse_value(&ttl_key, &secs)?,
+        None => section.ttl_secs.unwrap_or(default.ttl.as_secs()),
+    };
+    if ttl_secs == 0 && capacity > 0 {
+        bail!("cache.{kind}.ttl_secs must be positive (set capacity to 0 to disable the cache)");
+    }
+
+    Ok(CacheLimits::new(capacity, ttl_secs))
+}
+
+fn compile_regex(name: &str, pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).with_context(|| format!("{name} is not a valid regex"))
+}
+
+fn check_url(name: &str, url: &str) -> Result<()> {
+    if !(url.starts_with("https://") || url.starts_with("http://")) {
+        bail!("{name} must be an http(s) URL, got `{url}`");
+    }
+
+    Ok(())
+}
+
+fn check_ws_url(name: &str, url: &str) -> Result<()> {
+    if !(url.starts_with("wss://") || url.starts_with("ws://")) {
+        bail!("{name} must be a ws(s) URL, got `{url}`");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// Loads a config from TOML and a few environment variables, with a token
+    /// from the environment unless one is given.
+    fn load(toml: &str, vars: &[(&str, &str)]) -> Result<Config> {
+        let mut vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        vars.entry("DISCORD_TOKEN".to_owned())
+            .or_insert("token".to_owned());
+
+        Config::from_file(toml::from_str(toml)?, |key| vars.get(key).cloned())
+    }
+
+    fn error(toml: &str, vars: &[(&str, &str)]) -> String {
+        format!(
+            "{:#}",
+            load(toml, vars).err().expect("config should be invalid")
+        )
+    }
+
+    #[test]
+    fn defaults() {
+        let config = load("", &[]).unwrap();
+
+        assert_eq!(config.discord.token, "token");
+        assert_eq!(config.http.port, DEFAULT_HTTP_PORT);
+        assert_eq!(config.beatsaver.api_url, DEFAULT_BEATSAVER_API_URL);
+        assert_eq!(config.storage.database, DEFAULT_DATABASE);
+        assert_eq!(config.cache.maps.capacity, DEFAULT_CACHE_MAPS.capacity);
+        assert_eq!(
+            config.subscriptions.poll_interval,
+            Duration::from_secs(DEFAULT_POLL_INTERVAL_SECS)
+        );
+    }
+
+    #[test]
+    fn file_beats_defaults() {
+        let config = load(
+            r#"
+            [http]
+            port = 6000
+
+            [storage]
+            database = "file.db"
+
+            [cache.maps]
+            capacity = 10
+            "#,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(config.http.port, 6000);
+        assert_eq!(config.storage.database, "file.db");
+        assert_eq!(config.cache.maps.capacity, 10);
+        assert_eq!(config.cache.maps.ttl, DEFAULT_CACHE_MAPS.ttl);
+    }
+
+    #[test]
+    fn env_beats_file() {
+        let config = load(
+            r#"
+            [discord]
+            token = "from file"
+
+            [http]
+            port = 6000
+
+            [cache.maps]
+            capacity = 10
+            "#,
+            &[
+                ("DISCORD_TOKEN", "from env"),
+                ("MAFUYU_HTTP_PORT", "7000"),
+                ("MAFUYU_CACHE_MAPS_CAPACITY", "20"),
+                ("MAFUYU_BEATSAVER_API_URL", "http://localhost:1234/"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(config.discord.token, "from env");
+        assert_eq!(config.http.port, 7000);
+        assert_eq!(config.cache.maps.capacity, 20);
+        assert_eq!(config.beatsaver.api_url, "http://localhost:1234");
+    }
+
+    #[test]
+    fn token_from_file() {
+        let config = Config::from_file(
+            toml::from_str("[discord]\ntoken = \"from file\"").unwrap(),
+            |_| None,
+        )
+        .unwrap();
+
+        assert_eq!(config.discord.token, "from file");
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!(Config::from_file(ConfigFile::default(), |_| None).is_err());
+        assert!(error("[http]\nport = 0", &[]).contains("HTTP port"));
+        assert!(error("", &[("MAFUYU_HTTP_PORT", "lots")]).contains("MAFUYU_HTTP_PORT"));
+        assert!(error("", &[("MAFUYU_DISCORD_INTENTS", "NOPE")]).contains("NOPE"));
+        assert!(error("[subscriptions]\npoll_interval_secs = 5", &[])
+            .contains("subscriptions.poll_interval_secs"));
+        assert!(error("", &[("MAFUYU_SNIPES_POLL_INTERVAL_SECS", "5")])
+            .contains("snipes.poll_interval_secs"));
+        assert!(error("[beatsaver]\nlink_pattern = \"maps/(.+)\"", &[]).contains("`bsr`"));
+        assert!(error("[beatsaver]\ncode_pattern = \"(\"", &[]).contains("code_pattern"));
+        assert!(error("[beatsaver]\napi_url = \"ftp://x\"", &[]).contains("beatsaver.api_url"));
+        assert!(
+            error("", &[("MAFUYU_BEATSAVER_FEED_URL", "https://x")]).contains("beatsaver.feed_url")
+        );
+        assert!(error("[cache.maps]\nttl_secs = 0", &[]).contains("cache.maps.ttl_secs"));
+    }
+
+    #[test]
+    fn disabled_cache_needs_no_ttl() {
+        let config = load("[cache.maps]\ncapacity = 0\nttl_secs = 0", &[]).unwrap();
+
+        assert_eq!(config.cache.maps.capacity, 0);
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        assert!(toml::from_str::<ConfigFile>("[http]\nprot = 6000").is_err());
+    }
+}
@@ -0,0 +1,319 @@
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+use log::{error, warn};
+use poise::serenity_prelude::{
+    CacheHttp, Colour, CreateAllowedMentions, CreateEmbed, CreateEmbedFooter, CreateMessage,
+    GuildId,
+};
+use reqwest::StatusCode;
+
+use crate::{
+    clients::beatsaver::query::{QueryError, FILTER_HELP},
+    clients::http::ApiError,
+    core::{settings::GuildSettings, storage::Storage},
+    utils::text::{neutralise_mentions, sanitise, EmbedBudget},
+};
+
+/// What kind of failure a command ran into, as far as the user is concerned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The thing they asked for doesn't exist on a service.
+    NotFound { service: &'static str },
+    /// It existed, but got deleted.
+    Deleted,
+    /// We're being rate limited upstream.
+    RateLimited {
+        service: &'static str,
+        retry_after: Option<Duration>,
+    },
+    /// The upstream service is down, slow or erroring.
+    UpstreamDown { service: &'static str },
+    /// They typed something we can't make sense of.
+    InvalidQuery,
+    /// Our fault.
+    Internal,
+}
+
+impl ErrorKind {
+    /// Works out the kind by looking through the whole error chain.
+    pub fn classify(error: &anyhow::Error) -> Self {
+        for cause in error.chain() {
+            if cause.is::<QueryError>() {
+                return Self::InvalidQuery;
+            }
+
+            if let Some(err) = cause.downcast_ref::<ApiError>() {
+                return match *err {
+                    ApiError::NotFound { service, .. } => Self::NotFound { service },
+                    ApiError::Deleted { .. } => Self::Deleted,
+                    ApiError::RateLimited {
+                        service,
+                        retry_after,
+                    } => Self::RateLimited {
+                        service,
+                        retry_after,
+                    },
+                    ApiError::Status { service, status } => Self::from_status(service, status),
+                    ApiError::Unreachable { service, .. } => Self::UpstreamDown { service },
+                };
+            }
+        }
+
+        Self::Internal
+    }
+
+    fn from_status(service: &'static str, status: StatusCode) -> Self {
+        match status {
+            StatusCode::NOT_FOUND => Self::NotFound { service },
+            StatusCode::GONE => Self::Deleted,
+            StatusCode::TOO_MANY_REQUESTS => Self::RateLimited {
+                service,
+                retry_after: None,
+            },
+            status if status.is_server_error() => Self::UpstreamDown { service },
+            _ => Self::Internal,
+        }
+    }
+
+    /// Whether it's something missing, whichever service it's missing from.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Self::NotFound { .. })
+    }
+
+    fn title(&self) -> String {
+        match self {
+            Self::NotFound { .. } => "Couldn't find that".to_owned(),
+            Self::Deleted => "That's been deleted".to_owned(),
+            Self::RateLimited { .. } => "Slow down a little".to_owned(),
+            Self::UpstreamDown { service } => format!("{service} isn't responding"),
+            Self::InvalidQuery => "That search doesn't work".to_owned(),
+            Self::Internal => "Something went wrong".to_owned(),
+        }
+    }
+
+    fn description(&self) -> String {
+        match self {
+            Self::NotFound {
+                service: "BeatSaver",
+            } => "Double-check the map code or link, or try searching instead.".to_owned(),
+            Self::NotFound { service } => {
+                format!("Double-check the player's name, ID or {service} profile link.")
+            }
+            Self::Deleted => "The uploader has taken it down, so there's nothing to show.".to_owned(),
+            Self::RateLimited {
+                service,
+                retry_after: Some(after),
+            } => format!(
+                "{service} is getting too many requests from us. Try again in {} seconds.",
+                after.as_secs().max(1)
+            ),
+            Self::RateLimited {
+                service,
+                retry_after: None,
+            } => format!("{service} is getting too many requests from us. Try again in a minute."),
+            Self::UpstreamDown { .. } => {
+                "It might be down or having a moment. Try again in a few minutes.".to_owned()
+            }
+            Self::InvalidQuery => format!("Filters you can use: {FILTER_HELP}"),
+            Self::Internal => {
+                "This one's on us. If it keeps happening, report it with the error ID below."
+                    .to_owned()
+            }
+        }
+    }
+
+    fn colour(&self) -> Colour {
+        match self {
+            Self::NotFound { .. } | Self::Deleted | Self::InvalidQuery => {
+                Colour::from_rgb(68, 68, 68)
+            }
+            Self::RateLimited { .. } | Self::UpstreamDown { .. } => Colour::from_rgb(243, 156, 18),
+            Self::Internal => Colour::from_rgb(231, 76, 60),
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::NotFound { .. } => "not found",
+            Self::Deleted => "deleted",
+            Self::RateLimited { .. } => "rate limited",
+            Self::UpstreamDown { .. } => "upstream down",
+            Self::InvalidQuery => "invalid query",
+            Self::Internal => "internal",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A classified error with a short ID that shows up both in the reply and in
+/// the logs, so reports can be matched up.
+pub struct ErrorReport {
+    pub id: String,
+    pub kind: ErrorKind,
+    /// The top-level message, shown for errors the user can do something about.
+    pub message: String,
+}
+
+impl ErrorReport {
+    pub fn new(error: &anyhow::Error) -> Self {
+        Self {
+            id: error_id(),
+            kind: ErrorKind::classify(error),
+            message: error.to_string(),
+        }
+    }
+
+    /// Logs the full error chain under this report's ID.
+    pub fn log(&self, error: &anyhow::Error) {
+        match self.kind {
+            ErrorKind::Internal => error!("[{}] {} error: {error:?}", self.id, self.kind),
+            _ => warn!("[{}] {} error: {error:#}", self.id, self.kind),
+        }
+    }
+
+    pub fn embed(&self) -> CreateEmbed {
+        let description = match self.kind {
+            ErrorKind::NotFound { .. } | ErrorKind::Deleted => {
+                format!("{}\n{}", sanitise(&self.message), self.kind.description())
+            }
+            // query errors quote filters in code blocks on purpose
+            ErrorKind::InvalidQuery => format!(
+                "{}\n{}",
+                neutralise_mentions(&self.message),
+                self.kind.description()
+            ),
+            _ => self.kind.description(),
+        };
+
+        let mut budget = EmbedBudget::new();
+        CreateEmbed::new()
+            .title(self.kind.title())
+            .description(budget.description(&description))
+            .colour(self.kind.colour())
+            .footer(CreateEmbedFooter::new(format!("Error ID: {}", self.id)))
+    }
+
+    /// Posts this to a server's log channel, unless it's just a typo or a
+    /// missing map.
+    pub async fn notify_log_channel(
+        &self,
+        http: impl CacheHttp,
+        storage: &Storage,
+        guild: Option<GuildId>,
+        origin: &str,
+    ) {
+        if matches!(
+            self.kind,
+            ErrorKind::NotFound { .. } | ErrorKind::Deleted | ErrorKind::InvalidQuery
+        ) {
+            return;
+        }
+
+        let channel = match GuildSettings::load(storage, guild).await {
+            Ok(settings) => settings.log_channel,
+            Err(err) => {
+                warn!("[{}] Couldn't load settings to report it: {err:#}", self.id);
+                return;
+            }
+        };
+        let Some(channel) = channel else {
+            return;
+        };
+
+        let embed = self
+            .embed()
+            .field("Where", sanitise(origin), true)
+            .field("Kind", self.kind.to_string(), true);
+        let builder = CreateMessage::new()
+            .embed(embed)
+            .allowed_mentions(CreateAllowedMentions::new());
+
+        if let Err(err) = channel.send_message(http, builder).await {
+            warn!("[{}] Couldn't post to log channel {channel}: {err}", self.id);
+        }
+    }
+}
+
+/// Makes a short, random-enough ID like `3fa9c1`.
+fn error_id() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+
+    format!("{:06x}", hasher.finish() & 0xff_ffff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn not_found_hint_fits_the_service() {
+        let not_found = |service| {
+            ErrorKind::classify(
+                &ApiError::NotFound {
+                    service,
+                    what: "It".to_owned(),
+                }
+                .into(),
+            )
+        };
+
+        let map = not_found("BeatSaver");
+        assert_eq!(
+            map,
+            ErrorKind::NotFound {
+                service: "BeatSaver"
+            }
+        );
+        assert!(map.description().contains("map code"));
+
+        let player = not_found("ScoreSaber");
+        assert!(player.is_not_found());
+        assert!(!player.description().contains("map code"));
+        assert!(player.description().contains("ScoreSaber"));
+
+        let player = not_found("BeatLeader");
+        assert!(player.description().contains("BeatLeader"));
+    }
+
+    #[test]
+    fn finds_api_errors_under_context() {
+        let error = anyhow::Error::from(ApiError::RateLimited {
+            service: "ScoreSaber",
+            retry_after: None,
+        })
+        .context("couldn't look up scores");
+
+        assert_eq!(
+            ErrorKind::classify(&error),
+            ErrorKind::RateLimited {
+                service: "ScoreSaber",
+                retry_after: None
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn other_http_failures_are_ours() {
+        // e.g. a Discord send failing, which isn't any service we look things up on
+        let error = reqwest::Client::new()
+            .get("not a url")
+            .send()
+            .await
+            .unwrap_err();
+
+        assert_eq!(ErrorKind::classify(&error.into()), ErrorKind::Internal);
+    }
+}
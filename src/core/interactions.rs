@@ -0,0 +1,162 @@
+use anyhow::anyhow;
+use log::{debug, warn};
+use poise::serenity_prelude::{
+    self as serenity, ComponentInteraction, CreateInteractionResponse,
+    CreateInteractionResponseFollowup, CreateInteractionResponseMessage, GuildId, User,
+};
+
+use crate::{
+    core::error::ErrorReport,
+    ui::{leaderboard, mapembed, maplist, playlistview, searchresults, serverleaderboard},
+    Data, Error,
+};
+
+/// Framework-wide event handler.
+pub async fn event_handler(
+    ctx: &serenity::Context,
+    event: &serenity::FullEvent,
+    _framework: poise::FrameworkContext<'_, Data, Error>,
+    data: &Data,
+) -> Result<(), Error> {
+    if let serenity::FullEvent::InteractionCreate {
+        interaction: serenity::Interaction::Component(mci),
+    } = event
+    {
+        route_component(ctx, data, mci).await;
+    }
+
+    Ok(())
+}
+
+/// Sends a component interaction to whatever owns its custom ID.
+///
+/// Custom IDs look like `<prefix>:<args...>`, so handlers get everything they
+/// need from the ID itself and keep working across restarts.
+async fn route_component(ctx: &serenity::Context, data: &Data, mci: &ComponentInteraction) {
+    let guard = data.shutdown.command_guard();
+    note_seen(data, mci.guild_id, &mci.user);
+    let res = dispatch_component(ctx, data, mci).await;
+    drop(guard);
+
+    if let Err(error) = res {
+        let report = ErrorReport::new(&error);
+        report.log(&error);
+
+        let builder = CreateInteractionResponseMessage::new()
+            .embed(report.embed())
+            .ephemeral(true);
+        let res = mci
+            .create_response(ctx, CreateInteractionResponse::Message(builder))
+            .await;
+        if res.is_err() {
+            // the handler already answered, e.g. by deferring
+            let builder = CreateInteractionResponseFollowup::new()
+                .embed(report.embed())
+                .ephemeral(true);
+            let _ = mci.create_followup(ctx, builder).await;
+        }
+
+        let origin = format!("Component {}", mci.data.custom_id);
+        report
+            .notify_log_channel(ctx, &data.storage, mci.guild_id, &origin)
+            .await;
+    }
+}
+
+/// Notes that someone used the bot in a guild, for server leaderboards,
+/// without holding up whatever they're doing.
+pub fn note_seen(data: &Data, guild: Option<GuildId>, user: &User) {
+    let Some(guild) = guild else {
+        return;
+    };
+    let storage = data.storage.clone();
+    let (user, name) = (user.id, user.name.clone());
+
+    tokio::spawn(async move {
+        if let Err(err) = storage.linked_accounts().seen_in(guild, user).await {
+            warn!("Couldn't note {name} in guild {guild}: {err:#}");
+        }
+    });
+}
+
+async fn dispatch_component(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &ComponentInteraction,
+) -> Result<(), Error> {
+    let mut args = mci.data.custom_id.split(':');
+
+    match args.next() {
+        Some(mapembed::COMPONENT_PREFIX) => {
+            let map_id = args
+                .next()
+                .ok_or_else(|| anyhow!("malformed custom ID {}", mci.data.custom_id))?;
+            let menu = args.next();
+            mapembed::handle_interaction(ctx, data, mci, map_id, menu, args.next()).await
+        }
+        Some(maplist::COMPONENT_PREFIX) => maplist::handle_interaction(ctx, data, mci).await,
+        Some(leaderboard::COMPONENT_PREFIX) => {
+            match (args.next(), args.next(), args.next(), args.next()) {
+                (Some(source), Some(hash), Some(characteristic), Some(difficulty)) => {
+                    leaderboard::handle_interaction(
+                        ctx,
+                        data,
+                        mci,
+                        source,
+                        hash,
+                        characteristic,
+                        difficulty,
+                        args.next(),
+                    )
+                    .await
+                }
+                _ => Err(anyhow!("malformed custom ID {}", mci.data.custom_id)),
+            }
+        }
+        Some(serverleaderboard::COMPONENT_PREFIX) => {
+            match (args.next(), args.next(), args.next(), args.next()) {
+                (Some(source), Some(hash), Some(characteristic), Some(difficulty)) => {
+                    serverleaderboard::handle_interaction(
+                        ctx,
+                        data,
+                        mci,
+                        source,
+                        hash,
+                        characteristic,
+                        difficulty,
+                    )
+                    .await
+                }
+                _ => Err(anyhow!("malformed custom ID {}", mci.data.custom_id)),
+            }
+        }
+        Some(playlistview::COMPONENT_PREFIX) => match (args.next(), args.next()) {
+            (Some("page"), Some(page)) => {
+                playlistview::handle_interaction(ctx, data, mci, page).await
+            }
+            _ => Err(anyhow!("malformed custom ID {}", mci.data.custom_id)),
+        },
+        Some(searchresults::COMPONENT_PREFIX) => match (args.next(), args.next()) {
+            (Some("page"), Some(page)) => {
+                searchresults::handle_interaction(ctx, data, mci, page).await
+            }
+            _ => Err(anyhow!("malformed custom ID {}", mci.data.custom_id)),
+        },
+        // sent before the map ID was in the custom ID, but it's in the footer too
+        Some("diffsel") => {
+            let map_id = mci
+                .message
+                .embeds
+                .first()
+                .and_then(|embed| embed.footer.as_ref())
+                .and_then(|footer| footer.text.strip_prefix("!bsr "))
+                .ok_or_else(|| anyhow!("couldn't find the map ID on an old map embed"))?;
+            mapembed::handle_interaction(ctx, data, mci, map_id, None, None).await
+        }
+        // probably a collector's, leave it alone
+        _ => {
+            debug!("Not routing component {}", mci.data.custom_id);
+            Ok(())
+        }
+    }
+}
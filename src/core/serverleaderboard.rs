@@ -0,0 +1,203 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::{StreamExt, TryStreamExt};
+use log::debug;
+use poise::serenity_prelude::{self as serenity, GuildId, UserId};
+
+use crate::{
+    clients::{beatleader, scoresaber},
+    core::{error::ErrorKind, storage::linked_accounts::LinkedAccount},
+    ui::leaderboard::Source,
+    utils::{cache::TtlCache, discord::is_member},
+    Data, Error,
+};
+
+const CACHE: (usize, Duration) = (200, Duration::from_secs(2 * 60));
+/// Most people looked up for one leaderboard, most recently active first.
+const MAX_MEMBERS: u32 = 50;
+/// Lookups running at once, to go easy on the score sites.
+const CONCURRENCY: usize = 5;
+
+/// A server member's best score on a difficulty.
+#[derive(Clone, Debug)]
+pub struct MemberScore {
+    pub user_id: UserId,
+    pub player_id: String,
+    pub player_name: String,
+    pub score: u64,
+    /// From 0 to 1, if it can be worked out.
+    pub accuracy: Option<f64>,
+    pub pp: f64,
+    pub misses: u32,
+    pub full_combo: bool,
+    /// Unix seconds.
+    pub set_at: i64,
+}
+
+impl MemberScore {
+    /// `max_score` is the difficulty's, for working out accuracy.
+    pub fn from_scoresaber(
+        account: &LinkedAccount,
+        score: &scoresaber::Score,
+        max_score: u64,
+    ) -> Self {
+        Self {
+            user_id: account.user_id,
+            player_id: account.player_id.clone(),
+            player_name: account.player_name.clone(),
+            score: score.modified_score,
+            accuracy: score.accuracy(max_score),
+            pp: score.pp,
+            misses: score.missed_notes + score.bad_cuts,
+            full_combo: score.full_combo,
+            set_at: score.time_set.timestamp(),
+        }
+    }
+
+    pub fn from_beatleader(account: &LinkedAccount, score: &beatleader::Score) -> Self {
+        Self {
+            user_id: account.user_id,
+            player_id: account.player_id.clone(),
+            player_name: account.player_name.clone(),
+            score: score.modified_score,
+            accuracy: Some(score.accuracy),
+            pp: score.pp,
+            misses: score.missed_notes + score.bad_cuts,
+            full_combo: score.full_combo,
+            set_at: score.timepost,
+        }
+    }
+}
+
+/// Scores from a server's linked members, cached for a couple of minutes.
+pub struct ServerLeaderboards {
+    cache: TtlCache<String, Arc<Vec<MemberScore>>>,
+}
+
+impl Default for ServerLeaderboards {
+    fn default() -> Self {
+        Self {
+            cache: TtlCache::new(CACHE.0, CACHE.1),
+        }
+    }
+}
+
+impl ServerLeaderboards {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The best score of every linked member on a difficulty, best first.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn get(
+        &self,
+        ctx: &serenity::Context,
+        data: &Data,
+        guild: GuildId,
+        source: Source,
+        hash: &str,
+        characteristic: &str,
+        difficulty: &str,
+    ) -> Result<Arc<Vec<MemberScore>>, Error> {
+        let hash = hash.to_lowercase();
+        let key = format!(
+            "{guild}:{}:{hash}:{characteristic}:{difficulty}",
+            source.id()
+        );
+        if let Some(scores) = self.cache.get(&key) {
+            return Ok(scores);
+        }
+
+        debug!("Server leaderboard cache miss for {key}");
+        let accounts = data
+            .storage
+            .linked_accounts()
+            .in_guild(guild, source.into(), MAX_MEMBERS)
+            .await?;
+
+        // ScoreSaber scores only have points, so accuracy needs the max score
+        let max_score = match source {
+            Source::ScoreSaber if !accounts.is_empty() => {
+                match data
+                    .scoresaber
+                    .leaderboard(&hash, characteristic, difficulty)
+                    .await
+                {
+                    Ok(leaderboard) => leaderboard.info.max_score,
+                    Err(err) if ErrorKind::classify(&err).is_not_found() => {
+                        // nobody's played it on ScoreSaber
+                        self.cache.insert(key, Arc::default());
+                        return Ok(Arc::default());
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+            _ => 0,
+        };
+
+        let mut scores: Vec<MemberScore> = futures::stream::iter(accounts)
+            .map(|account| {
+                member_score(
+                    ctx,
+                    data,
+                    guild,
+                    account,
+                    source,
+                    &hash,
+                    characteristic,
+                    difficulty,
+                    max_score,
+                )
+            })
+            .buffer_unordered(CONCURRENCY)
+            .try_filter_map(|score| async move { Ok(score) })
+            .try_collect()
+            .await?;
+        scores.sort_by(|a, b| b.score.cmp(&a.score).then(a.set_at.cmp(&b.set_at)));
+
+        let scores = Arc::new(scores);
+        self.cache.insert(key, scores.clone());
+
+        Ok(scores)
+    }
+}
+
+/// One account's score, or `None` if they have none or have left the guild.
+#[allow(clippy::too_many_arguments)]
+async fn member_score(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild: GuildId,
+    account: LinkedAccount,
+    source: Source,
+    hash: &str,
+    characteristic: &str,
+    difficulty: &str,
+    max_score: u64,
+) -> Result<Option<MemberScore>, Error> {
+    if !is_member(ctx, guild, account.user_id).await? {
+        return Ok(None);
+    }
+
+    let res = match source {
+        Source::ScoreSaber => data
+            .scoresaber
+            .player_score(&account.player_id, hash, characteristic, difficulty)
+            .await
+            .map(|score| {
+                score.map(|score| MemberScore::from_scoresaber(&account, &score, max_score))
+            }),
+        Source::BeatLeader => data
+            .beatleader
+            .player_score(&account.player_id, hash, characteristic, difficulty)
+            .await
+            .map(|score| score.map(|score| MemberScore::from_beatleader(&account, &score))),
+    };
+
+    match res {
+        Ok(score) => Ok(score),
+        // their account's gone, which shouldn't sink everyone else's scores
+        Err(err) if ErrorKind::classify(&err).is_not_found() => Ok(None),
+        Err(err) => Err(err),
+    }
+}
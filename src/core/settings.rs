@@ -0,0 +1,174 @@
+use std::fmt;
+
+use log::warn;
+use poise::serenity_prelude::{ChannelId, GuildId};
+
+use crate::{
+    core::storage::{linked_accounts::Service, Storage},
+    Context, Error,
+};
+
+/// Who can see the bot's replies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, poise::ChoiceParameter)]
+pub enum Visibility {
+    #[default]
+    #[name = "Public"]
+    Public,
+    #[name = "Private (only whoever used the command)"]
+    Ephemeral,
+}
+
+/// Which leaderboard to show scores from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, poise::ChoiceParameter)]
+pub enum Leaderboard {
+    #[default]
+    ScoreSaber,
+    BeatLeader,
+}
+
+impl From<Leaderboard> for Service {
+    fn from(leaderboard: Leaderboard) -> Self {
+        match leaderboard {
+            Leaderboard::ScoreSaber => Self::ScoreSaber,
+            Leaderboard::BeatLeader => Self::BeatLeader,
+        }
+    }
+}
+
+/// How much a map embed shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, poise::ChoiceParameter)]
+pub enum Layout {
+    #[default]
+    Full,
+    Compact,
+}
+
+/// Every setting a server can change, for resetting them by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum SettingName {
+    #[name = "Reply visibility"]
+    Visibility,
+    #[name = "Restrict selections"]
+    RestrictSelections,
+    Leaderboard,
+    Layout,
+    #[name = "Log channel"]
+    LogChannel,
+    #[name = "Snipe channel"]
+    SnipeChannel,
+    #[name = "Snipe limit"]
+    SnipeLimit,
+}
+
+impl SettingName {
+    /// The key it's stored under.
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::Visibility => "visibility",
+            Self::RestrictSelections => "restrict_selections",
+            Self::Leaderboard => "leaderboard",
+            Self::Layout => "layout",
+            Self::LogChannel => "log_channel",
+            Self::SnipeChannel => "snipe_channel",
+            Self::SnipeLimit => "snipe_limit",
+        }
+    }
+}
+
+/// Most snipes posted in a server per hour, unless it's changed.
+pub const DEFAULT_SNIPE_LIMIT: u32 = 5;
+
+/// A server's settings, with defaults for anything it hasn't changed.
+///
+/// These are read from storage whenever they're needed, so changes apply
+/// straight away.
+#[derive(Debug, Clone)]
+pub struct GuildSettings {
+    pub visibility: Visibility,
+    /// Whether only the person who used `/bsr` can use its menus.
+    pub restrict_selections: bool,
+    pub leaderboard: Leaderboard,
+    pub layout: Layout,
+    /// Where to post setting changes and errors.
+    pub log_channel: Option<ChannelId>,
+    /// Where to post linked members sniping each other, if anywhere.
+    pub snipe_channel: Option<ChannelId>,
+    /// Most snipes posted per hour.
+    pub snipe_limit: u32,
+}
+
+impl Default for GuildSettings {
+    fn default() -> Self {
+        Self {
+            visibility: Visibility::default(),
+            restrict_selections: false,
+            leaderboard: Leaderboard::default(),
+            layout: Layout::default(),
+            log_channel: None,
+            snipe_channel: None,
+            snipe_limit: DEFAULT_SNIPE_LIMIT,
+        }
+    }
+}
+
+impl GuildSettings {
+    /// Loads a server's settings, or the defaults outside of servers.
+    pub async fn load(storage: &Storage, guild: Option<GuildId>) -> Result<Self, Error> {
+        let mut settings = Self::default();
+        let Some(guild) = guild else {
+            return Ok(settings);
+        };
+
+        for (key, value) in storage.guild_settings().all(guild).await? {
+            // a bad value shouldn't lock a server out of everything else
+            let parsed = match key.as_str() {
+                "visibility" => parse_choice(&value).map(|v| settings.visibility = v),
+                "restrict_selections" => value
+                    .parse()
+                    .ok()
+                    .map(|v| settings.restrict_selections = v),
+                "leaderboard" => parse_choice(&value).map(|v| settings.leaderboard = v),
+                "layout" => parse_choice(&value).map(|v| settings.layout = v),
+                "log_channel" => value
+                    .parse()
+                    .ok()
+                    .map(|id| settings.log_channel = Some(ChannelId::new(id))),
+                "snipe_channel" => value
+                    .parse()
+                    .ok()
+                    .map(|id| settings.snipe_channel = Some(ChannelId::new(id))),
+                "snipe_limit" => value.parse().ok().map(|v| settings.snipe_limit = v),
+                _ => Some(()),
+            };
+            if parsed.is_none() {
+                warn!("Ignoring invalid setting {key} = {value:?} in guild {guild}");
+            }
+        }
+
+        Ok(settings)
+    }
+
+    /// Loads the settings for wherever a command was used.
+    pub async fn for_context(ctx: Context<'_>) -> Result<Self, Error> {
+        Self::load(&ctx.data().storage, ctx.guild_id()).await
+    }
+
+    pub fn ephemeral(&self) -> bool {
+        self.visibility == Visibility::Ephemeral
+    }
+
+    pub fn compact(&self) -> bool {
+        self.layout == Layout::Compact
+    }
+}
+
+/// Stores a choice by its name, e.g. `BeatLeader`.
+pub fn choice_value<T: fmt::Debug>(choice: T) -> String {
+    format!("{choice:?}")
+}
+
+fn parse_choice<T: poise::ChoiceParameter + fmt::Debug>(value: &str) -> Option<T> {
+    (0..T::list().len())
+        .filter_map(T::from_index)
+        .find(|choice| choice_value(choice) == value)
+}
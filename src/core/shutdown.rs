@@ -0,0 +1,174 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use log::{info, warn};
+use tokio::{signal, sync::Notify};
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
+
+/// Shared shutdown state.
+///
+/// Long-running work (collectors, background tasks) should stop once
+/// [`Shutdown::cancelled`] resolves, and command invocations are counted so
+/// shutdown can wait for them to finish.
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    token: CancellationToken,
+    in_flight: Arc<AtomicUsize>,
+    idle: Arc<Notify>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts shutting down. Safe to call more than once.
+    pub fn trigger(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Resolves once shutdown has started.
+    pub fn cancelled(&self) -> WaitForCancellationFutureOwned {
+        self.token.clone().cancelled_owned()
+    }
+
+    /// Marks a command invocation as started, until the guard is dropped.
+    ///
+    /// Holding a guard instead of pairing up calls means every way out of a
+    /// command (errors, panics, bad arguments) counts as finishing it.
+    pub fn command_guard(&self) -> CommandGuard {
+        self.command_started();
+        CommandGuard {
+            shutdown: self.clone(),
+        }
+    }
+
+    /// Marks a command invocation as started.
+    pub fn command_started(&self) {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Marks a command invocation as finished, successfully or not.
+    pub fn command_finished(&self) {
+        let previous = self
+            .in_flight
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .unwrap_or(0);
+
+        if previous <= 1 {
+            self.idle.notify_waiters();
+        }
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Waits until no commands are running, or the timeout runs out.
+    ///
+    /// Returns `false` if commands were still running at the deadline.
+    pub async fn wait_idle(&self, timeout: Duration) -> bool {
+        let drained = tokio::time::timeout(timeout, async {
+            loop {
+                let notified = self.idle.notified();
+                if self.in_flight() == 0 {
+                    break;
+                }
+                notified.await;
+            }
+        })
+        .await;
+
+        if drained.is_err() {
+            warn!(
+                "{} command(s) still running after {:?}, shutting down anyway",
+                self.in_flight(),
+                timeout
+            );
+        }
+
+        drained.is_ok()
+    }
+}
+
+/// Keeps a command counted as in flight while it's alive.
+pub struct CommandGuard {
+    shutdown: Shutdown,
+}
+
+impl Drop for CommandGuard {
+    fn drop(&mut self) {
+        self.shutdown.command_finished();
+    }
+}
+
+/// Waits for SIGINT or SIGTERM.
+pub async fn wait_for_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("Unable to listen for SIGINT");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("Unable to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("Received SIGINT"),
+        _ = terminate => info!("Received SIGTERM"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn guards_count_until_dropped() {
+        let shutdown = Shutdown::new();
+
+        let first = shutdown.command_guard();
+        let second = shutdown.command_guard();
+        assert_eq!(shutdown.in_flight(), 2);
+
+        drop(first);
+        assert_eq!(shutdown.in_flight(), 1);
+        assert!(!shutdown.wait_idle(Duration::from_millis(10)).await);
+
+        drop(second);
+        assert!(shutdown.wait_idle(Duration::from_millis(10)).await);
+    }
+
+    #[tokio::test]
+    async fn guard_is_released_on_panic() {
+        let shutdown = Shutdown::new();
+
+        let task = {
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move {
+                let _guard = shutdown.command_guard();
+                panic!("command blew up");
+            })
+        };
+        assert!(task.await.is_err());
+
+        assert_eq!(shutdown.in_flight(), 0);
+    }
+}
@@ -0,0 +1,525 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use chrono::Utc;
+use futures::{StreamExt, TryStreamExt};
+use log::{debug, info, warn};
+use poise::serenity_prelude::{ChannelId, CreateAllowedMentions, CreateMessage, GuildId, Http};
+use tokio::time::MissedTickBehavior;
+
+use crate::{
+    clients::{
+        beatleader::BeatLeader, beatsaver::BeatSaver, http::ApiError, scoresaber::ScoreSaber,
+    },
+    core::{
+        error::ErrorKind,
+        serverleaderboard::MemberScore,
+        settings::{GuildSettings, SettingName},
+        shutdown::Shutdown,
+        storage::{
+            linked_accounts::{LinkedAccount, Service},
+            Storage,
+        },
+        subscriptions::is_unknown_channel,
+    },
+    ui::{mapembed::MapEmbed, snipe},
+    utils::discord::is_member,
+    Error,
+};
+
+/// Most members watched in one guild, most recently active first.
+const MAX_MEMBERS: u32 = 25;
+/// Scores looked at per player each check. Anyone playing more than this
+/// between checks only gets their latest ones looked at.
+const RECENT_SCORES: u32 = 10;
+/// Lookups running at once, to go easy on the score sites.
+const CONCURRENCY: usize = 5;
+/// The window a guild's snipe limit covers.
+const LIMIT_WINDOW_SECS: i64 = 60 * 60;
+
+/// A score someone set since their last check.
+#[derive(Clone)]
+struct NewScore {
+    hash: String,
+    characteristic: String,
+    difficulty: String,
+    /// What the score site calls the song, for maps BeatSaver doesn't have.
+    song_name: String,
+    /// ScoreSaber's max score for the difficulty, for working out accuracy.
+    max_score: u64,
+    score: MemberScore,
+    /// Their best on the leaderboard before this, if it's been seen.
+    previous_best: Option<u64>,
+}
+
+impl NewScore {
+    /// How snipes on its leaderboard are stored.
+    fn leaderboard(&self) -> String {
+        format!(
+            "{}:{}:{}",
+            self.hash.to_lowercase(),
+            self.characteristic,
+            self.difficulty
+        )
+    }
+}
+
+/// Posts linked members beating each other's scores, in guilds that picked a
+/// snipe channel.
+///
+/// Every check looks at what each watched player set since the last one, so
+/// nothing from before a guild turned snipes on gets posted.
+pub struct SnipeWatcher {
+    http: Arc<Http>,
+    /// Its own score clients, so checks never get served stale scores from
+    /// the commands' caches.
+    scoresaber: ScoreSaber,
+    beatleader: BeatLeader,
+    /// Shared with the commands, since maps don't change with every score.
+    beatsaver: Arc<BeatSaver>,
+    storage: Storage,
+    shutdown: Shutdown,
+    interval: Duration,
+}
+
+impl SnipeWatcher {
+    pub fn new(
+        http: Arc<Http>,
+        scoresaber: ScoreSaber,
+        beatleader: BeatLeader,
+        beatsaver: Arc<BeatSaver>,
+        storage: Storage,
+        shutdown: Shutdown,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            http,
+            scoresaber,
+            beatleader,
+            beatsaver,
+            storage,
+            shutdown,
+            interval,
+        }
+    }
+
+    /// Checks for snipes until shutdown.
+    pub async fn run(self) {
+        info!("Checking for snipes every {:?}", self.interval);
+
+        let mut ticker = tokio::time::interval(self.interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = self.shutdown.cancelled() => break,
+                _ = ticker.tick() => {
+                    if let Err(err) = self.poll().await {
+                        warn!("Couldn't check for snipes: {err:#}");
+                    }
+                }
+            }
+        }
+
+        debug!("Snipe watcher stopped");
+    }
+
+    async fn poll(&self) -> Result<(), Error> {
+        let guilds = self
+            .storage
+            .guild_settings()
+            .guilds_with(SettingName::SnipeChannel.key())
+            .await?;
+
+        // players in more than one guild only get looked up once
+        let mut checked = HashMap::new();
+        for guild in guilds {
+            if self.shutdown.is_triggered() {
+                break;
+            }
+            if let Err(err) = self.check_guild(guild, &mut checked).await {
+                warn!("Couldn't check guild {guild} for snipes: {err:#}");
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn check_guild(
+        &self,
+        guild: GuildId,
+        checked: &mut HashMap<(&'static str, String), Vec<NewScore>>,
+    ) -> Result<(), Error> {
+        let settings = GuildSettings::load(&self.storage, Some(guild)).await?;
+        let Some(channel) = settings.snipe_channel else {
+            return Ok(());
+        };
+        let service = Service::from(settings.leaderboard);
+
+        let since = Utc::now().timestamp() - LIMIT_WINDOW_SECS;
+        let posted = self.storage.snipes().count_since(guild, since).await?;
+        let mut budget = settings.snipe_limit.saturating_sub(posted);
+        if budget == 0 {
+            debug!("Guild {guild} is at its snipe limit");
+            return Ok(());
+        }
+
+        let accounts = self
+            .storage
+            .linked_accounts()
+            .in_guild(guild, service, MAX_MEMBERS)
+            .await?;
+        if accounts.len() < 2 {
+            return Ok(());
+        }
+
+        for account in &accounts {
+            let key = (service.key(), account.player_id.clone());
+            if !checked.contains_key(&key) {
+                let scores = self.new_scores(account).await.unwrap_or_else(|err| {
+                    warn!(
+                        "Couldn't get new {} scores for player {}: {err:#}",
+                        service.name(),
+                        account.player_id
+                    );
+                    Vec::new()
+                });
+                checked.insert(key.clone(), scores);
+            }
+
+            for new in &checked[&key] {
+                if budget == 0 || self.shutdown.is_triggered() {
+                    return Ok(());
+                }
+
+                // another user could've linked the same player
+                let new = NewScore {
+                    score: MemberScore {
+                        user_id: account.user_id,
+                        ..new.score.clone()
+                    },
+                    ..new.clone()
+                };
+                // one bad score shouldn't cost the rest theirs
+                match self
+                    .check_score(guild, channel, &settings, service, &accounts, &new)
+                    .await
+                {
+                    Ok(true) => budget -= 1,
+                    Ok(false) => {}
+                    Err(err) => warn!(
+                        "Couldn't check {}'s score on {} for snipes: {err:#}",
+                        new.score.player_id,
+                        new.leaderboard()
+                    ),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// A player's scores since they were last checked, oldest first, with
+    /// what they had on each leaderboard before.
+    ///
+    /// Done once per player per check, before any guild looks at them, since
+    /// it moves their bests forward.
+    async fn new_scores(&self, account: &LinkedAccount) -> Result<Vec<NewScore>, Error> {
+        let snipes = self.storage.snipes();
+        // anything set while this check runs is left for the next one
+        let started = Utc::now().timestamp();
+        let Some(since) = snipes
+            .last_checked(account.service, &account.player_id)
+            .await?
+        else {
+            // the first check is just to know where to start from
+            snipes
+                .mark_checked(account.service, &account.player_id, started)
+                .await?;
+            return Ok(Vec::new());
+        };
+
+        let mut scores: Vec<NewScore> = match account.service {
+            Service::ScoreSaber => self
+                .scoresaber
+                .recent_scores(&account.player_id, RECENT_SCORES as usize)
+                .await?
+                .into_iter()
+                .map(|played| NewScore {
+                    hash: played.leaderboard.song_hash.clone(),
+                    characteristic: played.leaderboard.difficulty.characteristic().to_owned(),
+                    difficulty: played.leaderboard.difficulty.name().to_owned(),
+                    song_name: played.leaderboard.song_name.clone(),
+                    max_score: played.leaderboard.max_score,
+                    score: MemberScore::from_scoresaber(
+                        account,
+                        &played.score,
+                        played.leaderboard.max_score,
+                    ),
+                    previous_best: None,
+                })
+                .collect(),
+            Service::BeatLeader => self
+                .beatleader
+                .scores(&account.player_id, "date", RECENT_SCORES)
+                .await?
+                .into_iter()
+                .filter_map(|played| {
+                    let leaderboard = played.leaderboard.as_ref()?;
+                    Some(NewScore {
+                        hash: leaderboard.song.hash.clone(),
+                        characteristic: leaderboard.difficulty.characteristic().to_owned(),
+                        difficulty: leaderboard.difficulty.difficulty_name.clone(),
+                        song_name: leaderboard.song.name.clone(),
+                        max_score: 0,
+                        score: MemberScore::from_beatleader(account, &played),
+                        previous_best: None,
+                    })
+                })
+                .collect(),
+        };
+
+        // a score from the second of the last check might get looked at
+        // twice, but the same lead change never gets posted twice anyway
+        scores.retain(|new| new.score.set_at >= since);
+        scores.sort_by_key(|new| new.score.set_at);
+
+        // in order, so two scores on one leaderboard compare to each other
+        for new in &mut scores {
+            let leaderboard = new.leaderboard();
+            new.previous_best = snipes
+                .best(account.service, &account.player_id, &leaderboard)
+                .await?;
+            snipes
+                .set_best(
+                    account.service,
+                    &account.player_id,
+                    &leaderboard,
+                    new.score.score,
+                    new.score.set_at,
+                )
+                .await?;
+        }
+
+        // only now, so a failed lookup gets the same scores next time
+        snipes
+            .mark_checked(account.service, &account.player_id, started)
+            .await?;
+
+        Ok(scores)
+    }
+
+    /// Posts a snipe if a new score took the lead from another member,
+    /// returning whether it did.
+    async fn check_score(
+        &self,
+        guild: GuildId,
+        channel: ChannelId,
+        settings: &GuildSettings,
+        service: Service,
+        accounts: &[LinkedAccount],
+        new: &NewScore,
+    ) -> Result<bool, Error> {
+        let sniper = &new.score;
+        let others = accounts
+            .iter()
+            .filter(|account| {
+                account.user_id != sniper.user_id && account.player_id != sniper.player_id
+            })
+            .cloned();
+
+        let scores: Vec<MemberScore> = futures::stream::iter(others)
+            .map(|account| self.member_score(service, account, new))
+            .buffer_unordered(CONCURRENCY)
+            .try_filter_map(|score| async move { Ok(score) })
+            .try_collect()
+            .await?;
+        let Some(victim) = victim(sniper, new.previous_best, scores) else {
+            return Ok(false);
+        };
+
+        let snipes = self.storage.snipes();
+        let leaderboard = new.leaderboard();
+        let last_sniper = snipes
+            .last_sniper(guild, service, &leaderboard, sniper.user_id, victim.user_id)
+            .await?;
+        if last_sniper == Some(sniper.user_id)
+            || snipes
+                .is_muted(guild, sniper.user_id, victim.user_id)
+                .await?
+        {
+            return Ok(false);
+        }
+
+        // either of them might have left since they were last seen
+        for user in [sniper.user_id, victim.user_id] {
+            if !is_member(&self.http, guild, user).await? {
+                return Ok(false);
+            }
+        }
+
+        // OST maps and the like aren't on BeatSaver, so they go without
+        let map = match self.beatsaver.map_by_hash(&new.hash).await {
+            Ok(map) => Some(map),
+            Err(err) if ApiError::is_not_found(&err) || ApiError::is_deleted(&err) => None,
+            Err(err) => return Err(err),
+        };
+        let map_name = map.as_ref().map_or(&new.song_name, |map| &map.name);
+        let mut builder = CreateMessage::new()
+            .embed(snipe::build_embed(
+                map_name,
+                service,
+                &new.characteristic,
+                &new.difficulty,
+                sniper,
+                &victim,
+            ))
+            .allowed_mentions(CreateAllowedMentions::new());
+        if let Some(map) = map {
+            let map_embed = MapEmbed::new(map)
+                .with_hash(&new.hash)
+                .with_settings(settings);
+            // without its menus, since using them would replace the snipe
+            builder = builder.embed(map_embed.build_embeds()[0].clone());
+        }
+
+        match channel.send_message(&self.http, builder).await {
+            Ok(_) => {
+                snipes
+                    .record(guild, service, &leaderboard, sniper.user_id, victim.user_id)
+                    .await?;
+                info!(
+                    "Posted {} sniping {} on {leaderboard} to channel {channel}",
+                    sniper.user_id, victim.user_id
+                );
+                Ok(true)
+            }
+            Err(err) if is_unknown_channel(&err) => {
+                self.storage
+                    .guild_settings()
+                    .reset(guild, SettingName::SnipeChannel.key())
+                    .await?;
+                info!("Snipe channel {channel} is gone, turned snipes off in guild {guild}");
+                Ok(false)
+            }
+            Err(err) => {
+                // most likely missing permissions, so it's skipped
+                warn!("Couldn't post a snipe to channel {channel}: {err}");
+                Ok(false)
+            }
+        }
+    }
+
+    /// Another member's score on the same difficulty, if they have one.
+    async fn member_score(
+        &self,
+        service: Service,
+        account: LinkedAccount,
+        new: &NewScore,
+    ) -> Result<Option<MemberScore>, Error> {
+        let res = match service {
+            Service::ScoreSaber => self
+                .scoresaber
+                .player_score(
+                    &account.player_id,
+                    &new.hash,
+                    &new.characteristic,
+                    &new.difficulty,
+                )
+                .await
+                .map(|score| {
+                    score.map(|score| MemberScore::from_scoresaber(&account, &score, new.max_score))
+                }),
+            Service::BeatLeader => self
+                .beatleader
+                .player_score(
+                    &account.player_id,
+                    &new.hash,
+                    &new.characteristic,
+                    &new.difficulty,
+                )
+                .await
+                .map(|score| score.map(|score| MemberScore::from_beatleader(&account, &score))),
+        };
+
+        match res {
+            Ok(score) => Ok(score),
+            // their account's gone, which shouldn't stop the others
+            Err(err) if ErrorKind::classify(&err).is_not_found() => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Whose lead a new score took, out of other members' scores on the same
+/// difficulty.
+///
+/// Only whoever was right above them before counts, and only if they really
+/// were above, not just below the new score.
+fn victim(
+    sniper: &MemberScore,
+    previous_best: Option<u64>,
+    mut others: Vec<MemberScore>,
+) -> Option<MemberScore> {
+    others.retain(|score| score.score < sniper.score && score.set_at < sniper.set_at);
+    let victim = others.into_iter().max_by_key(|score| score.score)?;
+    if previous_best.is_some_and(|best| best >= victim.score) {
+        return None;
+    }
+    Some(victim)
+}
+
+#[cfg(test)]
+mod tests {
+    use poise::serenity_prelude::UserId;
+
+    use super::*;
+
+    fn score(user: u64, score: u64, set_at: i64) -> MemberScore {
+        MemberScore {
+            user_id: UserId::new(user),
+            player_id: user.to_string(),
+            player_name: format!("Player {user}"),
+            score,
+            accuracy: None,
+            pp: 0.0,
+            misses: 0,
+            full_combo: false,
+            set_at,
+        }
+    }
+
+    #[test]
+    fn beating_an_older_score_is_a_snipe() {
+        let sniper = score(1, 900, 100);
+        let victim = victim(&sniper, None, vec![score(2, 800, 50)]).unwrap();
+        assert_eq!(victim.user_id, UserId::new(2));
+    }
+
+    #[test]
+    fn the_score_right_below_is_the_victim() {
+        let sniper = score(1, 900, 100);
+        let others = vec![score(2, 700, 50), score(3, 850, 60), score(4, 600, 70)];
+        let victim = victim(&sniper, Some(650), others).unwrap();
+        assert_eq!(victim.user_id, UserId::new(3));
+    }
+
+    #[test]
+    fn already_being_ahead_is_no_snipe() {
+        let sniper = score(1, 900, 100);
+        assert!(victim(&sniper, Some(850), vec![score(2, 800, 50)]).is_none());
+        assert!(victim(&sniper, Some(800), vec![score(2, 800, 50)]).is_none());
+    }
+
+    #[test]
+    fn being_behind_before_is_a_snipe() {
+        let sniper = score(1, 900, 100);
+        assert!(victim(&sniper, Some(799), vec![score(2, 800, 50)]).is_some());
+    }
+
+    #[test]
+    fn higher_or_later_scores_are_not_beaten() {
+        let sniper = score(1, 900, 100);
+        let others = vec![score(2, 950, 50), score(3, 900, 50), score(4, 800, 150)];
+        assert!(victim(&sniper, None, others).is_none());
+    }
+}
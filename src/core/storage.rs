@@ -0,0 +1,123 @@
+pub mod guild_settings;
+pub mod linked_accounts;
+pub mod snipes;
+pub mod subscriptions;
+
+use std::{str::FromStr, time::Duration};
+
+use anyhow::Context as _;
+use log::info;
+use sqlx::{
+    migrate::Migrator,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions},
+    SqlitePool,
+};
+
+use self::{
+    guild_settings::GuildSettings, linked_accounts::LinkedAccounts, snipes::Snipes,
+    subscriptions::Subscriptions,
+};
+use crate::{core::config::StorageConfig, Error};
+
+/// Everything in `migrations/`, built into the binary.
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Database name that keeps everything in memory.
+pub const IN_MEMORY: &str = ":memory:";
+
+/// SQLite only writes one thing at a time anyway.
+const MAX_CONNECTIONS: u32 = 4;
+/// How long a query waits for another one's write lock before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The bot's SQLite database. Cheap to clone, and safe to use from anywhere.
+///
+/// Queries live in the repositories (like [`Storage::guild_settings`]) rather
+/// than being written wherever they're needed.
+#[derive(Clone)]
+pub struct Storage {
+    pool: SqlitePool,
+}
+
+impl Storage {
+    /// Opens the database, creating it if needed, and brings its schema up
+    /// to date.
+    pub async fn open(config: &StorageConfig) -> Result<Self, Error> {
+        if config.database == IN_MEMORY {
+            return Self::in_memory().await;
+        }
+
+        let options = SqliteConnectOptions::new()
+            .filename(&config.database)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .foreign_keys(true)
+            .busy_timeout(BUSY_TIMEOUT);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(MAX_CONNECTIONS)
+            .connect_with(options)
+            .await
+            .with_context(|| format!("couldn't open database {}", config.database))?;
+
+        info!("Opened database {}", config.database);
+        Self::migrate(pool).await
+    }
+
+    /// A fresh database that's gone once this is dropped, for tests and
+    /// trying things out.
+    pub async fn in_memory() -> Result<Self, Error> {
+        // every connection to :memory: gets its own database, so keep exactly one
+        let options = SqliteConnectOptions::from_str("sqlite::memory:")?.foreign_keys(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .min_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(options)
+            .await
+            .context("couldn't open in-memory database")?;
+
+        info!("Using an in-memory database, nothing will be saved");
+        Self::migrate(pool).await
+    }
+
+    async fn migrate(pool: SqlitePool) -> Result<Self, Error> {
+        MIGRATOR
+            .run(&pool)
+            .await
+            .context("couldn't bring the database schema up to date")?;
+
+        Ok(Self { pool })
+    }
+
+    pub fn guild_settings(&self) -> GuildSettings<'_> {
+        GuildSettings::new(&self.pool)
+    }
+
+    pub fn subscriptions(&self) -> Subscriptions<'_> {
+        Subscriptions::new(&self.pool)
+    }
+
+    pub fn linked_accounts(&self) -> LinkedAccounts<'_> {
+        LinkedAccounts::new(&self.pool)
+    }
+
+    pub fn snipes(&self) -> Snipes<'_> {
+        Snipes::new(&self.pool)
+    }
+
+    /// Waits for running queries, then closes every connection.
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+}
+
+/// SQLite only has signed integers, but Discord IDs fit in them anyway.
+fn db_id(id: u64) -> i64 {
+    id as i64
+}
+
+/// The current time as unix seconds, the way timestamps are stored.
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
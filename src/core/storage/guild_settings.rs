@@ -0,0 +1,204 @@
+use poise::serenity_prelude::{GuildId, UserId};
+use sqlx::SqlitePool;
+
+use super::{db_id, now};
+use crate::Error;
+
+/// Per-guild settings, stored as text under a name.
+///
+/// Settings that have never been changed (or have been reset) aren't stored
+/// at all, so callers fall back to their own defaults.
+pub struct GuildSettings<'a> {
+    pool: &'a SqlitePool,
+}
+
+impl<'a> GuildSettings<'a> {
+    pub(super) fn new(pool: &'a SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get(&self, guild: GuildId, key: &str) -> Result<Option<String>, Error> {
+        let value = sqlx::query_scalar(
+            "SELECT value FROM guild_settings WHERE guild_id = ? AND key = ?",
+        )
+        .bind(db_id(guild.get()))
+        .bind(key)
+        .fetch_optional(self.pool)
+        .await?;
+
+        Ok(value)
+    }
+
+    /// Every changed setting for a guild, as `(key, value)`.
+    pub async fn all(&self, guild: GuildId) -> Result<Vec<(String, String)>, Error> {
+        let settings = sqlx::query_as(
+            "SELECT key, value FROM guild_settings WHERE guild_id = ? ORDER BY key",
+        )
+        .bind(db_id(guild.get()))
+        .fetch_all(self.pool)
+        .await?;
+
+        Ok(settings)
+    }
+
+    pub async fn set(
+        &self,
+        guild: GuildId,
+        key: &str,
+        value: &str,
+        by: UserId,
+    ) -> Result<(), Error> {
+        sqlx::query(
+            "INSERT INTO guild_settings (guild_id, key, value, updated_by, updated_at)
+             VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT (guild_id, key) DO UPDATE SET
+                 value = excluded.value,
+                 updated_by = excluded.updated_by,
+                 updated_at = excluded.updated_at",
+        )
+        .bind(db_id(guild.get()))
+        .bind(key)
+        .bind(value)
+        .bind(db_id(by.get()))
+        .bind(now())
+        .execute(self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Every guild that has changed a setting, e.g. to find who turned
+    /// something on.
+    pub async fn guilds_with(&self, key: &str) -> Result<Vec<GuildId>, Error> {
+        let guilds: Vec<i64> = sqlx::query_scalar(
+            "SELECT guild_id FROM guild_settings WHERE key = ? ORDER BY guild_id",
+        )
+        .bind(key)
+        .fetch_all(self.pool)
+        .await?;
+
+        Ok(guilds
+            .into_iter()
+            .map(|id| GuildId::new(id as u64))
+            .collect())
+    }
+
+    /// Puts a setting back to its default, returning whether it had been changed.
+    pub async fn reset(&self, guild: GuildId, key: &str) -> Result<bool, Error> {
+        let res = sqlx::query("DELETE FROM guild_settings WHERE guild_id = ? AND key = ?")
+            .bind(db_id(guild.get()))
+            .bind(key)
+            .execute(self.pool)
+            .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Puts every setting back to its default, returning how many had been changed.
+    pub async fn reset_all(&self, guild: GuildId) -> Result<u64, Error> {
+        let res = sqlx::query("DELETE FROM guild_settings WHERE guild_id = ?")
+            .bind(db_id(guild.get()))
+            .execute(self.pool)
+            .await?;
+
+        Ok(res.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Storage;
+    use super::*;
+
+    const GUILD: GuildId = GuildId::new(1);
+    const OTHER_GUILD: GuildId = GuildId::new(2);
+    const BY: UserId = UserId::new(10);
+
+    #[tokio::test]
+    async fn set_then_get() {
+        let storage = Storage::in_memory().await.unwrap();
+        let settings = storage.guild_settings();
+
+        assert_eq!(settings.get(GUILD, "ephemeral").await.unwrap(), None);
+
+        settings.set(GUILD, "ephemeral", "true", BY).await.unwrap();
+        settings.set(GUILD, "ephemeral", "false", BY).await.unwrap();
+        assert_eq!(
+            settings.get(GUILD, "ephemeral").await.unwrap().as_deref(),
+            Some("false")
+        );
+        // other guilds keep their own
+        assert_eq!(settings.get(OTHER_GUILD, "ephemeral").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn all_and_guilds_with() {
+        let storage = Storage::in_memory().await.unwrap();
+        let settings = storage.guild_settings();
+
+        settings
+            .set(GUILD, "leaderboard", "beatleader", BY)
+            .await
+            .unwrap();
+        settings.set(GUILD, "ephemeral", "true", BY).await.unwrap();
+        settings
+            .set(OTHER_GUILD, "ephemeral", "false", BY)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            settings.all(GUILD).await.unwrap(),
+            [
+                ("ephemeral".to_owned(), "true".to_owned()),
+                ("leaderboard".to_owned(), "beatleader".to_owned()),
+            ]
+        );
+        assert_eq!(
+            settings.guilds_with("ephemeral").await.unwrap(),
+            [GUILD, OTHER_GUILD]
+        );
+        assert_eq!(settings.guilds_with("leaderboard").await.unwrap(), [GUILD]);
+    }
+
+    #[tokio::test]
+    async fn reset() {
+        let storage = Storage::in_memory().await.unwrap();
+        let settings = storage.guild_settings();
+
+        settings.set(GUILD, "ephemeral", "true", BY).await.unwrap();
+        settings
+            .set(OTHER_GUILD, "ephemeral", "true", BY)
+            .await
+            .unwrap();
+
+        assert!(settings.reset(GUILD, "ephemeral").await.unwrap());
+        assert!(!settings.reset(GUILD, "ephemeral").await.unwrap());
+        assert_eq!(settings.get(GUILD, "ephemeral").await.unwrap(), None);
+        assert!(settings
+            .get(OTHER_GUILD, "ephemeral")
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn reset_all() {
+        let storage = Storage::in_memory().await.unwrap();
+        let settings = storage.guild_settings();
+
+        settings.set(GUILD, "ephemeral", "true", BY).await.unwrap();
+        settings
+            .set(GUILD, "leaderboard", "beatleader", BY)
+            .await
+            .unwrap();
+        settings
+            .set(OTHER_GUILD, "ephemeral", "true", BY)
+            .await
+            .unwrap();
+
+        assert_eq!(settings.reset_all(GUILD).await.unwrap(), 2);
+        assert_eq!(settings.reset_all(GUILD).await.unwrap(), 0);
+        assert!(settings.all(GUILD).await.unwrap().is_empty());
+        assert_eq!(settings.all(OTHER_GUILD).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,332 @@
+use poise::serenity_prelude::{GuildId, UserId};
+use sqlx::SqlitePool;
+
+use super::{db_id, now};
+use crate::Error;
+
+/// Somewhere a Discord user can link a player account from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Service {
+    ScoreSaber,
+    BeatLeader,
+}
+
+impl Service {
+    /// How it's stored.
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::ScoreSaber => "scoresaber",
+            Self::BeatLeader => "beatleader",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::ScoreSaber => "ScoreSaber",
+            Self::BeatLeader => "BeatLeader",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "scoresaber" => Some(Self::ScoreSaber),
+            "beatleader" => Some(Self::BeatLeader),
+            _ => None,
+        }
+    }
+
+    /// Link to a player's profile.
+    pub fn profile_url(self, player_id: &str) -> String {
+        match self {
+            Self::ScoreSaber => format!("https://scoresaber.com/u/{player_id}"),
+            Self::BeatLeader => format!("https://beatleader.com/u/{player_id}"),
+        }
+    }
+}
+
+/// A Discord user's account on a score service.
+#[derive(Clone, Debug)]
+pub struct LinkedAccount {
+    pub user_id: UserId,
+    pub service: Service,
+    pub player_id: String,
+    /// The player's name when they linked.
+    pub player_name: String,
+    pub verified: bool,
+    /// Unix seconds.
+    pub linked_at: i64,
+}
+
+type LinkedAccountRow = (i64, String, String, String, bool, i64);
+
+impl LinkedAccount {
+    fn from_row(
+        (user_id, service, player_id, player_name, verified, linked_at): LinkedAccountRow,
+    ) -> Option<Self> {
+        Some(Self {
+            user_id: UserId::new(user_id as u64),
+            service: Service::from_key(&service)?,
+            player_id,
+            player_name,
+            verified,
+            linked_at,
+        })
+    }
+}
+
+/// Which player accounts belong to which Discord users.
+pub struct LinkedAccounts<'a> {
+    pool: &'a SqlitePool,
+}
+
+impl<'a> LinkedAccounts<'a> {
+    pub(super) fn new(pool: &'a SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Links an account, replacing whatever the user had linked there before.
+    pub async fn link(
+        &self,
+        user: UserId,
+        service: Service,
+        player_id: &str,
+        player_name: &str,
+        verified: bool,
+    ) -> Result<(), Error> {
+        sqlx::query(
+            "INSERT INTO linked_accounts
+                 (user_id, service, player_id, player_name, verified, linked_at)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT (user_id, service) DO UPDATE SET
+                 player_id = excluded.player_id,
+                 player_name = excluded.player_name,
+                 verified = excluded.verified,
+                 linked_at = excluded.linked_at",
+        )
+        .bind(db_id(user.get()))
+        .bind(service.key())
+        .bind(player_id)
+        .bind(player_name)
+        .bind(verified)
+        .bind(now())
+        .execute(self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns `false` if nothing was linked.
+    pub async fn unlink(&self, user: UserId, service: Service) -> Result<bool, Error> {
+        let res = sqlx::query("DELETE FROM linked_accounts WHERE user_id = ? AND service = ?")
+            .bind(db_id(user.get()))
+            .bind(service.key())
+            .execute(self.pool)
+            .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    pub async fn get(
+        &self,
+        user: UserId,
+        service: Service,
+    ) -> Result<Option<LinkedAccount>, Error> {
+        let row: Option<LinkedAccountRow> = sqlx::query_as(
+            "SELECT user_id, service, player_id, player_name, verified, linked_at
+             FROM linked_accounts WHERE user_id = ? AND service = ?",
+        )
+        .bind(db_id(user.get()))
+        .bind(service.key())
+        .fetch_optional(self.pool)
+        .await?;
+
+        Ok(row.and_then(LinkedAccount::from_row))
+    }
+
+    /// Everything a user has linked.
+    pub async fn for_user(&self, user: UserId) -> Result<Vec<LinkedAccount>, Error> {
+        let rows: Vec<LinkedAccountRow> = sqlx::query_as(
+            "SELECT user_id, service, player_id, player_name, verified, linked_at
+             FROM linked_accounts WHERE user_id = ? ORDER BY service",
+        )
+        .bind(db_id(user.get()))
+        .fetch_all(self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(LinkedAccount::from_row)
+            .collect())
+    }
+
+    /// Notes that a user was in a guild. Whether they've linked anything yet
+    /// doesn't matter, so it works out whichever happens first.
+    pub async fn seen_in(&self, guild: GuildId, user: UserId) -> Result<(), Error> {
+        sqlx::query(
+            "INSERT INTO linked_guild_members (guild_id, user_id, seen_at) VALUES (?, ?, ?)
+             ON CONFLICT (guild_id, user_id) DO UPDATE SET seen_at = excluded.seen_at",
+        )
+        .bind(db_id(guild.get()))
+        .bind(db_id(user.get()))
+        .bind(now())
+        .execute(self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Accounts on a service belonging to people seen in a guild, most
+    /// recently seen first. They might have left since.
+    pub async fn in_guild(
+        &self,
+        guild: GuildId,
+        service: Service,
+        limit: u32,
+    ) -> Result<Vec<LinkedAccount>, Error> {
+        let rows: Vec<LinkedAccountRow> = sqlx::query_as(
+            "SELECT a.user_id, a.service, a.player_id, a.player_name, a.verified, a.linked_at
+             FROM linked_guild_members m
+             JOIN linked_accounts a ON a.user_id = m.user_id
+             WHERE m.guild_id = ? AND a.service = ?
+             ORDER BY m.seen_at DESC
+             LIMIT ?",
+        )
+        .bind(db_id(guild.get()))
+        .bind(service.key())
+        .bind(limit)
+        .fetch_all(self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(LinkedAccount::from_row)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Storage;
+    use super::*;
+
+    const GUILD: GuildId = GuildId::new(1);
+    const OTHER_GUILD: GuildId = GuildId::new(2);
+    const LINKED: UserId = UserId::new(10);
+    const BOTH: UserId = UserId::new(11);
+    const UNLINKED: UserId = UserId::new(12);
+
+    async fn storage() -> Storage {
+        let storage = Storage::in_memory().await.unwrap();
+        let accounts = storage.linked_accounts();
+        accounts
+            .link(LINKED, Service::ScoreSaber, "1", "Kin", false)
+            .await
+            .unwrap();
+        accounts
+            .link(BOTH, Service::ScoreSaber, "2", "Mafu", true)
+            .await
+            .unwrap();
+        accounts
+            .link(BOTH, Service::BeatLeader, "3", "Mafu", true)
+            .await
+            .unwrap();
+
+        storage
+    }
+
+    fn users(accounts: &[LinkedAccount]) -> Vec<UserId> {
+        let mut users: Vec<UserId> = accounts.iter().map(|account| account.user_id).collect();
+        users.sort();
+        users
+    }
+
+    #[tokio::test]
+    async fn only_linked_users_are_listed() {
+        let storage = storage().await;
+        let accounts = storage.linked_accounts();
+
+        for user in [LINKED, BOTH, UNLINKED] {
+            accounts.seen_in(GUILD, user).await.unwrap();
+        }
+        // seeing someone twice doesn't list them twice
+        accounts.seen_in(GUILD, LINKED).await.unwrap();
+
+        let seen = accounts
+            .in_guild(GUILD, Service::ScoreSaber, 10)
+            .await
+            .unwrap();
+        assert_eq!(users(&seen), [LINKED, BOTH]);
+        assert!(seen
+            .iter()
+            .all(|account| account.service == Service::ScoreSaber));
+    }
+
+    #[tokio::test]
+    async fn in_guild_goes_by_guild_and_service() {
+        let storage = storage().await;
+        let accounts = storage.linked_accounts();
+
+        accounts.seen_in(GUILD, LINKED).await.unwrap();
+        accounts.seen_in(OTHER_GUILD, BOTH).await.unwrap();
+
+        let beatleader = accounts
+            .in_guild(OTHER_GUILD, Service::BeatLeader, 10)
+            .await
+            .unwrap();
+        assert_eq!(users(&beatleader), [BOTH]);
+        assert_eq!(beatleader[0].player_id, "3");
+        assert!(accounts
+            .in_guild(GUILD, Service::BeatLeader, 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn in_guild_stops_at_the_limit() {
+        let storage = storage().await;
+        let accounts = storage.linked_accounts();
+
+        accounts.seen_in(GUILD, LINKED).await.unwrap();
+        accounts.seen_in(GUILD, BOTH).await.unwrap();
+
+        let seen = accounts
+            .in_guild(GUILD, Service::ScoreSaber, 1)
+            .await
+            .unwrap();
+        assert_eq!(seen.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn unlinked_accounts_drop_out() {
+        let storage = storage().await;
+        let accounts = storage.linked_accounts();
+
+        accounts.seen_in(GUILD, LINKED).await.unwrap();
+        accounts.unlink(LINKED, Service::ScoreSaber).await.unwrap();
+
+        assert!(accounts
+            .in_guild(GUILD, Service::ScoreSaber, 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn linking_after_being_seen_counts() {
+        let storage = storage().await;
+        let accounts = storage.linked_accounts();
+
+        accounts.seen_in(GUILD, UNLINKED).await.unwrap();
+        accounts
+            .link(UNLINKED, Service::ScoreSaber, "4", "New", false)
+            .await
+            .unwrap();
+
+        let seen = accounts
+            .in_guild(GUILD, Service::ScoreSaber, 10)
+            .await
+            .unwrap();
+        assert_eq!(users(&seen), [UNLINKED]);
+    }
+}
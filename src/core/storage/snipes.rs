@@ -0,0 +1,398 @@
+use poise::serenity_prelude::{GuildId, UserId};
+use sqlx::SqlitePool;
+
+use super::{db_id, linked_accounts::Service, now};
+use crate::Error;
+
+/// Posted snipes, who muted them, and whose scores have been checked.
+///
+/// Also keeps the best score seen from each watched player per leaderboard.
+pub struct Snipes<'a> {
+    pool: &'a SqlitePool,
+}
+
+impl<'a> Snipes<'a> {
+    pub(super) fn new(pool: &'a SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// How many snipes a guild has had posted since a time (unix seconds).
+    pub async fn count_since(&self, guild: GuildId, since: i64) -> Result<u32, Error> {
+        let count: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM snipes WHERE guild_id = ? AND sniped_at >= ?")
+                .bind(db_id(guild.get()))
+                .bind(since)
+                .fetch_one(self.pool)
+                .await?;
+
+        Ok(count as u32)
+    }
+
+    /// Who took the lead the last time either of two people sniped the other
+    /// on a leaderboard.
+    pub async fn last_sniper(
+        &self,
+        guild: GuildId,
+        service: Service,
+        leaderboard: &str,
+        a: UserId,
+        b: UserId,
+    ) -> Result<Option<UserId>, Error> {
+        let sniper: Option<i64> = sqlx::query_scalar(
+            "SELECT sniper_id FROM snipes
+             WHERE guild_id = ? AND service = ? AND leaderboard = ?
+                 AND ((sniper_id = ? AND victim_id = ?) OR (sniper_id = ? AND victim_id = ?))
+             ORDER BY sniped_at DESC, rowid DESC
+             LIMIT 1",
+        )
+        .bind(db_id(guild.get()))
+        .bind(service.key())
+        .bind(leaderboard)
+        .bind(db_id(a.get()))
+        .bind(db_id(b.get()))
+        .bind(db_id(b.get()))
+        .bind(db_id(a.get()))
+        .fetch_optional(self.pool)
+        .await?;
+
+        Ok(sniper.map(|id| UserId::new(id as u64)))
+    }
+
+    pub async fn record(
+        &self,
+        guild: GuildId,
+        service: Service,
+        leaderboard: &str,
+        sniper: UserId,
+        victim: UserId,
+    ) -> Result<(), Error> {
+        sqlx::query(
+            "INSERT INTO snipes (guild_id, service, leaderboard, sniper_id, victim_id, sniped_at)
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(db_id(guild.get()))
+        .bind(service.key())
+        .bind(leaderboard)
+        .bind(db_id(sniper.get()))
+        .bind(db_id(victim.get()))
+        .bind(now())
+        .execute(self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Stops posting snipes involving a user, against one person or anyone.
+    /// Returns `false` if that was already muted.
+    pub async fn mute(
+        &self,
+        guild: GuildId,
+        user: UserId,
+        muted: Option<UserId>,
+    ) -> Result<bool, Error> {
+        let res = sqlx::query(
+            "INSERT INTO snipe_mutes (guild_id, user_id, muted_id, created_at)
+             VALUES (?, ?, ?, ?)
+             ON CONFLICT (guild_id, user_id, muted_id) DO NOTHING",
+        )
+        .bind(db_id(guild.get()))
+        .bind(db_id(user.get()))
+        .bind(muted.map_or(0, |id| db_id(id.get())))
+        .bind(now())
+        .execute(self.pool)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Returns `false` if that wasn't muted.
+    pub async fn unmute(
+        &self,
+        guild: GuildId,
+        user: UserId,
+        muted: Option<UserId>,
+    ) -> Result<bool, Error> {
+        let res = sqlx::query(
+            "DELETE FROM snipe_mutes WHERE guild_id = ? AND user_id = ? AND muted_id = ?",
+        )
+        .bind(db_id(guild.get()))
+        .bind(db_id(user.get()))
+        .bind(muted.map_or(0, |id| db_id(id.get())))
+        .execute(self.pool)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// What a user has muted in a guild, `None` meaning everyone.
+    pub async fn mutes(&self, guild: GuildId, user: UserId) -> Result<Vec<Option<UserId>>, Error> {
+        let muted: Vec<i64> = sqlx::query_scalar(
+            "SELECT muted_id FROM snipe_mutes WHERE guild_id = ? AND user_id = ?
+             ORDER BY muted_id",
+        )
+        .bind(db_id(guild.get()))
+        .bind(db_id(user.get()))
+        .fetch_all(self.pool)
+        .await?;
+
+        Ok(muted
+            .into_iter()
+            .map(|id| (id != 0).then(|| UserId::new(id as u64)))
+            .collect())
+    }
+
+    /// Whether either of two people muted snipes between them.
+    pub async fn is_muted(&self, guild: GuildId, a: UserId, b: UserId) -> Result<bool, Error> {
+        let muted: bool = sqlx::query_scalar(
+            "SELECT EXISTS (
+                 SELECT 1 FROM snipe_mutes
+                 WHERE guild_id = ?
+                     AND ((user_id = ? AND muted_id IN (0, ?))
+                         OR (user_id = ? AND muted_id IN (0, ?)))
+             )",
+        )
+        .bind(db_id(guild.get()))
+        .bind(db_id(a.get()))
+        .bind(db_id(b.get()))
+        .bind(db_id(b.get()))
+        .bind(db_id(a.get()))
+        .fetch_one(self.pool)
+        .await?;
+
+        Ok(muted)
+    }
+
+    /// When a player's scores were last checked (unix seconds), if ever.
+    pub async fn last_checked(
+        &self,
+        service: Service,
+        player_id: &str,
+    ) -> Result<Option<i64>, Error> {
+        let checked_at = sqlx::query_scalar(
+            "SELECT checked_at FROM snipe_checks WHERE service = ? AND player_id = ?",
+        )
+        .bind(service.key())
+        .bind(player_id)
+        .fetch_optional(self.pool)
+        .await?;
+
+        Ok(checked_at)
+    }
+
+    /// Marks a player's scores as checked up to a time (unix seconds). Only
+    /// call this once the check worked, or scores in between get skipped.
+    pub async fn mark_checked(
+        &self,
+        service: Service,
+        player_id: &str,
+        at: i64,
+    ) -> Result<(), Error> {
+        sqlx::query(
+            "INSERT INTO snipe_checks (service, player_id, checked_at) VALUES (?, ?, ?)
+             ON CONFLICT (service, player_id) DO UPDATE SET checked_at = excluded.checked_at",
+        )
+        .bind(service.key())
+        .bind(player_id)
+        .bind(at)
+        .execute(self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// A player's best score on a leaderboard, as last seen.
+    pub async fn best(
+        &self,
+        service: Service,
+        player_id: &str,
+        leaderboard: &str,
+    ) -> Result<Option<u64>, Error> {
+        let score: Option<i64> = sqlx::query_scalar(
+            "SELECT score FROM snipe_bests
+             WHERE service = ? AND player_id = ? AND leaderboard = ?",
+        )
+        .bind(service.key())
+        .bind(player_id)
+        .bind(leaderboard)
+        .fetch_optional(self.pool)
+        .await?;
+
+        Ok(score.map(|score| score as u64))
+    }
+
+    /// Replaces a player's best score on a leaderboard with a newer one.
+    pub async fn set_best(
+        &self,
+        service: Service,
+        player_id: &str,
+        leaderboard: &str,
+        score: u64,
+        set_at: i64,
+    ) -> Result<(), Error> {
+        sqlx::query(
+            "INSERT INTO snipe_bests (service, player_id, leaderboard, score, set_at)
+             VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT (service, player_id, leaderboard) DO UPDATE SET
+                 score = excluded.score,
+                 set_at = excluded.set_at",
+        )
+        .bind(service.key())
+        .bind(player_id)
+        .bind(leaderboard)
+        .bind(score as i64)
+        .bind(set_at)
+        .execute(self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Storage;
+    use super::*;
+
+    const GUILD: GuildId = GuildId::new(1);
+    const ALICE: UserId = UserId::new(10);
+    const BOB: UserId = UserId::new(20);
+    const CAROL: UserId = UserId::new(30);
+
+    #[tokio::test]
+    async fn is_muted_either_way() {
+        let storage = Storage::in_memory().await.unwrap();
+        let snipes = storage.snipes();
+
+        assert!(!snipes.is_muted(GUILD, ALICE, BOB).await.unwrap());
+        assert!(snipes.mute(GUILD, ALICE, Some(BOB)).await.unwrap());
+        assert!(!snipes.mute(GUILD, ALICE, Some(BOB)).await.unwrap());
+
+        assert!(snipes.is_muted(GUILD, ALICE, BOB).await.unwrap());
+        assert!(snipes.is_muted(GUILD, BOB, ALICE).await.unwrap());
+        assert!(!snipes.is_muted(GUILD, ALICE, CAROL).await.unwrap());
+        assert!(!snipes.is_muted(GuildId::new(2), ALICE, BOB).await.unwrap());
+
+        assert!(snipes.unmute(GUILD, ALICE, Some(BOB)).await.unwrap());
+        assert!(!snipes.is_muted(GUILD, ALICE, BOB).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn muting_everyone() {
+        let storage = Storage::in_memory().await.unwrap();
+        let snipes = storage.snipes();
+
+        snipes.mute(GUILD, CAROL, None).await.unwrap();
+
+        assert!(snipes.is_muted(GUILD, ALICE, CAROL).await.unwrap());
+        assert!(snipes.is_muted(GUILD, CAROL, BOB).await.unwrap());
+        assert!(!snipes.is_muted(GUILD, ALICE, BOB).await.unwrap());
+        assert_eq!(snipes.mutes(GUILD, CAROL).await.unwrap(), vec![None]);
+    }
+
+    #[tokio::test]
+    async fn last_sniper_either_way() {
+        let storage = Storage::in_memory().await.unwrap();
+        let snipes = storage.snipes();
+        let last =
+            |leaderboard| snipes.last_sniper(GUILD, Service::ScoreSaber, leaderboard, ALICE, BOB);
+
+        assert_eq!(last("a").await.unwrap(), None);
+
+        snipes
+            .record(GUILD, Service::ScoreSaber, "a", ALICE, BOB)
+            .await
+            .unwrap();
+        assert_eq!(last("a").await.unwrap(), Some(ALICE));
+
+        snipes
+            .record(GUILD, Service::ScoreSaber, "a", BOB, ALICE)
+            .await
+            .unwrap();
+        assert_eq!(last("a").await.unwrap(), Some(BOB));
+
+        // other leaderboards, services and people don't count
+        snipes
+            .record(GUILD, Service::ScoreSaber, "b", ALICE, BOB)
+            .await
+            .unwrap();
+        snipes
+            .record(GUILD, Service::BeatLeader, "a", ALICE, BOB)
+            .await
+            .unwrap();
+        snipes
+            .record(GUILD, Service::ScoreSaber, "a", ALICE, CAROL)
+            .await
+            .unwrap();
+        assert_eq!(last("a").await.unwrap(), Some(BOB));
+        assert_eq!(snipes.count_since(GUILD, 0).await.unwrap(), 5);
+    }
+
+    #[tokio::test]
+    async fn checks_move_forward() {
+        let storage = Storage::in_memory().await.unwrap();
+        let snipes = storage.snipes();
+
+        assert_eq!(
+            snipes.last_checked(Service::ScoreSaber, "1").await.unwrap(),
+            None
+        );
+
+        snipes
+            .mark_checked(Service::ScoreSaber, "1", 100)
+            .await
+            .unwrap();
+        snipes
+            .mark_checked(Service::ScoreSaber, "1", 200)
+            .await
+            .unwrap();
+        assert_eq!(
+            snipes.last_checked(Service::ScoreSaber, "1").await.unwrap(),
+            Some(200)
+        );
+        assert_eq!(
+            snipes.last_checked(Service::BeatLeader, "1").await.unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn bests_per_leaderboard() {
+        let storage = Storage::in_memory().await.unwrap();
+        let snipes = storage.snipes();
+
+        assert_eq!(
+            snipes.best(Service::ScoreSaber, "1", "a").await.unwrap(),
+            None
+        );
+
+        snipes
+            .set_best(Service::ScoreSaber, "1", "a", 900, 100)
+            .await
+            .unwrap();
+        snipes
+            .set_best(Service::ScoreSaber, "1", "b", 500, 100)
+            .await
+            .unwrap();
+        snipes
+            .set_best(Service::ScoreSaber, "1", "a", 950, 200)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            snipes.best(Service::ScoreSaber, "1", "a").await.unwrap(),
+            Some(950)
+        );
+        assert_eq!(
+            snipes.best(Service::ScoreSaber, "1", "b").await.unwrap(),
+            Some(500)
+        );
+        assert_eq!(
+            snipes.best(Service::BeatLeader, "1", "a").await.unwrap(),
+            None
+        );
+        assert_eq!(
+            snipes.best(Service::ScoreSaber, "2", "a").await.unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use poise::serenity_prelude::{ChannelId, GuildId, UserId};
+use sqlx::SqlitePool;
+
+use super::{db_id, now};
+use crate::Error;
+
+/// A channel following a mapper's new uploads.
+#[derive(Clone, Debug)]
+pub struct Subscription {
+    pub guild_id: GuildId,
+    pub channel_id: ChannelId,
+    pub mapper_id: u32,
+    /// The mapper's name when they were subscribed to.
+    pub mapper_name: String,
+    /// Unix seconds. Maps published before this don't get posted.
+    pub created_at: i64,
+}
+
+type SubscriptionRow = (i64, i64, i64, String, i64);
+
+impl From<SubscriptionRow> for Subscription {
+    fn from((guild_id, channel_id, mapper_id, mapper_name, created_at): SubscriptionRow) -> Self {
+        Self {
+            guild_id: GuildId::new(guild_id as u64),
+            channel_id: ChannelId::new(channel_id as u64),
+            mapper_id: mapper_id as u32,
+            mapper_name,
+            created_at,
+        }
+    }
+}
+
+/// Mapper subscriptions, and which maps have been posted where.
+pub struct Subscriptions<'a> {
+    pool: &'a SqlitePool,
+}
+
+impl<'a> Subscriptions<'a> {
+    pub(super) fn new(pool: &'a SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Subscribes a channel to a mapper, returning `false` if it already was.
+    pub async fn add(
+        &self,
+        guild: GuildId,
+        channel: ChannelId,
+        mapper_id: u32,
+        mapper_name: &str,
+        by: UserId,
+    ) -> Result<bool, Error> {
+        let res = sqlx::query(
+            "INSERT INTO subscriptions
+                 (guild_id, channel_id, mapper_id, mapper_name, created_by, created_at)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT (channel_id, mapper_id) DO NOTHING",
+        )
+        .bind(db_id(guild.get()))
+        .bind(db_id(channel.get()))
+        .bind(i64::from(mapper_id))
+        .bind(mapper_name)
+        .bind(db_id(by.get()))
+        .bind(now())
+        .execute(self.pool)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Unsubscribes from a mapper by the name they were subscribed under,
+    /// in one channel or all of a guild's. Returns how many were removed.
+    pub async fn remove(
+        &self,
+        guild: GuildId,
+        mapper_name: &str,
+        channel: Option<ChannelId>,
+    ) -> Result<u64, Error> {
+        let res = sqlx::query(
+            "DELETE FROM subscriptions
+             WHERE guild_id = ? AND mapper_name = ? COLLATE NOCASE
+                 AND (? IS NULL OR channel_id = ?)",
+        )
+        .bind(db_id(guild.get()))
+        .bind(mapper_name)
+        .bind(channel.map(|c| db_id(c.get())))
+        .bind(channel.map(|c| db_id(c.get())))
+        .execute(self.pool)
+        .await?;
+
+        Ok(res.rows_affected())
+    }
+
+    /// Drops every subscription for a channel, e.g. once it's been deleted.
+    pub async fn remove_channel(&self, channel: ChannelId) -> Result<u64, Error> {
+        let res = sqlx::query("DELETE FROM subscriptions WHERE channel_id = ?")
+            .bind(db_id(channel.get()))
+            .execute(self.pool)
+            .await?;
+
+        Ok(res.rows_affected())
+    }
+
+    /// A guild's subscriptions, by mapper name.
+    pub async fn for_guild(&self, guild: GuildId) -> Result<Vec<Subscription>, Error> {
+        let rows: Vec<SubscriptionRow> = sqlx::query_as(
+            "SELECT guild_id, channel_id, mapper_id, mapper_name, created_at
+             FROM subscriptions WHERE guild_id = ?
+             ORDER BY mapper_name COLLATE NOCASE, channel_id",
+        )
+        .bind(db_id(guild.get()))
+        .fetch_all(self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(Subscription::from).collect())
+    }
+
+    /// Every subscription, grouped by mapper.
+    pub async fn all(&self) -> Result<Vec<Subscription>, Error> {
+        let rows: Vec<SubscriptionRow> = sqlx::query_as(
+            "SELECT guild_id, channel_id, mapper_id, mapper_name, created_at
+             FROM subscriptions ORDER BY mapper_id, channel_id",
+        )
+        .fetch_all(self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(Subscription::from).collect())
+    }
+
+    /// Every channel following a mapper.
+    pub async fn for_mapper(&self, mapper_id: u32) -> Result<Vec<Subscription>, Error> {
+        let rows: Vec<SubscriptionRow> = sqlx::query_as(
+            "SELECT guild_id, channel_id, mapper_id, mapper_name, created_at
+             FROM subscriptions WHERE mapper_id = ? ORDER BY channel_id",
+        )
+        .bind(i64::from(mapper_id))
+        .fetch_all(self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(Subscription::from).collect())
+    }
+
+    /// Marks a map as posted to a channel, returning `false` if it already was.
+    ///
+    /// Claim before posting, so two polls can never both post the same map.
+    /// A post that failed can be claimed again until it's given up on.
+    pub async fn claim_post(&self, channel: ChannelId, map_id: &str) -> Result<bool, Error> {
+        let res = sqlx::query(
+            "INSERT INTO posted_maps (channel_id, map_id, posted_at) VALUES (?, ?, ?)
+             ON CONFLICT (channel_id, map_id) DO UPDATE SET
+                 posted_at = excluded.posted_at,
+                 retry = 0
+             WHERE retry = 1",
+        )
+        .bind(db_id(channel.get()))
+        .bind(map_id.to_lowercase())
+        .bind(now())
+        .execute(self.pool)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    /// Counts a failed post after [`Subscriptions::claim_post`], so it's tried
+    /// again next time. After `max_attempts` failures it stays claimed and is
+    /// given up on, which is when this returns `false`.
+    pub async fn fail_post(
+        &self,
+        channel: ChannelId,
+        map_id: &str,
+        max_attempts: u32,
+    ) -> Result<bool, Error> {
+        let retry: Option<bool> = sqlx::query_scalar(
+            "UPDATE posted_maps SET failures = failures + 1, retry = failures + 1 < ?
+             WHERE channel_id = ? AND map_id = ?
+             RETURNING retry",
+        )
+        .bind(max_attempts)
+        .bind(db_id(channel.get()))
+        .bind(map_id.to_lowercase())
+        .fetch_optional(self.pool)
+        .await?;
+
+        Ok(retry.unwrap_or(false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Storage;
+    use super::*;
+
+    const CHANNEL: ChannelId = ChannelId::new(1);
+    const OTHER_CHANNEL: ChannelId = ChannelId::new(2);
+
+    #[tokio::test]
+    async fn maps_are_only_claimed_once() {
+        let storage = Storage::in_memory().await.unwrap();
+        let subscriptions = storage.subscriptions();
+
+        assert!(subscriptions.claim_post(CHANNEL, "25F").await.unwrap());
+        assert!(!subscriptions.claim_post(CHANNEL, "25f").await.unwrap());
+        // other channels get it posted too
+        assert!(subscriptions
+            .claim_post(OTHER_CHANNEL, "25f")
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn failed_posts_are_retried_a_few_times() {
+        let storage = Storage::in_memory().await.unwrap();
+        let subscriptions = storage.subscriptions();
+
+        assert!(subscriptions.claim_post(CHANNEL, "25f").await.unwrap());
+        assert!(subscriptions.fail_post(CHANNEL, "25f", 2).await.unwrap());
+
+        assert!(subscriptions.claim_post(CHANNEL, "25f").await.unwrap());
+        // claimed again, so a second poll doesn't post it alongside
+        assert!(!subscriptions.claim_post(CHANNEL, "25f").await.unwrap());
+        assert!(!subscriptions.fail_post(CHANNEL, "25f", 2).await.unwrap());
+
+        // given up on
+        assert!(!subscriptions.claim_post(CHANNEL, "25f").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn failing_an_unclaimed_post_does_nothing() {
+        let storage = Storage::in_memory().await.unwrap();
+        let subscriptions = storage.subscriptions();
+
+        assert!(!subscriptions.fail_post(CHANNEL, "25f", 3).await.unwrap());
+        assert!(subscriptions.claim_post(CHANNEL, "25f").await.unwrap());
+    }
+}
@@ -0,0 +1,254 @@
+use std::{sync::Arc, time::Duration};
+
+use beatsaver_api::models::map::Map;
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use poise::serenity_prelude::{
+    self as serenity, CreateAllowedMentions, CreateMessage, Http, HttpError,
+};
+use tokio::{sync::broadcast, time::MissedTickBehavior};
+
+use crate::{
+    clients::beatsaver::{feed::FeedEvent, BeatSaver},
+    core::{
+        settings::GuildSettings,
+        shutdown::Shutdown,
+        storage::{subscriptions::Subscription, Storage},
+    },
+    ui::mapembed::MapEmbed,
+    utils::text::sanitise,
+    Error,
+};
+
+/// Tries at posting a map to a channel before giving up on it.
+const MAX_POST_ATTEMPTS: u32 = 3;
+
+/// Posts followed mappers' new maps to the channels following them.
+///
+/// New maps usually come in live from the BeatSaver feed, with a slower poll
+/// to catch anything missed while it was disconnected. What's been posted is
+/// kept in storage, so nothing goes out twice, restarts included.
+pub struct SubscriptionPoller {
+    http: Arc<Http>,
+    /// Shared with the commands. Latest uploads are never cached, so polling
+    /// still sees new maps straight away.
+    beatsaver: Arc<BeatSaver>,
+    storage: Storage,
+    shutdown: Shutdown,
+    interval: Duration,
+    events: broadcast::Receiver<FeedEvent>,
+}
+
+impl SubscriptionPoller {
+    pub fn new(
+        http: Arc<Http>,
+        beatsaver: Arc<BeatSaver>,
+        storage: Storage,
+        shutdown: Shutdown,
+        interval: Duration,
+        events: broadcast::Receiver<FeedEvent>,
+    ) -> Self {
+        Self {
+            http,
+            beatsaver,
+            storage,
+            shutdown,
+            interval,
+            events,
+        }
+    }
+
+    /// Listens and polls until shutdown.
+    pub async fn run(mut self) {
+        info!("Checking subscriptions every {:?}", self.interval);
+
+        let mut ticker = tokio::time::interval(self.interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut feed_open = true;
+
+        loop {
+            let res = tokio::select! {
+                _ = self.shutdown.cancelled() => break,
+                _ = ticker.tick() => self.poll().await,
+                event = self.events.recv(), if feed_open => match event {
+                    Ok(FeedEvent::Published(map, published_at)) => {
+                        self.post_new(&map, published_at).await
+                    }
+                    Ok(_) => Ok(()),
+                    // the next poll picks up whatever got skipped
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Subscriptions missed {skipped} feed event(s)");
+                        Ok(())
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        feed_open = false;
+                        Ok(())
+                    }
+                },
+            };
+
+            if let Err(err) = res {
+                warn!("Couldn't check subscriptions: {err:#}");
+            }
+        }
+
+        debug!("Subscription poller stopped");
+    }
+
+    async fn poll(&self) -> Result<(), Error> {
+        let subscriptions = self.storage.subscriptions().all().await?;
+
+        // sorted by mapper, so each mapper only gets looked up once
+        for group in subscriptions.chunk_by(|a, b| a.mapper_id == b.mapper_id) {
+            if self.shutdown.is_triggered() {
+                break;
+            }
+
+            let mapper_id = group[0].mapper_id;
+            let uploads = match self.beatsaver.latest_maps(mapper_id).await {
+                Ok(uploads) => uploads,
+                Err(err) => {
+                    warn!("Couldn't check mapper {mapper_id} for new maps: {err:#}");
+                    continue;
+                }
+            };
+
+            for subscription in group {
+                // oldest first, so a burst of uploads is posted in order
+                let new_maps = uploads.iter().rev().filter(|upload| {
+                    !upload.map.versions.is_empty() && is_new(subscription, upload.published_at)
+                });
+                for upload in new_maps {
+                    self.post(subscription, &upload.map).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Posts a newly published map to every channel following its mapper.
+    async fn post_new(&self, map: &Map, published_at: DateTime<Utc>) -> Result<(), Error> {
+        let subscriptions = self
+            .storage
+            .subscriptions()
+            .for_mapper(map.uploader.id)
+            .await?;
+
+        for subscription in &subscriptions {
+            if is_new(subscription, published_at) {
+                self.post(subscription, map).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Posts a map to a subscribed channel, unless it's been posted there before.
+    async fn post(&self, subscription: &Subscription, map: &Map) -> Result<(), Error> {
+        let repo = self.storage.subscriptions();
+        let channel = subscription.channel_id;
+        if !repo.claim_post(channel, &map.id).await? {
+            return Ok(());
+        }
+
+        let settings = GuildSettings::load(&self.storage, Some(subscription.guild_id)).await?;
+        let map_embed = MapEmbed::new(map.clone()).with_settings(&settings);
+        let builder = CreateMessage::new()
+            .content(format!(
+                "New map from **{}**!",
+                sanitise(&map.uploader.name)
+            ))
+            .embed(map_embed.build_embeds()[0].clone())
+            .components(map_embed.build_embed_components())
+            .allowed_mentions(CreateAllowedMentions::new());
+
+        match channel.send_message(&self.http, builder).await {
+            Ok(_) => info!("Posted map {} to channel {channel}", map.id),
+            Err(err) if is_unknown_channel(&err) => {
+                let removed = repo.remove_channel(channel).await?;
+                info!("Channel {channel} is gone, dropped its {removed} subscription(s)");
+            }
+            Err(err) => {
+                // most likely missing permissions, which might get fixed
+                warn!("Couldn't post map {} to channel {channel}: {err}", map.id);
+                if !repo.fail_post(channel, &map.id, MAX_POST_ATTEMPTS).await? {
+                    info!(
+                        "Gave up on posting map {} to channel {channel} after {MAX_POST_ATTEMPTS} tries",
+                        map.id
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether a map went public after a channel subscribed, so it's new there.
+///
+/// Goes by when it was published rather than uploaded, since a map uploaded
+/// as a WIP before someone subscribed can still be published after.
+fn is_new(subscription: &Subscription, published_at: DateTime<Utc>) -> bool {
+    published_at.timestamp() >= subscription.created_at
+}
+
+pub(crate) fn is_unknown_channel(error: &serenity::Error) -> bool {
+    matches!(
+        error,
+        serenity::Error::Http(HttpError::UnsuccessfulRequest(res))
+            if res.status_code.as_u16() == 404
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use poise::serenity_prelude::{ChannelId, GuildId};
+
+    use super::*;
+    use crate::clients::beatsaver::first_published;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(secs, 0).unwrap()
+    }
+
+    fn subscribed_at(created_at: i64) -> Subscription {
+        Subscription {
+            guild_id: GuildId::new(1),
+            channel_id: ChannelId::new(2),
+            mapper_id: 3,
+            mapper_name: "Mapper".to_owned(),
+            created_at,
+        }
+    }
+
+    #[test]
+    fn uploaded_before_but_published_after_is_new() {
+        let subscription = subscribed_at(1000);
+
+        let published_at = first_published(1, at(500), Some(at(2000)));
+        assert!(is_new(&subscription, published_at));
+    }
+
+    #[test]
+    fn published_before_is_old() {
+        let subscription = subscribed_at(1000);
+
+        assert!(!is_new(
+            &subscription,
+            first_published(1, at(500), Some(at(600)))
+        ));
+        assert!(!is_new(&subscription, first_published(1, at(500), None)));
+        assert!(is_new(&subscription, first_published(1, at(1000), None)));
+    }
+
+    #[test]
+    fn new_versions_dont_make_old_maps_new() {
+        let subscription = subscribed_at(1000);
+
+        // publishing v2 moves the publish time, but the map's still old
+        let published_at = first_published(2, at(500), Some(at(2000)));
+        assert!(!is_new(&subscription, published_at));
+    }
+}
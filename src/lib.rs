@@ -1,16 +1,37 @@
-use beatsaver_api::client::BeatSaverClient;
-use regex::Regex;
-
-pub mod commands;
-pub mod core;
-pub mod ui;
-pub mod utils;
-pub mod api;
-
-pub struct Data {
-    pub beatsaver_client: BeatSaverClient,
-    pub bsr_link_regex: Regex,
-    pub hexstring_regex: Regex,
-} // User data, which is stored and accessible in all command invocations
-pub type Error = anyhow::Error;
-pub type Context<'a> = poise::Context<'a, Data, Error>;
+use std::sync::Arc;
+
+use crate::{
+    clients::{
+        beatleader::BeatLeader,
+        beatsaver::{feed::BeatSaverFeed, BeatSaver},
+        scoresaber::ScoreSaber,
+    },
+    core::{
+        config::Config, serverleaderboard::ServerLeaderboards, shutdown::Shutdown,
+        storage::Storage,
+    },
+    utils::discord::debounce::Debouncer,
+};
+
+pub mod clients;
+pub mod commands;
+pub mod core;
+pub mod ui;
+pub mod utils;
+pub mod api;
+
+pub struct Data {
+    pub config: Config,
+    pub shutdown: Shutdown,
+    /// Shared with the background tasks, so there's only one cache to manage.
+    pub beatsaver: Arc<BeatSaver>,
+    pub scoresaber: ScoreSaber,
+    pub beatleader: BeatLeader,
+    pub server_leaderboards: ServerLeaderboards,
+    pub debouncer: Debouncer,
+    pub storage: Storage,
+    /// Live BeatSaver map updates, for anything that wants to react to them.
+    pub feed: Arc<BeatSaverFeed>,
+} // User data, which is stored and accessible in all command invocations
+pub type Error = anyhow::Error;
+pub type Context<'a> = poise::Context<'a, Data, Error>;
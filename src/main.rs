@@ -1,24 +1,96 @@
+use std::process::ExitCode;
+
 use axum::{routing::get, Router};
 use dotenvy::dotenv;
-use poise::serenity_prelude::{self as serenity};
+use log::{error, info};
 
 use mafuyu::{
     api::{health, serve},
-    core::bot::Mafuyu,
+    core::{
+        bot::Mafuyu,
+        config::Config,
+        shutdown::{wait_for_signal, Shutdown},
+        storage::Storage,
+    },
 };
 use tokio::join;
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     dotenv().ok();
     env_logger::init();
 
-    let token = dotenvy::var("DISCORD_TOKEN").expect("Missing Discord token");
-    let intents = serenity::GatewayIntents::non_privileged();
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Error: {err:#}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let storage = match Storage::open(&config.storage).await {
+        Ok(storage) => storage,
+        Err(err) => {
+            eprintln!("Error: {err:#}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let port = config.http.port;
+    let shutdown_timeout = config.shutdown.timeout;
 
-    let mut mafuyu = Mafuyu::new(&token, intents).await;
+    let shutdown = Shutdown::new();
+    let mut mafuyu = Mafuyu::new(config, shutdown.clone(), storage.clone()).await;
+    let shard_manager = mafuyu.client.shard_manager.clone();
 
     let app: Router = Router::new().route("/health", get(health));
 
-    let _ = join!(serve(app, 5000), mafuyu.client.start());
+    // stops everything on a signal, or when one of the other halves dies
+    let stopper = {
+        let shutdown = shutdown.clone();
+        async move {
+            tokio::select! {
+                _ = wait_for_signal() => {},
+                _ = shutdown.cancelled() => {},
+            }
+
+            info!("Shutting down...");
+            shutdown.trigger();
+
+            let drained = shutdown.wait_idle(shutdown_timeout).await;
+            shard_manager.shutdown_all().await;
+
+            drained
+        }
+    };
+
+    let server = {
+        let shutdown = shutdown.clone();
+        async move {
+            let res = serve(app, port, shutdown.cancelled()).await;
+            if let Err(err) = &res {
+                error!("HTTP server failed: {err}");
+                shutdown.trigger();
+            }
+            res
+        }
+    };
+
+    let gateway = async {
+        let res = mafuyu.client.start().await;
+        if let Err(err) = &res {
+            error!("Gateway connection failed: {err}");
+        }
+        // if the gateway goes away first, take everything else down with it
+        shutdown.trigger();
+        res
+    };
+
+    let (server_res, gateway_res, drained) = join!(server, gateway, stopper);
+    storage.close().await;
+
+    if server_res.is_err() || gateway_res.is_err() || !drained {
+        ExitCode::FAILURE
+    } else {
+        info!("Goodbye!");
+        ExitCode::SUCCESS
+    }
 }
@@ -1 +1,11 @@
-pub mod mapembed;
+pub mod beatleaderprofile;
+pub mod difficulty;
+pub mod leaderboard;
+pub mod mapembed;
+pub mod maplist;
+pub mod mapperprofile;
+pub mod playlistview;
+pub mod scoresaberprofile;
+pub mod searchresults;
+pub mod serverleaderboard;
+pub mod snipe;
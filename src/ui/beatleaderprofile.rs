@@ -0,0 +1,123 @@
+use poise::serenity_prelude::{Colour, CreateEmbed, CreateEmbedFooter};
+
+use crate::{
+    clients::beatleader::{Player, Score},
+    ui::difficulty::difficulty_name,
+    utils::text::{country_flag, sanitise, truncate, EmbedBudget},
+};
+
+/// Builds the embed for a BeatLeader player, with their top and recent plays.
+pub fn build_player_embed(
+    player: &Player,
+    top_plays: &[Score],
+    recent_plays: &[Score],
+) -> CreateEmbed {
+    let mut budget = EmbedBudget::new();
+
+    let rank = |rank: u32| match rank {
+        0 => "Unranked".to_owned(),
+        rank => format!("#{rank}"),
+    };
+
+    let mut embed = CreateEmbed::new()
+        .title(budget.title(&format!(
+            "{} {}",
+            country_flag(&player.country),
+            sanitise(&player.name)
+        )))
+        .url(format!("https://beatleader.com/u/{}", player.id))
+        .thumbnail(&player.avatar)
+        .colour(Colour::from_rgb(235, 89, 160))
+        .footer(CreateEmbedFooter::new(
+            budget.footer(&format!("BeatLeader player {}", player.id)),
+        ));
+
+    let mut description = vec![];
+    if !player.clans.is_empty() {
+        let clans: Vec<String> = player
+            .clans
+            .iter()
+            .map(|clan| format!("`[{}]`", clan.tag.replace('`', "")))
+            .collect();
+        description.push(clans.join(" "));
+    }
+    if player.banned {
+        description.push("This player is banned.".to_owned());
+    } else if player.inactive {
+        description.push("This player is inactive, so they don't have a rank.".to_owned());
+    }
+    if !description.is_empty() {
+        embed = embed.description(budget.description(&description.join("\n")));
+    }
+
+    embed = embed.fields(
+        [
+            ("Global rank", rank(player.rank), true),
+            ("Country rank", rank(player.country_rank), true),
+            ("PP", format!("{:.2}pp", player.pp), true),
+        ]
+        .map(|(name, value, inline)| budget.field(name, &value, inline)),
+    );
+    if let Some(stats) = &player.score_stats {
+        embed = embed.fields(
+            [
+                (
+                    "Ranked accuracy",
+                    format!("{:.2}%", stats.average_ranked_accuracy * 100.0),
+                    true,
+                ),
+                ("Ranked plays", stats.ranked_play_count.to_string(), true),
+                ("Total plays", stats.total_play_count.to_string(), true),
+            ]
+            .map(|(name, value, inline)| budget.field(name, &value, inline)),
+        );
+    }
+
+    for (name, plays, recent) in [
+        ("Top plays", top_plays, false),
+        ("Recent plays", recent_plays, true),
+    ] {
+        if plays.is_empty() {
+            continue;
+        }
+        let plays: Vec<String> = plays
+            .iter()
+            .enumerate()
+            .map(|(idx, play)| format_play(idx + 1, play, recent))
+            .collect();
+        embed = embed.field(
+            budget.field_name(name),
+            budget.field_value(&plays.join("\n")),
+            false,
+        );
+    }
+
+    embed
+}
+
+/// One line for a play, with when it was set if `recent`.
+fn format_play(number: usize, play: &Score, recent: bool) -> String {
+    let song = match &play.leaderboard {
+        Some(leaderboard) => format!(
+            "[{}](https://beatleader.com/leaderboard/global/{}) ({})",
+            truncate(&sanitise(&leaderboard.song.name), 40, "..."),
+            leaderboard.id,
+            difficulty_name(&leaderboard.difficulty.difficulty_name)
+        ),
+        None => "Unknown map".to_owned(),
+    };
+    let pp = match play.pp > 0.0 {
+        true => format!(" · **{:.2}pp**", play.pp),
+        false => String::new(),
+    };
+    let when = match recent && play.timepost > 0 {
+        true => format!(" · <t:{}:R>", play.timepost),
+        false => String::new(),
+    };
+
+    format!(
+        "{number}. {song}{pp} · {:.2}% · #{}{when}",
+        play.accuracy * 100.0,
+        play.rank
+    )
+}
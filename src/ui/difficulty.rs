@@ -0,0 +1,200 @@
+use std::{
+    collections::HashSet,
+    sync::{LazyLock, Mutex},
+};
+
+use log::warn;
+use poise::serenity_prelude::Colour;
+
+const ASSETS_URL: &str = "https://raw.githubusercontent.com/mercurialworld/mafuyu/refs/heads/main/assets";
+
+/// How a difficulty is shown.
+pub struct DifficultyInfo {
+    /// The name BeatSaver uses.
+    pub name: &'static str,
+    pub display_name: &'static str,
+    /// For badges where the full name doesn't fit.
+    pub short_name: &'static str,
+    pub colour: (u8, u8, u8),
+}
+
+/// How a characteristic is shown.
+pub struct CharacteristicInfo {
+    /// Names BeatSaver (or the API crate) might use for it.
+    pub names: &'static [&'static str],
+    pub display_name: &'static str,
+    /// File in `assets/`, if there's an icon for it.
+    pub icon: Option<&'static str>,
+}
+
+const DIFFICULTIES: &[DifficultyInfo] = &[
+    DifficultyInfo {
+        name: "Easy",
+        display_name: "Easy",
+        short_name: "E",
+        colour: (129, 199, 132),
+    },
+    DifficultyInfo {
+        name: "Normal",
+        display_name: "Normal",
+        short_name: "N",
+        colour: (0, 238, 255),
+    },
+    DifficultyInfo {
+        name: "Hard",
+        display_name: "Hard",
+        short_name: "H",
+        colour: (255, 183, 77),
+    },
+    DifficultyInfo {
+        name: "Expert",
+        display_name: "Expert",
+        short_name: "X",
+        colour: (255, 149, 166),
+    },
+    DifficultyInfo {
+        name: "ExpertPlus",
+        display_name: "Expert+",
+        short_name: "X+",
+        colour: (166, 149, 255),
+    },
+];
+
+static UNKNOWN_DIFFICULTY: DifficultyInfo = DifficultyInfo {
+    name: "Unknown",
+    display_name: "Unknown",
+    short_name: "?",
+    colour: (68, 68, 68),
+};
+
+const CHARACTERISTICS: &[CharacteristicInfo] = &[
+    CharacteristicInfo {
+        names: &["Standard"],
+        display_name: "Standard",
+        icon: Some("Standard.png"),
+    },
+    CharacteristicInfo {
+        names: &["OneSaber"],
+        display_name: "One Saber",
+        icon: Some("OneSaber.png"),
+    },
+    CharacteristicInfo {
+        names: &["NoArrows"],
+        display_name: "No Arrows",
+        icon: Some("NoArrows.png"),
+    },
+    CharacteristicInfo {
+        names: &["90Degree", "Rotation90Degrees"],
+        display_name: "90 Degree",
+        icon: Some("90Degree.png"),
+    },
+    CharacteristicInfo {
+        names: &["360Degree", "Rotation360Degrees"],
+        display_name: "360 Degree",
+        icon: Some("360Degree.png"),
+    },
+    CharacteristicInfo {
+        names: &["Lightshow"],
+        display_name: "Lightshow",
+        icon: Some("Lightshow.png"),
+    },
+    CharacteristicInfo {
+        names: &["Lawless"],
+        display_name: "Lawless",
+        icon: Some("Lawless.png"),
+    },
+    CharacteristicInfo {
+        names: &["Legacy"],
+        display_name: "Legacy",
+        icon: None,
+    },
+];
+
+static UNKNOWN_CHARACTERISTIC: CharacteristicInfo = CharacteristicInfo {
+    names: &[],
+    display_name: "Unknown",
+    icon: None,
+};
+
+/// Names we've already warned about, so the logs don't fill up.
+static WARNED: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(Default::default);
+
+fn warn_once(kind: &str, name: &str) {
+    if WARNED.lock().unwrap().insert(format!("{kind}:{name}")) {
+        warn!("Unknown {kind} {name:?}, showing it generically");
+    }
+}
+
+impl DifficultyInfo {
+    /// Looks up a difficulty, falling back to a generic entry.
+    pub fn get(name: &str) -> &'static Self {
+        DIFFICULTIES
+            .iter()
+            .find(|info| info.name.eq_ignore_ascii_case(name))
+            .unwrap_or_else(|| {
+                warn_once("difficulty", name);
+                &UNKNOWN_DIFFICULTY
+            })
+    }
+
+    /// Every known difficulty, easiest first.
+    pub fn all() -> &'static [Self] {
+        DIFFICULTIES
+    }
+
+    /// Looks up a difficulty by any name someone might type, like `Expert+`
+    /// or `X+`.
+    pub fn find(name: &str) -> Option<&'static Self> {
+        DIFFICULTIES.iter().find(|info| {
+            [info.name, info.display_name, info.short_name]
+                .iter()
+                .any(|n| n.eq_ignore_ascii_case(name))
+        })
+    }
+
+    pub fn colour(&self) -> Colour {
+        Colour::from_rgb(self.colour.0, self.colour.1, self.colour.2)
+    }
+}
+
+impl CharacteristicInfo {
+    /// Looks up a characteristic, falling back to a generic entry.
+    pub fn get(name: &str) -> &'static Self {
+        CHARACTERISTICS
+            .iter()
+            .find(|info| info.names.iter().any(|n| n.eq_ignore_ascii_case(name)))
+            .unwrap_or_else(|| {
+                warn_once("characteristic", name);
+                &UNKNOWN_CHARACTERISTIC
+            })
+    }
+
+    /// Looks up a characteristic by any name someone might type, like
+    /// `OneSaber` or `One Saber`.
+    pub fn find(name: &str) -> Option<&'static Self> {
+        CHARACTERISTICS.iter().find(|info| {
+            info.display_name.eq_ignore_ascii_case(name)
+                || info.names.iter().any(|n| n.eq_ignore_ascii_case(name))
+        })
+    }
+
+    pub fn icon_url(&self) -> Option<String> {
+        self.icon.map(|icon| format!("{ASSETS_URL}/{icon}"))
+    }
+}
+
+/// The display name of a difficulty, keeping unknown names as they are.
+pub fn difficulty_name(name: &str) -> &str {
+    match DifficultyInfo::get(name) {
+        info if std::ptr::eq(info, &UNKNOWN_DIFFICULTY) => name,
+        info => info.display_name,
+    }
+}
+
+/// The display name of a characteristic, keeping unknown names as they are.
+pub fn characteristic_name(name: &str) -> &str {
+    match CharacteristicInfo::get(name) {
+        info if std::ptr::eq(info, &UNKNOWN_CHARACTERISTIC) => name,
+        info => info.display_name,
+    }
+}
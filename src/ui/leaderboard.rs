@@ -0,0 +1,319 @@
+use anyhow::anyhow;
+use log::info;
+use poise::serenity_prelude::{
+    self as serenity, ButtonStyle, ComponentInteraction, CreateActionRow, CreateButton,
+    CreateEmbed, CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseMessage,
+};
+
+use crate::{
+    clients::{
+        beatleader::{self, LeaderboardStatus},
+        scoresaber,
+    },
+    core::{settings::GuildSettings, storage::linked_accounts::Service},
+    ui::difficulty::{characteristic_name, difficulty_name, DifficultyInfo},
+    utils::text::{country_flag, sanitise, EmbedBudget},
+    Data, Error,
+};
+
+/// Custom ID prefix for leaderboard buttons,
+/// `leaderboard:<source>:<hash>:<characteristic>:<difficulty>[:<page>]`.
+///
+/// Without a page the leaderboard opens as a new message, with one it replaces
+/// the message the button is on.
+pub const COMPONENT_PREFIX: &str = "leaderboard";
+
+/// Where a leaderboard comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    ScoreSaber,
+    BeatLeader,
+}
+
+impl Source {
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::ScoreSaber => "ss",
+            Self::BeatLeader => "bl",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "ss" => Some(Self::ScoreSaber),
+            "bl" => Some(Self::BeatLeader),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::ScoreSaber => "ScoreSaber",
+            Self::BeatLeader => "BeatLeader",
+        }
+    }
+}
+
+impl From<Source> for Service {
+    fn from(source: Source) -> Self {
+        match source {
+            Source::ScoreSaber => Self::ScoreSaber,
+            Source::BeatLeader => Self::BeatLeader,
+        }
+    }
+}
+
+/// A button that opens the top scores for one difficulty of a map version.
+pub fn build_button(
+    source: Source,
+    hash: &str,
+    characteristic: &str,
+    difficulty: &str,
+) -> CreateButton {
+    CreateButton::new(format!(
+        "{COMPONENT_PREFIX}:{}:{hash}:{characteristic}:{difficulty}",
+        source.id()
+    ))
+    .label(format!("{} leaderboard", source.name()))
+    .emoji('🏆')
+    .style(ButtonStyle::Secondary)
+}
+
+/// Builds the embed for the top of a ScoreSaber leaderboard.
+pub fn build_scoresaber_embed(leaderboard: &scoresaber::Leaderboard) -> CreateEmbed {
+    let mut budget = EmbedBudget::new();
+    let info = &leaderboard.info;
+    let characteristic = info
+        .difficulty
+        .game_mode
+        .strip_prefix("Solo")
+        .unwrap_or(&info.difficulty.game_mode);
+    let difficulty = info.difficulty.name();
+
+    let scores: Vec<String> = leaderboard
+        .scores
+        .iter()
+        .map(|score| {
+            let player = match &score.leaderboard_player_info {
+                Some(player) => format!(
+                    "{} [{}](https://scoresaber.com/u/{})",
+                    country_flag(&player.country),
+                    sanitise(&player.name),
+                    player.id
+                ),
+                None => "Someone".to_owned(),
+            };
+            let accuracy = match score.accuracy(info.max_score) {
+                Some(accuracy) => format!(" · {:.2}%", accuracy * 100.0),
+                None => String::new(),
+            };
+            let pp = match score.pp > 0.0 {
+                true => format!(" · **{:.2}pp**", score.pp),
+                false => String::new(),
+            };
+            let misses = match score.full_combo {
+                true => "FC".to_owned(),
+                false => format!("{}x", score.missed_notes + score.bad_cuts),
+            };
+            format!("**#{}** {player}{accuracy}{pp} · {misses}", score.rank)
+        })
+        .collect();
+
+    let description = match scores.is_empty() {
+        true => "Nobody's set a score yet.".to_owned(),
+        false => scores.join("\n"),
+    };
+    let footer = match info.ranked {
+        true => format!("Ranked · ★ {:.2}", info.stars),
+        false => "Unranked".to_owned(),
+    };
+
+    CreateEmbed::new()
+        .title(budget.title(&format!(
+            "{} ({} {})",
+            sanitise(&info.song_name),
+            characteristic_name(characteristic),
+            difficulty_name(difficulty)
+        )))
+        .url(format!("https://scoresaber.com/leaderboard/{}", info.id))
+        .description(budget.description(&description))
+        .colour(DifficultyInfo::get(difficulty).colour())
+        .footer(CreateEmbedFooter::new(
+            budget.footer(&format!("ScoreSaber · {footer}")),
+        ))
+}
+
+/// What to call a BeatLeader status, with an emoji so it stands out.
+pub fn status_label(status: LeaderboardStatus) -> String {
+    match status {
+        LeaderboardStatus::Unranked => "Unranked".to_owned(),
+        LeaderboardStatus::Nominated => "📝 Nominated".to_owned(),
+        LeaderboardStatus::Qualified => "🔎 Qualified".to_owned(),
+        LeaderboardStatus::Ranked => "⭐ Ranked".to_owned(),
+        LeaderboardStatus::Unrankable => "Unrankable".to_owned(),
+        LeaderboardStatus::Outdated => "Outdated".to_owned(),
+        LeaderboardStatus::Event => "🎉 Event".to_owned(),
+        LeaderboardStatus::Ost => "OST".to_owned(),
+        LeaderboardStatus::Loved => "💖 Loved".to_owned(),
+        LeaderboardStatus::Other(status) => format!("Status {status}"),
+    }
+}
+
+/// Builds the embed for one page (starting at 0) of a BeatLeader leaderboard.
+pub fn build_beatleader_embed(leaderboard: &beatleader::Leaderboard, page: u32) -> CreateEmbed {
+    let mut budget = EmbedBudget::new();
+    let difficulty = &leaderboard.difficulty;
+
+    let scores: Vec<String> = leaderboard
+        .scores
+        .iter()
+        .map(|score| {
+            let player = match &score.player {
+                Some(player) => format!(
+                    "{} [{}](https://beatleader.com/u/{})",
+                    country_flag(&player.country),
+                    sanitise(&player.name),
+                    player.id
+                ),
+                None => "Someone".to_owned(),
+            };
+            let pp = match score.pp > 0.0 {
+                true => format!(" · **{:.2}pp**", score.pp),
+                false => String::new(),
+            };
+            let misses = match score.full_combo {
+                true => "FC".to_owned(),
+                false => format!("{}x", score.missed_notes + score.bad_cuts),
+            };
+            format!(
+                "**#{}** {player} · {:.2}%{pp} · {misses}",
+                score.rank,
+                score.accuracy * 100.0
+            )
+        })
+        .collect();
+
+    let mut status = format!("**{}**", status_label(difficulty.status));
+    if let Some(stars) = difficulty.stars.filter(|_| difficulty.status.has_pp()) {
+        status.push_str(&format!(" · ★ {stars:.2}"));
+    }
+    let description = match scores.is_empty() {
+        true => format!("{status}\n\nNobody's set a score yet."),
+        false => format!("{status}\n\n{}", scores.join("\n")),
+    };
+
+    CreateEmbed::new()
+        .title(budget.title(&format!(
+            "{} ({} {})",
+            sanitise(&leaderboard.song.name),
+            characteristic_name(&difficulty.mode_name),
+            difficulty_name(&difficulty.difficulty_name)
+        )))
+        .url(format!(
+            "https://beatleader.com/leaderboard/global/{}/{}",
+            leaderboard.id,
+            page + 1
+        ))
+        .description(budget.description(&description))
+        .colour(DifficultyInfo::get(&difficulty.difficulty_name).colour())
+        .footer(CreateEmbedFooter::new(budget.footer(&format!(
+            "BeatLeader · Page {} of {}",
+            page + 1,
+            leaderboard.page_count()
+        ))))
+}
+
+/// Previous and next buttons for a paged leaderboard.
+fn build_page_buttons(
+    source: Source,
+    hash: &str,
+    characteristic: &str,
+    difficulty: &str,
+    page: u32,
+    page_count: u32,
+) -> CreateActionRow {
+    let id = |page: u32| {
+        format!(
+            "{COMPONENT_PREFIX}:{}:{hash}:{characteristic}:{difficulty}:{page}",
+            source.id()
+        )
+    };
+
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(id(page.saturating_sub(1)))
+            .label("Previous")
+            .emoji('◀')
+            .disabled(page == 0),
+        CreateButton::new(id(page + 1))
+            .label("Next")
+            .emoji('▶')
+            .disabled(page + 1 >= page_count),
+    ])
+}
+
+/// Shows a leaderboard as a new message, leaving the map embed alone, or
+/// turns the page of one that's already up.
+#[allow(clippy::too_many_arguments)]
+pub async fn handle_interaction(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &ComponentInteraction,
+    source: &str,
+    hash: &str,
+    characteristic: &str,
+    difficulty: &str,
+    page: Option<&str>,
+) -> Result<(), Error> {
+    let source =
+        Source::from_id(source).ok_or_else(|| anyhow!("unknown leaderboard source {source}"))?;
+    let turning_page = page.is_some();
+    let page = page
+        .map(|page| page.parse::<u32>())
+        .transpose()
+        .map_err(|_| anyhow!("malformed custom ID {}", mci.data.custom_id))?
+        .unwrap_or(0);
+
+    info!(
+        "{:?} leaderboard for {} {} {} (page {}) requested by {}",
+        source, hash, characteristic, difficulty, page, mci.user.name
+    );
+
+    let mut builder = CreateInteractionResponseMessage::new();
+    match source {
+        Source::ScoreSaber => {
+            let leaderboard = data
+                .scoresaber
+                .leaderboard(hash, characteristic, difficulty)
+                .await?;
+            builder = builder.embed(build_scoresaber_embed(&leaderboard));
+        }
+        Source::BeatLeader => {
+            let leaderboard = data
+                .beatleader
+                .leaderboard(hash, characteristic, difficulty, page)
+                .await?;
+            builder = builder.embed(build_beatleader_embed(&leaderboard, page));
+            if leaderboard.page_count() > 1 {
+                builder = builder.components(vec![build_page_buttons(
+                    source,
+                    hash,
+                    characteristic,
+                    difficulty,
+                    page,
+                    leaderboard.page_count(),
+                )]);
+            }
+        }
+    }
+
+    let response = match turning_page {
+        true => CreateInteractionResponse::UpdateMessage(builder),
+        false => {
+            let settings = GuildSettings::load(&data.storage, mci.guild_id).await?;
+            CreateInteractionResponse::Message(builder.ephemeral(settings.ephemeral()))
+        }
+    };
+    mci.create_response(ctx, response).await?;
+
+    Ok(())
+}
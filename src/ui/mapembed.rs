@@ -1,244 +1,641 @@
-use beatsaver_api::models::{
-    enums::Characteristic,
-    map::{Map, MapDifficulty},
-};
-use poise::serenity_prelude::{
-    Colour, CreateActionRow, CreateButton, CreateEmbed, CreateEmbedFooter, CreateSelectMenu,
-    CreateSelectMenuKind, CreateSelectMenuOption,
-};
-
-use crate::utils::truncate::truncate_string;
-
-pub struct MapEmbed {
-    pub map: Map,
-    pub selected_index: usize,
-    pub options: Vec<CreateSelectMenuOption>,
-}
-
-/// Creates a list of the available embeds.
-fn get_map_diffs_list(map: &Map) -> Vec<CreateSelectMenuOption> {
-    let mut map_diffs: Vec<CreateSelectMenuOption> =
-        vec![CreateSelectMenuOption::new("Metadata", "0").default_selection(true)];
-
-    map_diffs.extend(map.versions[0].diffs.iter().enumerate().map(|(idx, diff)| {
-        CreateSelectMenuOption::new(
-            format!("{} {}", diff.characteristic, diff.difficulty),
-            (idx + 1).to_string(),
-        )
-    }));
-
-    // info!("Difficulties: {:?}", map_diffs);
-
-    map_diffs
-}
-
-/// Formats a duration in seconds to (optional) hours, minutes, and seconds.
-fn format_time(duration: i32) -> String {
-    let seconds = duration % 60;
-    let minutes = (duration / 60) % 60;
-    let hours = (duration / 60) / 60;
-
-    if hours > 0 {
-        format!("{hours}:{minutes:0>2}:{seconds:0>2}")
-    } else {
-        format!("{minutes}:{seconds:0>2}")
-    }
-}
-
-impl MapEmbed {
-    pub fn new(map: Map) -> Self {
-        let options = get_map_diffs_list(&map);
-
-        Self {
-            map,
-            selected_index: 0, // Metadata
-            options,
-        }
-    }
-
-    pub fn set_index(&mut self, new_index: &str) {
-        self.selected_index = str::parse::<usize>(new_index).unwrap_or_default();
-        self.set_new_default();
-    }
-
-    pub fn build_embeds(&self) -> Vec<CreateEmbed> {
-        match self.selected_index {
-            0 => vec![self.create_map_metadata_embed()],
-            idx => vec![
-                self.create_map_metadata_embed(),
-                self.create_map_diff_embed(&self.map.versions[0].diffs[idx - 1]),
-            ],
-        }
-    }
-
-    pub fn build_embed_components(&mut self) -> Vec<CreateActionRow> {
-        vec![
-            CreateActionRow::SelectMenu(
-                CreateSelectMenu::new(
-                    "diffsel",
-                    CreateSelectMenuKind::String {
-                        options: self.options.clone(),
-                    },
-                )
-                .placeholder("Select Difficulty"),
-            ),
-            CreateActionRow::Buttons(vec![
-                CreateButton::new_link(&self.map.versions[0].download_url)
-                    .label("Download")
-                    .emoji('⬇'),
-                CreateButton::new_link(format!(
-                    "https://allpoland.github.io/ArcViewer/?id={}",
-                    self.map.id
-                ))
-                .label("ArcViewer")
-                .emoji('⏯'),
-                CreateButton::new_link(format!("https://chroviewer.com/?map={}", self.map.id))
-                    .label("ChroViewer")
-                    .emoji('⏯'),
-            ]),
-        ]
-    }
-
-    fn set_new_default(&mut self) {
-        for idx in 0..self.options.len() {
-            self.options[idx] = self.options[idx]
-                .clone()
-                .default_selection(idx == self.selected_index);
-        }
-    }
-
-    // MARK: Embed creator functions
-
-    /// Creates the general map info embed.
-    fn create_base_embed(&self) -> CreateEmbed {
-        let embed: CreateEmbed = CreateEmbed::new()
-            .title(&self.map.name)
-            .url(format!("https://beatsaver.com/maps/{}", self.map.id))
-            .description(truncate_string(
-                self.map.description.clone(),
-                2048, // !bsr 45001 is 4100 characters
-                "...".to_string(),
-            ))
-            .thumbnail(&self.map.versions[0].cover_url)
-            .footer(CreateEmbedFooter::new(format!("!bsr {}", self.map.id)))
-            .timestamp(self.map.uploaded);
-
-        embed
-    }
-
-    /// Creates the general metadata of the map embed.
-    fn create_map_metadata_embed(&self) -> CreateEmbed {
-        let embed = self.create_base_embed();
-        embed
-            .field(
-                "Mapper(s)",
-                self.map
-                    .metadata
-                    .level_author_name
-                    .clone()
-                    .unwrap_or(self.map.uploader.name.clone()),
-                false,
-            )
-            .field(
-                "Artist(s)",
-                self.map
-                    .metadata
-                    .song_author_name
-                    .clone()
-                    .unwrap_or("Unknown".to_owned()),
-                false,
-            )
-            .fields([
-                ("BPM", &self.map.metadata.bpm.to_string(), true),
-                ("Length", &format_time(self.map.metadata.duration), true),
-                (
-                    "Rating",
-                    &format!(
-                        "▲ {} / ▼ {} ({:.2}%)",
-                        self.map.stats.upvotes,
-                        self.map.stats.downvotes,
-                        self.map.stats.score * 100.0
-                    ),
-                    true,
-                ),
-            ])
-            .colour(self.get_embed_colour())
-    }
-
-    /// Creates the embed representing data for one difficulty.
-    fn create_map_diff_embed(&self, diff: &MapDifficulty) -> CreateEmbed {
-        let mut embed = CreateEmbed::new()
-            .title(if let Some(label) = &diff.label {
-                label
-            } else {
-                &diff.difficulty
-            })
-            .thumbnail(self.get_characteristic_thumbnail(&diff.characteristic));
-
-        embed = embed.field(
-            "Characteristic/Difficulty",
-            format!("{} {}", diff.characteristic, diff.difficulty),
-            false,
-        );
-
-        if let Some(scoresaber_stars) = diff.ss_stars {
-            embed = embed.field("ScoreSaber Stars", format!("{scoresaber_stars:.2}"), true);
-        }
-        if let Some(beatleader_stars) = diff.bl_stars {
-            embed = embed.field("BeatLeader Stars", format!("{beatleader_stars:.2}"), true);
-        }
-
-        embed
-            .field("", "", false)
-            .fields(vec![
-                ("Notes", diff.notes.to_string(), true),
-                ("Bombs", diff.bombs.to_string(), true),
-                ("Walls", diff.obstacles.to_string(), true),
-                ("NJS", diff.njs.to_string(), true),
-                ("NPS", diff.nps.to_string(), true),
-                ("Lights", diff.events.to_string(), true),
-            ])
-            .colour(self.get_diff_colour(&diff.difficulty))
-    }
-
-    // MARK: Embed colour functions
-
-    /// Adds a colour to the map metadata embed.
-    fn get_embed_colour(&self) -> Colour {
-        if self.map.ss_ranked || self.map.bl_ranked {
-            Colour::from_rgb(243, 156, 18)
-        } else if self.map.curated_at.is_some() {
-            Colour::from_rgb(0, 188, 140)
-        } else if self.map.uploader.verified_mapper {
-            Colour::from_rgb(118, 70, 175)
-        } else {
-            Colour::from_rgb(68, 68, 68)
-        }
-    }
-
-    /// Adds a difficulty colour to the map difficulty embed.
-    fn get_diff_colour(&self, diff_name: &str) -> Colour {
-        match diff_name {
-            "ExpertPlus" => Colour::from_rgb(166, 149, 255),
-            "Expert" => Colour::from_rgb(255, 149, 166),
-            "Hard" => Colour::from_rgb(255, 183, 77),
-            "Normal" => Colour::from_rgb(0, 238, 255),
-            "Easy" => Colour::from_rgb(129, 199, 132),
-            _ => unreachable!(),
-        }
-    }
-
-    /// Gets a thumbnail of the difficulty's characteristic.
-    fn get_characteristic_thumbnail(&self, characteristic: &Characteristic) -> String {
-        match characteristic {
-            Characteristic::Standard => "https://raw.githubusercontent.com/mercurialworld/mafuyu/refs/heads/main/assets/Standard.png".into(),
-            Characteristic::OneSaber => "https://raw.githubusercontent.com/mercurialworld/mafuyu/refs/heads/main/assets/OneSaber.png".into(),
-            Characteristic::NoArrows => "https://raw.githubusercontent.com/mercurialworld/mafuyu/refs/heads/main/assets/NoArrows.png".into(),
-            Characteristic::Rotation90Degrees => "https://raw.githubusercontent.com/mercurialworld/mafuyu/refs/heads/main/assets/90Degree.png".into(),
-            Characteristic::Rotation360Degrees => "https://raw.githubusercontent.com/mercurialworld/mafuyu/refs/heads/main/assets/360Degree.png".into(),
-            Characteristic::Lightshow => "https://raw.githubusercontent.com/mercurialworld/mafuyu/refs/heads/main/assets/Lightshow.png".into(),
-            Characteristic::Lawless => "https://raw.githubusercontent.com/mercurialworld/mafuyu/refs/heads/main/assets/Lawless.png".into(),
-            Characteristic::Legacy => "https://raw.githubusercontent.com/mercurialworld/mafuyu/refs/heads/main/assets/Legacy.png".into(),
-        }
-    }
-}
+use beatsaver_api::models::{
+    enums::Characteristic,
+    map::{Map, MapDifficulty, MapVersion},
+};
+use log::info;
+use poise::serenity_prelude::{
+    self as serenity, Colour, ComponentInteraction, ComponentInteractionDataKind,
+    CreateActionRow, CreateButton, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse,
+    CreateInteractionResponseMessage, CreateSelectMenu, CreateSelectMenuKind,
+    CreateSelectMenuOption,
+};
+
+use crate::{
+    core::settings::{self, GuildSettings},
+    ui::{
+        difficulty::{characteristic_name, difficulty_name, CharacteristicInfo, DifficultyInfo},
+        leaderboard::{self, Source},
+        serverleaderboard,
+    },
+    utils::text::{limits, sanitise, truncate, EmbedBudget},
+    Data, Error,
+};
+
+/// Custom ID prefix for map embed components, `mapembed:<map id>:<menu>[:<hash>]`.
+pub const COMPONENT_PREFIX: &str = "mapembed";
+
+/// Characteristic menu value for going back to the metadata.
+const METADATA_VALUE: &str = "meta";
+
+/// Discord won't take more options than this in one select menu.
+const MAX_SELECT_OPTIONS: usize = 25;
+
+/// Shortest hash prefix accepted when picking a version.
+const MIN_HASH_PREFIX: usize = 4;
+
+pub struct MapEmbed {
+    pub map: Map,
+    /// The characteristic whose difficulties are listed, if one was picked.
+    pub selected_characteristic: Option<String>,
+    /// 0 for the metadata, otherwise the position of the difficulty plus one.
+    pub selected_index: usize,
+    /// Position in `map.versions`, 0 being the latest.
+    pub selected_version: usize,
+    /// Leaves out the description and the less important fields.
+    pub compact: bool,
+    /// Which leaderboard the difficulty pages link to.
+    pub leaderboard: settings::Leaderboard,
+}
+
+/// Lists the characteristics of a version, in the order they show up.
+fn get_characteristics(version: &MapVersion) -> Vec<String> {
+    let mut characteristics: Vec<String> = vec![];
+
+    for diff in &version.diffs {
+        let characteristic = diff.characteristic.to_string();
+        if !characteristics.contains(&characteristic) {
+            characteristics.push(characteristic);
+        }
+    }
+
+    characteristics
+}
+
+/// Formats a duration in seconds to (optional) hours, minutes, and seconds.
+pub fn format_time(duration: i32) -> String {
+    let seconds = duration % 60;
+    let minutes = (duration / 60) % 60;
+    let hours = (duration / 60) / 60;
+
+    if hours > 0 {
+        format!("{hours}:{minutes:0>2}:{seconds:0>2}")
+    } else {
+        format!("{minutes}:{seconds:0>2}")
+    }
+}
+
+impl MapEmbed {
+    pub fn new(map: Map) -> Self {
+        Self {
+            map,
+            selected_characteristic: None,
+            selected_index: 0, // Metadata
+            selected_version: 0, // Latest
+            compact: false,
+            leaderboard: settings::Leaderboard::default(),
+        }
+    }
+
+    /// Uses the layout and leaderboard a server asked for.
+    pub fn with_settings(mut self, settings: &GuildSettings) -> Self {
+        self.compact = settings.compact();
+        self.leaderboard = settings.leaderboard;
+        self
+    }
+
+    /// Shows the version with this hash, if the map has one.
+    pub fn with_hash(mut self, hash: &str) -> Self {
+        self.set_version(hash);
+        self
+    }
+
+    /// The version being shown, if the map has any left.
+    fn version(&self) -> Option<&MapVersion> {
+        self.map.versions.get(self.selected_version)
+    }
+
+    /// The difficulty being shown, if any.
+    fn selected_diff(&self) -> Option<&MapDifficulty> {
+        self.selected_index
+            .checked_sub(1)
+            .and_then(|idx| self.version()?.diffs.get(idx))
+    }
+
+    /// Selects a version by number (1 being the first upload) or hash prefix,
+    /// and goes back to its metadata.
+    ///
+    /// Returns `false` and leaves the selection alone if nothing matches.
+    pub fn set_version(&mut self, version: &str) -> bool {
+        let version = version.trim().to_lowercase();
+        let count = self.map.versions.len();
+
+        let by_number = version
+            .strip_prefix('v')
+            .unwrap_or(&version)
+            .parse::<usize>()
+            .ok()
+            .filter(|number| (1..=count).contains(number))
+            .map(|number| count - number);
+        let by_hash = || {
+            (version.len() >= MIN_HASH_PREFIX)
+                .then(|| {
+                    self.map
+                        .versions
+                        .iter()
+                        .position(|v| v.hash.to_lowercase().starts_with(&version))
+                })
+                .flatten()
+        };
+
+        match by_number.or_else(by_hash) {
+            Some(idx) => {
+                self.selected_version = idx;
+                self.selected_characteristic = None;
+                self.selected_index = 0;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Selects a difficulty by position (plus one), or the metadata with 0.
+    pub fn set_index(&mut self, new_index: &str) {
+        let selected = str::parse::<usize>(new_index).ok().and_then(|idx| {
+            let diff = self.version()?.diffs.get(idx.checked_sub(1)?)?;
+            Some((idx, diff.characteristic.to_string()))
+        });
+
+        match selected {
+            Some((idx, characteristic)) => {
+                self.selected_index = idx;
+                self.selected_characteristic = Some(characteristic);
+            }
+            None => {
+                self.selected_index = 0;
+                self.selected_characteristic = None;
+            }
+        }
+    }
+
+    /// Selects a characteristic and shows its first difficulty, or goes back
+    /// to the metadata.
+    pub fn set_characteristic(&mut self, characteristic: &str) {
+        let first_diff = self.version().and_then(|version| {
+            version
+                .diffs
+                .iter()
+                .position(|diff| diff.characteristic.to_string() == characteristic)
+        });
+
+        match first_diff {
+            Some(idx) if characteristic != METADATA_VALUE => {
+                self.selected_characteristic = Some(characteristic.to_owned());
+                self.selected_index = idx + 1;
+            }
+            _ => {
+                self.selected_characteristic = None;
+                self.selected_index = 0;
+            }
+        }
+    }
+
+    pub fn build_embeds(&self) -> Vec<CreateEmbed> {
+        // the embeds share one message, so they share the character limit too
+        let mut budget = EmbedBudget::new();
+
+        match self.selected_diff() {
+            None => vec![self.create_map_metadata_embed(&mut budget)],
+            Some(diff) => vec![
+                self.create_map_metadata_embed(&mut budget),
+                self.create_map_diff_embed(diff, &mut budget),
+            ],
+        }
+    }
+
+    pub fn build_embed_components(&self) -> Vec<CreateActionRow> {
+        let mut rows = vec![CreateActionRow::SelectMenu(
+            CreateSelectMenu::new(
+                self.component_id("char"),
+                CreateSelectMenuKind::String {
+                    options: self.get_characteristic_options(),
+                },
+            )
+            .placeholder("Select Characteristic"),
+        )];
+
+        if let Some(characteristic) = &self.selected_characteristic {
+            rows.push(CreateActionRow::SelectMenu(
+                CreateSelectMenu::new(
+                    self.component_id("diff"),
+                    CreateSelectMenuKind::String {
+                        options: self.get_diff_options(characteristic),
+                    },
+                )
+                .placeholder("Select Difficulty"),
+            ));
+        }
+
+        if self.map.versions.len() > 1 {
+            rows.push(CreateActionRow::SelectMenu(
+                CreateSelectMenu::new(
+                    self.component_id("ver"),
+                    CreateSelectMenuKind::String {
+                        options: self.get_version_options(),
+                    },
+                )
+                .placeholder("Select Version"),
+            ));
+        }
+
+        let mut buttons = vec![];
+        if let Some(version) = self.version() {
+            buttons.push(
+                CreateButton::new_link(&version.download_url)
+                    .label("Download")
+                    .emoji('⬇'),
+            );
+        }
+
+        // the viewers only know about the latest version by ID
+        if self.selected_version == 0 {
+            buttons.extend([
+                CreateButton::new_link(format!(
+                    "https://allpoland.github.io/ArcViewer/?id={}",
+                    self.map.id
+                ))
+                .label("ArcViewer")
+                .emoji('⏯'),
+                CreateButton::new_link(format!("https://chroviewer.com/?map={}", self.map.id))
+                    .label("ChroViewer")
+                    .emoji('⏯'),
+            ]);
+        } else if let Some(version) = self.version() {
+            buttons.push(
+                CreateButton::new_link(format!(
+                    "https://allpoland.github.io/ArcViewer/?url={}",
+                    version.download_url
+                ))
+                .label("ArcViewer")
+                .emoji('⏯'),
+            );
+        }
+
+        if let (Some(version), Some(diff)) = (self.version(), self.selected_diff()) {
+            // only ranked difficulties have a ScoreSaber leaderboard, BeatLeader has them all
+            let source = match self.leaderboard {
+                settings::Leaderboard::ScoreSaber if diff.ss_stars.is_some() => Source::ScoreSaber,
+                _ => Source::BeatLeader,
+            };
+            let characteristic = diff.characteristic.to_string();
+            buttons.push(leaderboard::build_button(
+                source,
+                &version.hash,
+                &characteristic,
+                &diff.difficulty,
+            ));
+            buttons.push(serverleaderboard::build_button(
+                source,
+                &version.hash,
+                &characteristic,
+                &diff.difficulty,
+            ));
+        }
+
+        rows.push(CreateActionRow::Buttons(buttons));
+
+        rows
+    }
+
+    /// Builds a custom ID that carries everything needed to rebuild this embed.
+    fn component_id(&self, menu: &str) -> String {
+        match self.version() {
+            Some(version) if self.selected_version != 0 => {
+                format!("{COMPONENT_PREFIX}:{}:{menu}:{}", self.map.id, version.hash)
+            }
+            _ => format!("{COMPONENT_PREFIX}:{}:{menu}", self.map.id),
+        }
+    }
+
+    // MARK: Select menu options
+
+    /// Creates the metadata option plus one option per characteristic.
+    fn get_characteristic_options(&self) -> Vec<CreateSelectMenuOption> {
+        let mut options = vec![CreateSelectMenuOption::new("Metadata", METADATA_VALUE)
+            .default_selection(self.selected_characteristic.is_none())];
+
+        let characteristics = self.version().map(get_characteristics).unwrap_or_default();
+        options.extend(characteristics.into_iter().map(|characteristic| {
+            let selected = self.selected_characteristic.as_ref() == Some(&characteristic);
+            CreateSelectMenuOption::new(characteristic_name(&characteristic), &characteristic)
+                .default_selection(selected)
+        }));
+
+        options.truncate(MAX_SELECT_OPTIONS);
+        options
+    }
+
+    /// Creates one option per difficulty of a characteristic.
+    fn get_diff_options(&self, characteristic: &str) -> Vec<CreateSelectMenuOption> {
+        self.version()
+            .into_iter()
+            .flat_map(|version| version.diffs.iter().enumerate())
+            .filter(|(_, diff)| diff.characteristic.to_string() == characteristic)
+            .take(MAX_SELECT_OPTIONS)
+            .map(|(idx, diff)| {
+                let mut option = CreateSelectMenuOption::new(
+                    difficulty_name(&diff.difficulty),
+                    (idx + 1).to_string(),
+                )
+                .default_selection(idx + 1 == self.selected_index);
+                if let Some(label) = &diff.label {
+                    option = option.description(truncate(label, limits::COMPONENT_LABEL, "..."));
+                }
+                option
+            })
+            .collect()
+    }
+
+    /// Creates one option per version, latest first.
+    fn get_version_options(&self) -> Vec<CreateSelectMenuOption> {
+        let count = self.map.versions.len();
+
+        self.map
+            .versions
+            .iter()
+            .enumerate()
+            .take(MAX_SELECT_OPTIONS)
+            .map(|(idx, version)| {
+                let label = match idx {
+                    0 => format!("v{} (latest)", count),
+                    _ => format!("v{}", count - idx),
+                };
+                CreateSelectMenuOption::new(label, &version.hash)
+                    .description(format!(
+                        "{} · {} · {:?}",
+                        &version.hash[..8.min(version.hash.len())],
+                        version.created_at.format("%Y-%m-%d"),
+                        version.state
+                    ))
+                    .default_selection(idx == self.selected_version)
+            })
+            .collect()
+    }
+
+    // MARK: Embed creator functions
+
+    /// Creates the general map info embed.
+    fn create_base_embed(&self, budget: &mut EmbedBudget) -> CreateEmbed {
+        let mut embed: CreateEmbed = CreateEmbed::new()
+            .title(budget.title(&sanitise(&self.map.name)))
+            .url(format!("https://beatsaver.com/maps/{}", self.map.id))
+            .footer(CreateEmbedFooter::new(
+                budget.footer(&format!("!bsr {}", self.map.id)),
+            ))
+            .timestamp(self.map.uploaded);
+
+        if let Some(version) = self.version() {
+            embed = embed.thumbnail(&version.cover_url);
+        }
+
+        if !self.compact {
+            embed = embed.description(budget.take(
+                &sanitise(&self.map.description),
+                2048, // !bsr 45001 is 4100 characters
+            ));
+        }
+
+        embed
+    }
+
+    /// Creates the general metadata of the map embed.
+    fn create_map_metadata_embed(&self, budget: &mut EmbedBudget) -> CreateEmbed {
+        let mappers = self
+            .map
+            .metadata
+            .level_author_name
+            .clone()
+            .unwrap_or(self.map.uploader.name.clone());
+        let artists = self
+            .map
+            .metadata
+            .song_author_name
+            .clone()
+            .unwrap_or("Unknown".to_owned());
+
+        let mut embed = self.create_base_embed(budget).field(
+            budget.field_name("Mapper(s)"),
+            budget.field_value(&sanitise(&mappers)),
+            false,
+        );
+        if !self.compact {
+            embed = embed.field(
+                budget.field_name("Artist(s)"),
+                budget.field_value(&sanitise(&artists)),
+                false,
+            );
+        }
+        embed = embed.fields(
+            [
+                ("BPM", self.map.metadata.bpm.to_string(), true),
+                ("Length", format_time(self.map.metadata.duration), true),
+                (
+                    "Rating",
+                    format!(
+                        "▲ {} / ▼ {} ({:.2}%)",
+                        self.map.stats.upvotes,
+                        self.map.stats.downvotes,
+                        self.map.stats.score * 100.0
+                    ),
+                    true,
+                ),
+            ]
+            .map(|(name, value, inline)| budget.field(name, &value, inline)),
+        );
+
+        if self.map.versions.len() > 1 && !self.compact {
+            embed = self.add_version_fields(embed, budget);
+        }
+
+        embed.colour(self.get_embed_colour())
+    }
+
+    /// Adds the selected version's details and difficulties.
+    fn add_version_fields(&self, embed: CreateEmbed, budget: &mut EmbedBudget) -> CreateEmbed {
+        let Some(version) = self.version() else {
+            return embed;
+        };
+        let age = match self.selected_version {
+            0 => "latest".to_owned(),
+            behind => format!("{behind} version(s) behind the latest"),
+        };
+
+        let difficulties: Vec<String> = get_characteristics(version)
+            .into_iter()
+            .map(|characteristic| {
+                let diffs: Vec<&str> = version
+                    .diffs
+                    .iter()
+                    .filter(|diff| diff.characteristic.to_string() == characteristic)
+                    .map(|diff| difficulty_name(&diff.difficulty))
+                    .collect();
+                format!(
+                    "**{}**: {}",
+                    characteristic_name(&characteristic),
+                    diffs.join(", ")
+                )
+            })
+            .collect();
+
+        embed
+            .field(
+                budget.field_name(&format!("Version ({age})")),
+                budget.field_value(&format!(
+                    "`{}`\nUploaded <t:{}:f>, {:?}",
+                    version.hash,
+                    version.created_at.timestamp(),
+                    version.state
+                )),
+                false,
+            )
+            .field(
+                budget.field_name("Difficulties"),
+                budget.field_value(&difficulties.join("\n")),
+                false,
+            )
+    }
+
+    /// Creates the embed representing data for one difficulty.
+    fn create_map_diff_embed(&self, diff: &MapDifficulty, budget: &mut EmbedBudget) -> CreateEmbed {
+        let title = match &diff.label {
+            Some(label) => sanitise(label),
+            None => difficulty_name(&diff.difficulty).to_owned(),
+        };
+        let mut embed = CreateEmbed::new().title(budget.title(&title));
+
+        if let Some(thumbnail) = self.get_characteristic_thumbnail(&diff.characteristic) {
+            embed = embed.thumbnail(thumbnail);
+        }
+
+        embed = embed.field(
+            budget.field_name("Characteristic/Difficulty"),
+            budget.field_value(&format!(
+                "{} {}",
+                characteristic_name(&diff.characteristic.to_string()),
+                difficulty_name(&diff.difficulty)
+            )),
+            false,
+        );
+
+        if let Some(scoresaber_stars) = diff.ss_stars {
+            embed = embed.field(
+                budget.field_name("ScoreSaber Stars"),
+                budget.field_value(&format!("{scoresaber_stars:.2}")),
+                true,
+            );
+        }
+        if let Some(beatleader_stars) = diff.bl_stars {
+            embed = embed.field(
+                budget.field_name("BeatLeader Stars"),
+                budget.field_value(&format!("{beatleader_stars:.2}")),
+                true,
+            );
+        }
+
+        embed
+            .field("", "", false)
+            .fields(
+                [
+                    ("Notes", diff.notes.to_string(), true),
+                    ("Bombs", diff.bombs.to_string(), true),
+                    ("Walls", diff.obstacles.to_string(), true),
+                    ("NJS", diff.njs.to_string(), true),
+                    ("NPS", diff.nps.to_string(), true),
+                    ("Lights", diff.events.to_string(), true),
+                ]
+                .map(|(name, value, inline)| budget.field(name, &value, inline)),
+            )
+            .colour(self.get_diff_colour(&diff.difficulty))
+    }
+
+    // MARK: Embed colour functions
+
+    /// Adds a colour to the map metadata embed.
+    fn get_embed_colour(&self) -> Colour {
+        if self.map.ss_ranked || self.map.bl_ranked {
+            Colour::from_rgb(243, 156, 18)
+        } else if self.map.curated_at.is_some() {
+            Colour::from_rgb(0, 188, 140)
+        } else if self.map.uploader.verified_mapper {
+            Colour::from_rgb(118, 70, 175)
+        } else {
+            Colour::from_rgb(68, 68, 68)
+        }
+    }
+
+    /// Adds a difficulty colour to the map difficulty embed.
+    fn get_diff_colour(&self, diff_name: &str) -> Colour {
+        DifficultyInfo::get(diff_name).colour()
+    }
+
+    /// Gets a thumbnail of the difficulty's characteristic, if there is one.
+    fn get_characteristic_thumbnail(&self, characteristic: &Characteristic) -> Option<String> {
+        CharacteristicInfo::get(&characteristic.to_string()).icon_url()
+    }
+}
+
+/// Handles a selection on a map embed, wherever and whenever it was sent.
+///
+/// Nothing is kept between interactions: the map ID comes from the custom ID
+/// and the embed is rebuilt from scratch.
+pub async fn handle_interaction(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &ComponentInteraction,
+    map_id: &str,
+    menu: Option<&str>,
+    hash: Option<&str>,
+) -> Result<(), Error> {
+    let selected = match &mci.data.kind {
+        ComponentInteractionDataKind::StringSelect { values } => values.first(),
+        _ => None,
+    };
+    let Some(selected) = selected else {
+        return Ok(());
+    };
+
+    let settings = GuildSettings::load(&data.storage, mci.guild_id).await?;
+    if settings.restrict_selections && !is_invoker(mci) {
+        let builder = CreateInteractionResponseMessage::new()
+            .content("Only whoever used the command can change what it shows here.")
+            .ephemeral(true);
+        mci.create_response(ctx, CreateInteractionResponse::Message(builder))
+            .await?;
+        return Ok(());
+    }
+
+    info!(
+        "Difficulty request for !bsr {} requested by {}",
+        map_id, mci.user.name
+    );
+
+    let mut map_embed = MapEmbed::new(data.beatsaver.map(map_id).await?).with_settings(&settings);
+    if let Some(hash) = hash {
+        map_embed = map_embed.with_hash(hash);
+    }
+    match menu {
+        Some("char") => map_embed.set_characteristic(selected),
+        Some("ver") => {
+            map_embed.set_version(selected);
+        }
+        // "diff", or an older embed with a single menu of every difficulty
+        _ => map_embed.set_index(selected),
+    }
+
+    let builder = CreateInteractionResponseMessage::new()
+        .embeds(map_embed.build_embeds())
+        .components(map_embed.build_embed_components());
+
+    mci.create_response(ctx, CreateInteractionResponse::UpdateMessage(builder))
+        .await?;
+
+    Ok(())
+}
+
+/// Whether this user is the one the message was sent for.
+///
+/// Messages without that info (e.g. very old ones) are open to everyone.
+fn is_invoker(mci: &ComponentInteraction) -> bool {
+    #[allow(deprecated)] // interaction_metadata doesn't have the user on every kind
+    let invoker = mci.message.interaction.as_ref().map(|i| i.user.id);
+    invoker.map_or(true, |invoker| invoker == mci.user.id)
+}
@@ -0,0 +1,168 @@
+use beatsaver_api::models::map::Map;
+use log::info;
+use poise::serenity_prelude::{
+    self as serenity, Colour, ComponentInteraction, ComponentInteractionDataKind,
+    CreateActionRow, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage,
+    CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption,
+};
+
+use crate::{
+    core::settings::GuildSettings,
+    ui::mapembed::MapEmbed,
+    utils::text::{limits, sanitise, truncate, EmbedBudget},
+    Data, Error,
+};
+
+/// Custom ID prefix for map list components, `maplist:open`.
+///
+/// Option values are `<map id>` or `<map id>:<hash>`.
+pub const COMPONENT_PREFIX: &str = "maplist";
+
+/// Discord won't take more options than this in one select menu.
+const MAX_ENTRIES: usize = 25;
+
+struct MapListEntry {
+    map: Map,
+    hash: Option<String>,
+}
+
+/// A short list of maps, with a menu to open any of them as a [`MapEmbed`].
+pub struct MapList {
+    title: String,
+    entries: Vec<MapListEntry>,
+    missing: Vec<String>,
+}
+
+impl MapList {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            entries: vec![],
+            missing: vec![],
+        }
+    }
+
+    /// Adds a map, optionally found through one of its version hashes.
+    pub fn push(&mut self, map: Map, hash: Option<String>) {
+        self.entries.push(MapListEntry { map, hash });
+    }
+
+    /// Notes something that couldn't be found.
+    pub fn push_missing(&mut self, what: impl Into<String>) {
+        self.missing.push(what.into());
+    }
+
+    pub fn build_embed(&self) -> CreateEmbed {
+        let mut description: String = self
+            .entries
+            .iter()
+            .take(MAX_ENTRIES)
+            .map(|entry| {
+                format!(
+                    "`{}` **{}** by {}\n",
+                    entry.map.id,
+                    truncate(&sanitise(&entry.map.name), 80, "..."),
+                    sanitise(&entry.map.uploader.name)
+                )
+            })
+            .collect();
+
+        if self.entries.len() > MAX_ENTRIES {
+            description.push_str(&format!(
+                "...and {} more\n",
+                self.entries.len() - MAX_ENTRIES
+            ));
+        }
+
+        let mut budget = EmbedBudget::new();
+        let mut embed = CreateEmbed::new()
+            .title(budget.title(&self.title))
+            .description(budget.description(&description))
+            .colour(Colour::from_rgb(68, 68, 68));
+
+        if !self.missing.is_empty() {
+            let missing: Vec<String> = self
+                .missing
+                .iter()
+                .take(10)
+                .map(|what| format!("`{}`", sanitise(what)))
+                .collect();
+            embed = embed.field(
+                budget.field_name(&format!("Not found ({})", self.missing.len())),
+                budget.field_value(&missing.join("\n")),
+                false,
+            );
+        }
+
+        embed
+    }
+
+    pub fn build_components(&self) -> Vec<CreateActionRow> {
+        if self.entries.is_empty() {
+            return vec![];
+        }
+
+        let options = self
+            .entries
+            .iter()
+            .take(MAX_ENTRIES)
+            .map(|entry| {
+                let value = match &entry.hash {
+                    Some(hash) => format!("{}:{}", entry.map.id, hash),
+                    None => entry.map.id.clone(),
+                };
+                CreateSelectMenuOption::new(
+                    truncate(&entry.map.name, limits::COMPONENT_LABEL, "..."),
+                    value,
+                )
+                .description(format!("!bsr {}", entry.map.id))
+            })
+            .collect();
+
+        vec![CreateActionRow::SelectMenu(
+            CreateSelectMenu::new(
+                format!("{COMPONENT_PREFIX}:open"),
+                CreateSelectMenuKind::String { options },
+            )
+            .placeholder("Open a map"),
+        )]
+    }
+}
+
+/// Opens the picked map as a new message, leaving the list alone.
+pub async fn handle_interaction(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &ComponentInteraction,
+) -> Result<(), Error> {
+    let selected = match &mci.data.kind {
+        ComponentInteractionDataKind::StringSelect { values } => values.first(),
+        _ => None,
+    };
+    let Some(selected) = selected else {
+        return Ok(());
+    };
+
+    let (map_id, hash) = match selected.split_once(':') {
+        Some((map_id, hash)) => (map_id, Some(hash)),
+        None => (selected.as_str(), None),
+    };
+
+    info!("Map {} opened from a list by {}", map_id, mci.user.name);
+
+    let settings = GuildSettings::load(&data.storage, mci.guild_id).await?;
+    let mut map_embed = MapEmbed::new(data.beatsaver.map(map_id).await?).with_settings(&settings);
+    if let Some(hash) = hash {
+        map_embed = map_embed.with_hash(hash);
+    }
+
+    let builder = CreateInteractionResponseMessage::new()
+        .embeds(map_embed.build_embeds())
+        .components(map_embed.build_embed_components())
+        .ephemeral(settings.ephemeral());
+
+    mci.create_response(ctx, CreateInteractionResponse::Message(builder))
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use poise::serenity_prelude::{Colour, CreateEmbed, CreateEmbedFooter};
+
+use crate::{
+    clients::beatsaver::Mapper,
+    utils::text::{sanitise, EmbedBudget},
+};
+
+/// Builds the embed for a BeatSaver user's profile.
+pub fn build_profile_embed(mapper: &Mapper) -> CreateEmbed {
+    let mut budget = EmbedBudget::new();
+
+    let mut badges = vec![];
+    if mapper.verified_mapper {
+        badges.push("✅ Verified mapper");
+    }
+    if mapper.curator {
+        badges.push("🏅 Curator");
+    }
+
+    let mut description = badges.join(" · ");
+    if !mapper.description.is_empty() {
+        if !description.is_empty() {
+            description.push_str("\n\n");
+        }
+        description.push_str(&sanitise(&mapper.description));
+    }
+
+    let mut embed = CreateEmbed::new()
+        .title(budget.title(&sanitise(&mapper.name)))
+        .url(format!("https://beatsaver.com/profile/{}", mapper.id))
+        .description(budget.take(&description, 1024))
+        .thumbnail(&mapper.avatar)
+        .colour(match mapper.verified_mapper {
+            true => Colour::from_rgb(166, 149, 255),
+            false => Colour::from_rgb(68, 68, 68),
+        })
+        .footer(CreateEmbedFooter::new(
+            budget.footer(&format!("BeatSaver user {}", mapper.id)),
+        ));
+
+    let Some(stats) = &mapper.stats else {
+        return embed;
+    };
+
+    let upload = |time: Option<chrono::DateTime<chrono::Utc>>| match time {
+        Some(time) => format!("<t:{}:D>", time.timestamp()),
+        None => "Never".to_owned(),
+    };
+
+    embed = embed.fields(
+        [
+            ("Maps", stats.total_maps.to_string(), true),
+            ("Ranked maps", stats.ranked_maps.to_string(), true),
+            (
+                "Average rating",
+                format!("{:.2}%", stats.avg_score * 100.0),
+                true,
+            ),
+            (
+                "Votes",
+                format!("▲ {} / ▼ {}", stats.total_upvotes, stats.total_downvotes),
+                true,
+            ),
+            ("First upload", upload(stats.first_upload), true),
+            ("Latest upload", upload(stats.last_upload), true),
+        ]
+        .map(|(name, value, inline)| budget.field(name, &value, inline)),
+    );
+
+    embed
+}
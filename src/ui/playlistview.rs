@@ -0,0 +1,417 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use beatsaver_api::models::map::{Map, MapVersion};
+use futures::{StreamExt, TryStreamExt};
+use log::info;
+use poise::serenity_prelude::{
+    self as serenity, Colour, ComponentInteraction, CreateActionRow, CreateButton, CreateEmbed,
+    CreateEmbedFooter, CreateInteractionResponse, CreateSelectMenu, CreateSelectMenuKind,
+    CreateSelectMenuOption, EditInteractionResponse,
+};
+
+use crate::{
+    clients::beatsaver::BeatSaver,
+    core::error::ErrorKind,
+    ui::{difficulty::DifficultyInfo, mapembed::format_time, maplist},
+    utils::{
+        playlist::Playlist,
+        text::{limits, sanitise, truncate, EmbedBudget},
+    },
+    Data, Error,
+};
+
+/// Custom ID prefix for playlist buttons, `playlist:page:<page>`.
+///
+/// The playlist itself is attached to the message, so it gets read again from
+/// there.
+pub const COMPONENT_PREFIX: &str = "playlist";
+
+/// How many songs are listed at once.
+const PAGE_SIZE: usize = 10;
+/// How many missing or deleted songs are named before just counting them.
+const MAX_PROBLEMS: usize = 10;
+/// Songs looked up by key at once, for hashes BeatSaver didn't know.
+const KEY_LOOKUPS: usize = 5;
+
+/// Where a song in the playlist ended up.
+enum SongStatus {
+    /// Found, and the hash is the map's latest version.
+    Latest(Map),
+    /// Found, but there's a newer version.
+    Outdated(Map),
+    /// Gone from BeatSaver, by the key the playlist gave it.
+    Deleted(String),
+    Missing,
+}
+
+struct PlaylistEntry {
+    hash: String,
+    /// The name the playlist gives it, for when BeatSaver doesn't know it.
+    song_name: Option<String>,
+    status: SongStatus,
+}
+
+/// A `.bplist` with every song looked up on BeatSaver.
+pub struct PlaylistView {
+    title: String,
+    author: Option<String>,
+    entries: Vec<PlaylistEntry>,
+}
+
+impl PlaylistView {
+    /// Looks up every song in the playlist, in batches.
+    pub async fn resolve(beatsaver: &BeatSaver, playlist: Playlist) -> Result<Self, Error> {
+        let hashes: Vec<String> = playlist
+            .songs
+            .iter()
+            .map(|song| song.hash.trim().to_lowercase())
+            .collect();
+        // one broken hash would make BeatSaver reject its whole batch
+        let valid: Vec<String> = hashes
+            .iter()
+            .filter(|hash| hash.len() == 40 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+            .cloned()
+            .collect();
+        let maps = beatsaver.maps_by_hashes(&valid).await?;
+
+        // deleted maps are just left out by hash, so the ones with a key get
+        // looked up by that to tell them apart from hashes that never existed
+        let mut keys: Vec<String> = playlist
+            .songs
+            .iter()
+            .zip(&hashes)
+            .filter(|(_, hash)| !maps.contains_key(*hash))
+            .filter_map(|(song, _)| song_key(song.key.as_deref()))
+            .collect();
+        keys.sort();
+        keys.dedup();
+        let by_key: HashMap<String, Option<Map>> = futures::stream::iter(keys)
+            .map(|key| async move {
+                match beatsaver.map(&key).await {
+                    Ok(map) => Ok((key, Some(map))),
+                    Err(err) => match ErrorKind::classify(&err) {
+                        ErrorKind::Deleted | ErrorKind::NotFound { .. } => Ok((key, None)),
+                        _ => Err(err),
+                    },
+                }
+            })
+            .buffer_unordered(KEY_LOOKUPS)
+            .try_collect()
+            .await?;
+
+        let entries = playlist
+            .songs
+            .into_iter()
+            .zip(hashes)
+            .map(|(song, hash)| {
+                let key = song_key(song.key.as_deref());
+                let looked_up = key.as_ref().and_then(|key| by_key.get(key));
+                let status = match (maps.get(&hash), looked_up) {
+                    (Some(map), _)
+                        if map
+                            .versions
+                            .first()
+                            .is_some_and(|latest| latest.hash.eq_ignore_ascii_case(&hash)) =>
+                    {
+                        SongStatus::Latest(map.clone())
+                    }
+                    (Some(map), _) => SongStatus::Outdated(map.clone()),
+                    // still up, just not with that hash anymore
+                    (None, Some(Some(map))) => SongStatus::Outdated(map.clone()),
+                    (None, Some(None)) => SongStatus::Deleted(key.unwrap_or_default()),
+                    (None, None) => SongStatus::Missing,
+                };
+                PlaylistEntry {
+                    hash,
+                    song_name: song.song_name,
+                    status,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            title: playlist.playlist_title,
+            author: playlist.playlist_author,
+            entries,
+        })
+    }
+
+    fn page_count(&self) -> usize {
+        self.entries.len().div_ceil(PAGE_SIZE).max(1)
+    }
+
+    fn page_entries(&self, page: usize) -> &[PlaylistEntry] {
+        let start = (page * PAGE_SIZE).min(self.entries.len());
+        let end = (start + PAGE_SIZE).min(self.entries.len());
+        &self.entries[start..end]
+    }
+
+    pub fn build_embed(&self, page: usize) -> CreateEmbed {
+        let mut budget = EmbedBudget::new();
+
+        let found: Vec<&Map> = self.entries.iter().filter_map(|e| e.map()).collect();
+        let total_duration: i32 = found.iter().map(|map| map.metadata.duration).sum();
+        let count =
+            |f: fn(&SongStatus) -> bool| self.entries.iter().filter(|e| f(&e.status)).count();
+
+        let mut summary = format!(
+            "{} song(s), {} long",
+            self.entries.len(),
+            format_time(total_duration)
+        );
+        if let Some(author) = &self.author {
+            summary = format!("By {}\n{summary}", sanitise(author));
+        }
+        for (label, n) in [
+            ("missing", count(|s| matches!(s, SongStatus::Missing))),
+            ("deleted", count(|s| matches!(s, SongStatus::Deleted(_)))),
+            ("outdated", count(|s| matches!(s, SongStatus::Outdated(_)))),
+        ] {
+            if n > 0 {
+                summary.push_str(&format!(", {n} {label}"));
+            }
+        }
+
+        let list: String = self
+            .page_entries(page)
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| format!("**{}.** {}\n", page * PAGE_SIZE + i + 1, entry.line()))
+            .collect();
+
+        let mut embed = CreateEmbed::new()
+            .title(budget.title(&sanitise(&self.title)))
+            .description(budget.description(&format!("{summary}\n\n{list}")))
+            .colour(Colour::from_rgb(166, 149, 255))
+            .footer(CreateEmbedFooter::new(format!(
+                "Page {}/{}",
+                page + 1,
+                self.page_count()
+            )));
+
+        let spread = self.difficulty_spread();
+        if !spread.is_empty() {
+            embed = embed.field(
+                budget.field_name("Difficulties"),
+                budget.field_value(&spread),
+                false,
+            );
+        }
+
+        let problems: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|entry| matches!(entry.status, SongStatus::Missing | SongStatus::Deleted(_)))
+            .map(|entry| entry.line())
+            .collect();
+        if !problems.is_empty() {
+            let mut value = problems
+                .iter()
+                .take(MAX_PROBLEMS)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join("\n");
+            if problems.len() > MAX_PROBLEMS {
+                value.push_str(&format!("\n...and {} more", problems.len() - MAX_PROBLEMS));
+            }
+            embed = embed.field(
+                budget.field_name(&format!("Missing or deleted ({})", problems.len())),
+                budget.field_value(&value),
+                false,
+            );
+        }
+
+        embed
+    }
+
+    pub fn build_components(&self, page: usize) -> Vec<CreateActionRow> {
+        let mut rows = vec![];
+
+        // opening a song works just like picking from a map list
+        let mut values: Vec<String> = vec![];
+        let options: Vec<CreateSelectMenuOption> = self
+            .page_entries(page)
+            .iter()
+            .filter_map(|entry| {
+                let map = entry.map()?;
+                // Discord won't take the same value twice
+                let value = format!("{}:{}", map.id, entry.hash);
+                if values.contains(&value) {
+                    return None;
+                }
+                values.push(value.clone());
+
+                Some(
+                    CreateSelectMenuOption::new(
+                        truncate(&map.name, limits::COMPONENT_LABEL, "..."),
+                        value,
+                    )
+                    .description(format!("!bsr {}", map.id)),
+                )
+            })
+            .collect();
+        if !options.is_empty() {
+            rows.push(CreateActionRow::SelectMenu(
+                CreateSelectMenu::new(
+                    format!("{}:open", maplist::COMPONENT_PREFIX),
+                    CreateSelectMenuKind::String { options },
+                )
+                .placeholder("Open a song"),
+            ));
+        }
+
+        if self.page_count() > 1 {
+            rows.push(CreateActionRow::Buttons(vec![
+                CreateButton::new(format!(
+                    "{COMPONENT_PREFIX}:page:{}",
+                    page.saturating_sub(1)
+                ))
+                .label("Previous")
+                .emoji('◀')
+                .disabled(page == 0),
+                CreateButton::new(format!("{COMPONENT_PREFIX}:page:{}", page + 1))
+                    .label("Next")
+                    .emoji('▶')
+                    .disabled(page + 1 >= self.page_count()),
+            ]));
+        }
+
+        rows
+    }
+
+    /// How many songs have each difficulty, e.g. `Expert 12 · Expert+ 9`.
+    fn difficulty_spread(&self) -> String {
+        let mut counts: HashMap<&'static str, usize> = HashMap::new();
+
+        for version in self.entries.iter().filter_map(|entry| entry.version()) {
+            let mut names: Vec<&'static str> = version
+                .diffs
+                .iter()
+                .map(|diff| DifficultyInfo::get(&diff.difficulty).display_name)
+                .collect();
+            // once per song, not per characteristic
+            names.sort();
+            names.dedup();
+
+            for name in names {
+                *counts.entry(name).or_default() += 1;
+            }
+        }
+
+        // easiest first, same as in game, with anything unknown at the end
+        let mut order: Vec<&'static str> = DifficultyInfo::all()
+            .iter()
+            .map(|info| info.display_name)
+            .collect();
+        order.push("Unknown");
+
+        order
+            .into_iter()
+            .filter_map(|name| counts.get(name).map(|count| format!("{name} {count}")))
+            .collect::<Vec<_>>()
+            .join(" · ")
+    }
+}
+
+impl PlaylistEntry {
+    fn map(&self) -> Option<&Map> {
+        match &self.status {
+            SongStatus::Latest(map) | SongStatus::Outdated(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// The version the playlist points at.
+    fn version(&self) -> Option<&MapVersion> {
+        self.map()?
+            .versions
+            .iter()
+            .find(|version| version.hash.eq_ignore_ascii_case(&self.hash))
+    }
+
+    /// The playlist's own name for it, for songs BeatSaver doesn't have.
+    fn fallback_name(&self) -> String {
+        truncate(
+            &sanitise(self.song_name.as_deref().unwrap_or("Unknown song")),
+            80,
+            "...",
+        )
+    }
+
+    fn line(&self) -> String {
+        let name = |map: &Map| truncate(&sanitise(&map.name), 80, "...");
+
+        match &self.status {
+            SongStatus::Latest(map) => format!(
+                "`{}` **{}** by {} · `{}`",
+                map.id,
+                name(map),
+                sanitise(&map.uploader.name),
+                format_time(map.metadata.duration)
+            ),
+            SongStatus::Outdated(map) => format!(
+                "`{}` **{}** by {} · `{}` · older version",
+                map.id,
+                name(map),
+                sanitise(&map.uploader.name),
+                format_time(map.metadata.duration)
+            ),
+            SongStatus::Deleted(key) => {
+                format!("`{}` **{}** · deleted", sanitise(key), self.fallback_name())
+            }
+            SongStatus::Missing => format!(
+                "`{}` **{}** · not on BeatSaver",
+                truncate(&self.hash, 12, "..."),
+                self.fallback_name()
+            ),
+        }
+    }
+}
+
+/// A song's `!bsr` key, tidied up for looking up, if the playlist gave one.
+fn song_key(key: Option<&str>) -> Option<String> {
+    key.map(|key| key.trim().to_lowercase())
+        .filter(|key| !key.is_empty())
+}
+
+/// Flips to another page, reading the playlist back from the attachment.
+pub async fn handle_interaction(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &ComponentInteraction,
+    page: &str,
+) -> Result<(), Error> {
+    let page: usize = page
+        .parse()
+        .map_err(|_| anyhow!("malformed playlist page {page}"))?;
+    let attachment = mci
+        .message
+        .attachments
+        .iter()
+        .find(|attachment| attachment.filename.ends_with(".bplist"))
+        .ok_or_else(|| anyhow!("couldn't find the playlist on a playlist embed"))?;
+
+    info!(
+        "Playlist page {} for {} requested by {}",
+        page + 1,
+        attachment.filename,
+        mci.user.name
+    );
+
+    // reading it back and looking every song up can take longer than Discord
+    // waits for a reply
+    mci.create_response(ctx, CreateInteractionResponse::Acknowledge)
+        .await?;
+
+    let playlist = Playlist::from_json(&attachment.download().await?)?;
+    let view = PlaylistView::resolve(&data.beatsaver, playlist).await?;
+    let page = page.min(view.page_count() - 1);
+
+    let builder = EditInteractionResponse::new()
+        .embed(view.build_embed(page))
+        .components(view.build_components(page));
+    mci.edit_response(ctx, builder).await?;
+
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use poise::serenity_prelude::{Colour, CreateEmbed, CreateEmbedFooter};
+
+use crate::{
+    clients::scoresaber::{Player, PlayerScore},
+    ui::difficulty::difficulty_name,
+    utils::text::{country_flag, sanitise, truncate, EmbedBudget},
+};
+
+/// Builds the embed for a ScoreSaber player, with their top plays.
+pub fn build_player_embed(player: &Player, top_plays: &[PlayerScore]) -> CreateEmbed {
+    let mut budget = EmbedBudget::new();
+
+    let rank = |rank: u32| match rank {
+        0 => "Unranked".to_owned(),
+        rank => format!("#{rank}"),
+    };
+
+    let mut embed = CreateEmbed::new()
+        .title(budget.title(&format!(
+            "{} {}",
+            country_flag(&player.country),
+            sanitise(&player.name)
+        )))
+        .url(format!("https://scoresaber.com/u/{}", player.id))
+        .thumbnail(&player.profile_picture)
+        .colour(Colour::from_rgb(255, 222, 24))
+        .footer(CreateEmbedFooter::new(
+            budget.footer(&format!("ScoreSaber player {}", player.id)),
+        ));
+
+    if player.banned {
+        embed = embed.description("This player is banned.");
+    } else if player.inactive {
+        embed = embed.description("This player is inactive, so they don't have a rank.");
+    }
+
+    embed = embed.fields(
+        [
+            ("Global rank", rank(player.rank), true),
+            ("Country rank", rank(player.country_rank), true),
+            ("PP", format!("{:.2}pp", player.pp), true),
+        ]
+        .map(|(name, value, inline)| budget.field(name, &value, inline)),
+    );
+    if let Some(stats) = &player.score_stats {
+        embed = embed.fields(
+            [
+                (
+                    "Ranked accuracy",
+                    format!("{:.2}%", stats.average_ranked_accuracy),
+                    true,
+                ),
+                ("Ranked plays", stats.ranked_play_count.to_string(), true),
+                ("Total plays", stats.total_play_count.to_string(), true),
+            ]
+            .map(|(name, value, inline)| budget.field(name, &value, inline)),
+        );
+    }
+
+    if !top_plays.is_empty() {
+        let plays: Vec<String> = top_plays
+            .iter()
+            .enumerate()
+            .map(|(idx, play)| {
+                let accuracy = match play.score.accuracy(play.leaderboard.max_score) {
+                    Some(accuracy) => format!(" · {:.2}%", accuracy * 100.0),
+                    None => String::new(),
+                };
+                format!(
+                    "{}. [{}](https://scoresaber.com/leaderboard/{}) ({}) · **{:.2}pp**{} · #{}",
+                    idx + 1,
+                    truncate(&sanitise(&play.leaderboard.song_name), 40, "..."),
+                    play.leaderboard.id,
+                    difficulty_name(play.leaderboard.difficulty.name()),
+                    play.score.pp,
+                    accuracy,
+                    play.score.rank
+                )
+            })
+            .collect();
+        embed = embed.field(
+            budget.field_name("Top plays"),
+            budget.field_value(&plays.join("\n")),
+            false,
+        );
+    }
+
+    embed
+}
@@ -0,0 +1,223 @@
+use anyhow::anyhow;
+use beatsaver_api::models::map::Map;
+use log::info;
+use poise::serenity_prelude::{
+    self as serenity, Colour, ComponentInteraction, CreateActionRow, CreateButton, CreateEmbed,
+    CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseMessage,
+    CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption,
+};
+
+use crate::{
+    clients::beatsaver::{query::SearchQuery, BeatSaver},
+    ui::{difficulty::DifficultyInfo, mapembed::format_time, maplist},
+    utils::text::{limits, sanitise, truncate, EmbedBudget},
+    Data, Error,
+};
+
+/// Custom ID prefix for search result buttons, `search:page:<page>`.
+///
+/// The query itself lives in the footer, since it can be longer than a custom
+/// ID allows.
+pub const COMPONENT_PREFIX: &str = "search";
+
+/// How many results are shown at once.
+const PAGE_SIZE: usize = 10;
+/// How many results BeatSaver sends per page.
+const BEATSAVER_PAGE_SIZE: usize = 20;
+
+/// Separates the page number from the query in the footer.
+const FOOTER_QUERY: &str = " · Search: ";
+
+/// One page of search results, with buttons to flip through the rest.
+pub struct SearchResults {
+    query: String,
+    /// Starts at 0.
+    page: usize,
+    maps: Vec<Map>,
+    has_next: bool,
+}
+
+impl SearchResults {
+    /// Searches BeatSaver and picks out one page of results.
+    pub async fn fetch(beatsaver: &BeatSaver, query: &str, page: usize) -> Result<Self, Error> {
+        let search = SearchQuery::parse(query)?;
+
+        // our pages are half of BeatSaver's
+        let per_request = BEATSAVER_PAGE_SIZE / PAGE_SIZE;
+        let docs = beatsaver
+            .search(&search, (page / per_request) as u32)
+            .await?;
+        let start = (page % per_request) * PAGE_SIZE;
+
+        let maps: Vec<Map> = docs.iter().skip(start).take(PAGE_SIZE).cloned().collect();
+        // a full page from BeatSaver probably means there's another one
+        let has_next = docs.len() > start + PAGE_SIZE || docs.len() == BEATSAVER_PAGE_SIZE;
+
+        Ok(Self {
+            query: query.to_owned(),
+            page,
+            maps,
+            has_next,
+        })
+    }
+
+    pub fn build_embed(&self) -> CreateEmbed {
+        let mut budget = EmbedBudget::new();
+
+        let description = if self.maps.is_empty() {
+            match self.page {
+                0 => "Nothing matched that search.".to_owned(),
+                _ => "That's all of them.".to_owned(),
+            }
+        } else {
+            self.maps
+                .iter()
+                .enumerate()
+                .map(|(i, map)| {
+                    format!(
+                        "**{}.** `{}` **{}** by {}\n{}\n",
+                        self.page * PAGE_SIZE + i + 1,
+                        map.id,
+                        truncate(&sanitise(&map.name), 80, "..."),
+                        truncate(&sanitise(&map.uploader.name), 40, "..."),
+                        badges(map)
+                    )
+                })
+                .collect()
+        };
+
+        CreateEmbed::new()
+            .title(budget.title(&format!("Search results for {}", sanitise(&self.query))))
+            .description(budget.description(&description))
+            .colour(Colour::from_rgb(68, 68, 68))
+            .footer(CreateEmbedFooter::new(format!(
+                "Page {}{FOOTER_QUERY}{}",
+                self.page + 1,
+                self.query
+            )))
+    }
+
+    pub fn build_components(&self) -> Vec<CreateActionRow> {
+        let mut rows = vec![];
+
+        // opening a result works just like picking from a map list
+        if !self.maps.is_empty() {
+            let options = self
+                .maps
+                .iter()
+                .map(|map| {
+                    CreateSelectMenuOption::new(
+                        truncate(&map.name, limits::COMPONENT_LABEL, "..."),
+                        map.id.clone(),
+                    )
+                    .description(truncate(
+                        &format!("!bsr {} by {}", map.id, map.uploader.name),
+                        limits::COMPONENT_LABEL,
+                        "...",
+                    ))
+                })
+                .collect();
+
+            rows.push(CreateActionRow::SelectMenu(
+                CreateSelectMenu::new(
+                    format!("{}:open", maplist::COMPONENT_PREFIX),
+                    CreateSelectMenuKind::String { options },
+                )
+                .placeholder("Open a map"),
+            ));
+        }
+
+        if self.page > 0 || self.has_next {
+            rows.push(CreateActionRow::Buttons(vec![
+                CreateButton::new(format!("{COMPONENT_PREFIX}:page:{}", self.page.saturating_sub(1)))
+                    .label("Previous")
+                    .emoji('◀')
+                    .disabled(self.page == 0),
+                CreateButton::new(format!("{COMPONENT_PREFIX}:page:{}", self.page + 1))
+                    .label("Next")
+                    .emoji('▶')
+                    .disabled(!self.has_next),
+            ]));
+        }
+
+        rows
+    }
+}
+
+/// Rating, length, ranked/curated status and difficulties, all on one line.
+fn badges(map: &Map) -> String {
+    let mut badges = vec![
+        format!("`{:.0}%`", map.stats.score * 100.0),
+        format!("`{}`", format_time(map.metadata.duration)),
+    ];
+
+    if map.ss_ranked || map.bl_ranked {
+        badges.push("`Ranked`".to_owned());
+    }
+    if map.curated_at.is_some() {
+        badges.push("`Curated`".to_owned());
+    }
+
+    if let Some(version) = map.versions.first() {
+        let mut difficulties: Vec<&str> = vec![];
+        for diff in &version.diffs {
+            let short_name = DifficultyInfo::get(&diff.difficulty).short_name;
+            if !difficulties.contains(&short_name) {
+                difficulties.push(short_name);
+            }
+        }
+
+        let difficulties: Vec<String> = difficulties
+            .into_iter()
+            .map(|name| format!("`{name}`"))
+            .collect();
+        badges.push(difficulties.join(" "));
+    }
+
+    badges.join(" · ")
+}
+
+/// Flips to another page, reading the query back out of the footer.
+///
+/// The results are always the last embed, anything before them (like a
+/// mapper's profile) is kept as it is.
+pub async fn handle_interaction(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &ComponentInteraction,
+    page: &str,
+) -> Result<(), Error> {
+    let page: usize = page
+        .parse()
+        .map_err(|_| anyhow!("malformed search page {page}"))?;
+    let Some((results_embed, kept)) = mci.message.embeds.split_last() else {
+        return Err(anyhow!("search message has no embeds"));
+    };
+    let query = results_embed
+        .footer
+        .as_ref()
+        .and_then(|footer| footer.text.split_once(FOOTER_QUERY))
+        .map(|(_, query)| query.to_owned())
+        .ok_or_else(|| anyhow!("couldn't find the query on a search embed"))?;
+
+    info!(
+        "Search page {} for {:?} requested by {}",
+        page + 1,
+        query,
+        mci.user.name
+    );
+
+    let results = SearchResults::fetch(&data.beatsaver, &query, page).await?;
+
+    let mut embeds: Vec<CreateEmbed> = kept.iter().cloned().map(CreateEmbed::from).collect();
+    embeds.push(results.build_embed());
+
+    let builder = CreateInteractionResponseMessage::new()
+        .embeds(embeds)
+        .components(results.build_components());
+
+    mci.create_response(ctx, CreateInteractionResponse::UpdateMessage(builder))
+        .await?;
+
+    Ok(())
+}
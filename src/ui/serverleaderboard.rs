@@ -0,0 +1,156 @@
+use anyhow::anyhow;
+use log::info;
+use poise::serenity_prelude::{
+    self as serenity, ButtonStyle, Colour, ComponentInteraction, CreateButton, CreateEmbed,
+    CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseFollowup,
+    CreateInteractionResponseMessage,
+};
+
+use crate::{
+    core::{
+        serverleaderboard::MemberScore, settings::GuildSettings, storage::linked_accounts::Service,
+    },
+    ui::{
+        difficulty::{characteristic_name, difficulty_name, DifficultyInfo},
+        leaderboard::Source,
+    },
+    utils::text::{sanitise, EmbedBudget},
+    Data, Error,
+};
+
+/// Custom ID prefix for server leaderboard buttons,
+/// `serverleaderboard:<source>:<hash>:<characteristic>:<difficulty>`.
+pub const COMPONENT_PREFIX: &str = "serverleaderboard";
+
+/// A button that opens the best scores from this server's linked members.
+pub fn build_button(
+    source: Source,
+    hash: &str,
+    characteristic: &str,
+    difficulty: &str,
+) -> CreateButton {
+    CreateButton::new(format!(
+        "{COMPONENT_PREFIX}:{}:{hash}:{characteristic}:{difficulty}",
+        source.id()
+    ))
+    .label("Server leaderboard")
+    .emoji('👥')
+    .style(ButtonStyle::Secondary)
+}
+
+/// Builds the embed for a server's scores on one difficulty.
+pub fn build_embed(
+    map_name: &str,
+    source: Source,
+    characteristic: &str,
+    difficulty: &str,
+    scores: &[MemberScore],
+) -> CreateEmbed {
+    let mut budget = EmbedBudget::new();
+
+    let lines: Vec<String> = scores
+        .iter()
+        .enumerate()
+        .map(|(idx, score)| {
+            let profile = Service::from(source).profile_url(&score.player_id);
+            let accuracy = match score.accuracy {
+                Some(accuracy) => format!(" · {:.2}%", accuracy * 100.0),
+                None => String::new(),
+            };
+            let pp = match score.pp > 0.0 {
+                true => format!(" · **{:.2}pp**", score.pp),
+                false => String::new(),
+            };
+            let misses = match score.full_combo {
+                true => "FC".to_owned(),
+                false => format!("{}x", score.misses),
+            };
+            format!(
+                "**#{}** <@{}> ([{}]({profile})){accuracy}{pp} · {misses} · <t:{}:d>",
+                idx + 1,
+                score.user_id,
+                sanitise(&score.player_name),
+                score.set_at
+            )
+        })
+        .collect();
+
+    let description = match lines.is_empty() {
+        true => "Nobody here has a score on this yet. Link an account with `/link` to show up."
+            .to_owned(),
+        false => lines.join("\n"),
+    };
+
+    CreateEmbed::new()
+        .title(budget.title(&format!(
+            "{} ({} {})",
+            sanitise(map_name),
+            characteristic_name(characteristic),
+            difficulty_name(difficulty)
+        )))
+        .description(budget.description(&description))
+        .colour(match scores.is_empty() {
+            true => Colour::from_rgb(68, 68, 68),
+            false => DifficultyInfo::get(difficulty).colour(),
+        })
+        .footer(CreateEmbedFooter::new(budget.footer(&format!(
+            "Server leaderboard · {} · Linked members who've used the bot here",
+            source.name()
+        ))))
+}
+
+/// Shows a server leaderboard as a new message, once everyone's been looked up.
+pub async fn handle_interaction(
+    ctx: &serenity::Context,
+    data: &Data,
+    mci: &ComponentInteraction,
+    source: &str,
+    hash: &str,
+    characteristic: &str,
+    difficulty: &str,
+) -> Result<(), Error> {
+    let source =
+        Source::from_id(source).ok_or_else(|| anyhow!("unknown leaderboard source {source}"))?;
+    let Some(guild) = mci.guild_id else {
+        let builder = CreateInteractionResponseMessage::new()
+            .content("Server leaderboards only work in servers.")
+            .ephemeral(true);
+        mci.create_response(ctx, CreateInteractionResponse::Message(builder))
+            .await?;
+        return Ok(());
+    };
+
+    info!(
+        "{:?} server leaderboard for {} {} {} in {} requested by {}",
+        source, hash, characteristic, difficulty, guild, mci.user.name
+    );
+
+    // looking everyone up can take longer than Discord waits for a reply
+    let settings = GuildSettings::load(&data.storage, mci.guild_id).await?;
+    mci.create_response(
+        ctx,
+        CreateInteractionResponse::Defer(
+            CreateInteractionResponseMessage::new().ephemeral(settings.ephemeral()),
+        ),
+    )
+    .await?;
+
+    let map = data.beatsaver.map_by_hash(hash).await?;
+    let scores = data
+        .server_leaderboards
+        .get(ctx, data, guild, source, hash, characteristic, difficulty)
+        .await?;
+
+    let builder = CreateInteractionResponseFollowup::new()
+        .embed(build_embed(
+            &map.name,
+            source,
+            characteristic,
+            difficulty,
+            &scores,
+        ))
+        .ephemeral(settings.ephemeral());
+    mci.create_followup(ctx, builder).await?;
+
+    Ok(())
+}
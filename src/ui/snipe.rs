@@ -0,0 +1,69 @@
+use poise::serenity_prelude::{CreateEmbed, CreateEmbedFooter};
+
+use crate::{
+    core::{serverleaderboard::MemberScore, storage::linked_accounts::Service},
+    ui::difficulty::{characteristic_name, difficulty_name, DifficultyInfo},
+    utils::text::{sanitise, EmbedBudget},
+};
+
+/// Builds the embed for one linked member taking the lead over another.
+pub fn build_embed(
+    map_name: &str,
+    service: Service,
+    characteristic: &str,
+    difficulty: &str,
+    sniper: &MemberScore,
+    victim: &MemberScore,
+) -> CreateEmbed {
+    let mut budget = EmbedBudget::new();
+
+    let description = format!(
+        "<@{}> beat <@{}>'s score on **{}** ({} {}).",
+        sniper.user_id,
+        victim.user_id,
+        sanitise(map_name),
+        characteristic_name(characteristic),
+        difficulty_name(difficulty)
+    );
+
+    CreateEmbed::new()
+        .title(budget.title("🎯 Sniped!"))
+        .description(budget.description(&description))
+        .field(
+            budget.field_name(&sanitise(&sniper.player_name)),
+            budget.field_value(&format_score(service, sniper)),
+            true,
+        )
+        .field(
+            budget.field_name(&sanitise(&victim.player_name)),
+            budget.field_value(&format_score(service, victim)),
+            true,
+        )
+        .colour(DifficultyInfo::get(difficulty).colour())
+        .footer(CreateEmbedFooter::new(budget.footer(&format!(
+            "{} · Mute these with /snipes mute",
+            service.name()
+        ))))
+}
+
+/// A score on its own lines, for a field.
+fn format_score(service: Service, score: &MemberScore) -> String {
+    let mut lines = vec![format!(
+        "[**{}**]({})",
+        score.score,
+        service.profile_url(&score.player_id)
+    )];
+    if let Some(accuracy) = score.accuracy {
+        lines.push(format!("{:.2}%", accuracy * 100.0));
+    }
+    if score.pp > 0.0 {
+        lines.push(format!("{:.2}pp", score.pp));
+    }
+    lines.push(match score.full_combo {
+        true => "FC".to_owned(),
+        false => format!("{}x", score.misses),
+    });
+    lines.push(format!("<t:{}:R>", score.set_at));
+
+    lines.join("\n")
+}
@@ -1,2 +1,4 @@
-pub mod discord;
-pub mod truncate;
+pub mod cache;
+pub mod discord;
+pub mod playlist;
+pub mod text;
@@ -1 +1,35 @@
-pub mod autocomplete;
+pub mod autocomplete;
+pub mod debounce;
+
+use poise::serenity_prelude::{self as serenity, CacheHttp, GuildId, HttpError, UserId};
+
+/// Whether someone's still in a guild, checking the cache before asking
+/// Discord.
+///
+/// Only Discord saying they aren't a member counts as them having left, so
+/// anything else (e.g. Discord having a moment) comes back as an error.
+pub async fn is_member(
+    cache_http: impl CacheHttp,
+    guild: GuildId,
+    user: UserId,
+) -> Result<bool, serenity::Error> {
+    if let Some(cache) = cache_http.cache() {
+        if cache.member(guild, user).is_some() {
+            return Ok(true);
+        }
+    }
+
+    match cache_http.http().get_member(guild, user).await {
+        Ok(_) => Ok(true),
+        Err(err) if is_unknown_member(&err) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Discord's "Unknown Member" error.
+fn is_unknown_member(error: &serenity::Error) -> bool {
+    matches!(
+        error,
+        serenity::Error::Http(HttpError::UnsuccessfulRequest(res)) if res.error.code == 10007
+    )
+}
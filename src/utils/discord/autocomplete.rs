@@ -1 +1,2 @@
-pub mod beatsaver;
+pub mod beatsaver;
+pub mod subscriptions;
@@ -1,81 +1,270 @@
-use futures::{Stream, StreamExt};
-use poise::serenity_prelude::{self as serenity};
-
-use crate::{utils::truncate::truncate_string, Context, Error};
-
-struct MapAutocomplete {
-    name: String,
-    id: String,
-}
-
-pub fn find_bsr(ctx: Context<'_>, input: &str) -> Option<String> {
-    // copied and pasted twitch icon
-    if let Some(stripped) = input.strip_prefix("!bsr ") {
-        return Some(stripped.to_string());
-    }
-    // beatsaver link
-    else if let Some(caps) = ctx.data().bsr_link_regex.captures(input) {
-        return Some(caps["bsr"].to_string());
-    }
-    // just the code flat out
-    else if let Some(caps) = ctx.data().hexstring_regex.captures(input.trim()) {
-        return Some(caps[0].to_string());
-    }
-
-    None
-}
-
-pub async fn autocomplete_map<'a>(
-    ctx: Context<'_>,
-    input: &'a str,
-) -> impl Stream<Item = serenity::AutocompleteChoice> + 'a {
-    let maps: Vec<MapAutocomplete> = match find_bsr(ctx, input) {
-        Some(bsr) => handle_code(bsr, ctx).await.unwrap(),
-        None => handle_search(input.to_string(), ctx).await.unwrap(),
-    };
-
-    futures::stream::iter(maps)
-        .map(move |map: MapAutocomplete| serenity::AutocompleteChoice::new(map.name, map.id))
-}
-
-async fn handle_code(bsr: String, ctx: Context<'_>) -> Result<Vec<MapAutocomplete>, Error> {
-    let res = ctx.data().beatsaver_client.map(&bsr).await?;
-    let uploader = format!(" [{}]", res.uploader.name);
-    let name = format!(
-        "{}{}",
-        truncate_string(res.name.clone(), 100 - uploader.len(), "...".into()),
-        uploader
-    );
-
-    Ok(vec![MapAutocomplete {
-        name,
-        id: bsr.clone(),
-    }])
-}
-
-async fn handle_search(query: String, ctx: Context<'_>) -> Result<Vec<MapAutocomplete>, Error> {
-    let search_results = ctx
-        .data()
-        .beatsaver_client
-        .search_maps(&query)
-        .await
-        .unwrap()
-        .docs;
-
-    Ok(search_results
-        .iter()
-        .map(|res| {
-            let uploader = format!(" [{}]", res.uploader.name);
-            let name = format!(
-                "{}{}",
-                truncate_string(res.name.clone(), 100 - uploader.len(), "...".into()),
-                uploader
-            );
-
-            MapAutocomplete {
-                name,
-                id: res.id.clone(),
-            }
-        })
-        .collect())
-}
+use std::time::Duration;
+
+use beatsaver_api::models::map::Map;
+use futures::Stream;
+use log::{debug, warn};
+use poise::serenity_prelude::{self as serenity};
+
+use crate::{
+    clients::beatsaver::query::{QueryError, SearchQuery},
+    utils::text::{limits, truncate},
+    Context, Error,
+};
+
+/// Searches shorter than this aren't worth sending to BeatSaver.
+const MIN_SEARCH_LENGTH: usize = 3;
+/// How long to wait for the user to stop typing.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+/// Discord gives up on autocomplete after 3 seconds, debounce included.
+const LOOKUP_TIMEOUT: Duration = Duration::from_millis(2400);
+
+struct MapAutocomplete {
+    name: String,
+    id: String,
+}
+
+/// A map someone pointed at directly, rather than searched for.
+pub enum MapQuery {
+    /// A map ID, the `!bsr` code.
+    Id(String),
+    /// One or more version hashes, lowercased and without duplicates.
+    Hashes(Vec<String>),
+}
+
+pub fn find_bsr(ctx: Context<'_>, input: &str) -> Option<MapQuery> {
+    let config = &ctx.data().config.beatsaver;
+
+    // copied and pasted twitch icon
+    if let Some(stripped) = input.strip_prefix("!bsr ") {
+        return Some(MapQuery::Id(stripped.to_string()));
+    }
+    // beatsaver link
+    else if let Some(caps) = config.link_regex.captures(input) {
+        return Some(MapQuery::Id(caps["bsr"].to_string()));
+    }
+    // map hashes, possibly a bunch of them pasted from a playlist
+    else if config.hash_regex.is_match(input) {
+        let mut hashes: Vec<String> = vec![];
+        for hash in config.hash_regex.find_iter(input) {
+            let hash = hash.as_str().to_lowercase();
+            if !hashes.contains(&hash) {
+                hashes.push(hash);
+            }
+        }
+
+        return Some(MapQuery::Hashes(hashes));
+    }
+    // just the code flat out
+    else if let Some(caps) = config.code_regex.captures(input.trim()) {
+        return Some(MapQuery::Id(caps[0].to_string()));
+    }
+
+    None
+}
+
+pub async fn autocomplete_map<'a>(
+    ctx: Context<'_>,
+    input: &'a str,
+) -> impl Stream<Item = serenity::AutocompleteChoice> + 'a {
+    let choices = map_choices(ctx, input.trim()).await;
+
+    futures::stream::iter(choices)
+}
+
+async fn map_choices(ctx: Context<'_>, input: &str) -> Vec<serenity::AutocompleteChoice> {
+    let code = find_bsr(ctx, input);
+    if code.is_none() && input.chars().count() < MIN_SEARCH_LENGTH {
+        return vec![];
+    }
+
+    // drop this one if the user has typed more since
+    if !ctx
+        .data()
+        .debouncer
+        .settle(ctx.author().id, DEBOUNCE)
+        .await
+    {
+        debug!("Dropping stale autocomplete for {input:?}");
+        return vec![];
+    }
+
+    let maps = match code {
+        // a half-typed code usually isn't a map yet, so failures are quiet
+        Some(query) => {
+            let lookup = async {
+                match query {
+                    MapQuery::Id(bsr) => handle_code(bsr, ctx).await,
+                    MapQuery::Hashes(hashes) => handle_hashes(hashes, ctx).await,
+                }
+            };
+            match tokio::time::timeout(LOOKUP_TIMEOUT, lookup).await {
+                Ok(Ok(maps)) => maps,
+                Ok(Err(err)) => {
+                    debug!("Autocomplete map lookup failed: {err:#}");
+                    vec![]
+                }
+                Err(_) => {
+                    warn!("Autocomplete map lookup for {input:?} timed out");
+                    vec![]
+                }
+            }
+        }
+        None => {
+            let query = match SearchQuery::parse(input) {
+                Ok(query) => query,
+                Err(err) => return invalid_query(input, &err),
+            };
+            let search = handle_search(query, ctx);
+            match tokio::time::timeout(LOOKUP_TIMEOUT, search).await {
+                Ok(Ok(maps)) => maps,
+                Ok(Err(err)) => {
+                    warn!("Autocomplete search for {input:?} failed: {err:#}");
+                    return unavailable(input);
+                }
+                Err(_) => {
+                    warn!("Autocomplete search for {input:?} timed out");
+                    return unavailable(input);
+                }
+            }
+        }
+    };
+
+    maps.into_iter()
+        .map(|map| serenity::AutocompleteChoice::new(map.name, map.id))
+        .collect()
+}
+
+/// Suggests mapper names, taken from the uploaders of maps matching what's
+/// been typed so far.
+pub async fn autocomplete_mapper<'a>(
+    ctx: Context<'_>,
+    input: &'a str,
+) -> impl Stream<Item = serenity::AutocompleteChoice> + 'a {
+    let choices = mapper_choices(ctx, input.trim()).await;
+
+    futures::stream::iter(choices)
+}
+
+async fn mapper_choices(ctx: Context<'_>, input: &str) -> Vec<serenity::AutocompleteChoice> {
+    if input.chars().count() < MIN_SEARCH_LENGTH {
+        return vec![];
+    }
+    if !ctx
+        .data()
+        .debouncer
+        .settle(ctx.author().id, DEBOUNCE)
+        .await
+    {
+        debug!("Dropping stale mapper autocomplete for {input:?}");
+        return vec![];
+    }
+
+    let query = SearchQuery {
+        text: input.to_owned(),
+        ..Default::default()
+    };
+    let search = ctx.data().beatsaver.search(&query, 0);
+    let maps = match tokio::time::timeout(LOOKUP_TIMEOUT, search).await {
+        Ok(Ok(maps)) => maps,
+        Ok(Err(err)) => {
+            warn!("Mapper autocomplete for {input:?} failed: {err:#}");
+            return vec![];
+        }
+        Err(_) => {
+            warn!("Mapper autocomplete for {input:?} timed out");
+            return vec![];
+        }
+    };
+
+    // names containing what was typed go first
+    let needle = input.to_lowercase();
+    let mut names: Vec<String> = vec![];
+    for map in &maps {
+        if !names.contains(&map.uploader.name) {
+            names.push(map.uploader.name.clone());
+        }
+    }
+    names.sort_by_key(|name| !name.to_lowercase().contains(&needle));
+
+    names
+        .into_iter()
+        .take(25)
+        .map(|name| {
+            let name = truncate(&name, limits::COMPONENT_LABEL, "");
+            serenity::AutocompleteChoice::new(name.clone(), name)
+        })
+        .collect()
+}
+
+/// A single choice saying BeatSaver can't be reached, which just submits
+/// what was typed if picked.
+fn unavailable(input: &str) -> Vec<serenity::AutocompleteChoice> {
+    vec![serenity::AutocompleteChoice::new(
+        "BeatSaver is unavailable right now",
+        truncate(input, limits::COMPONENT_LABEL, ""),
+    )]
+}
+
+/// A single choice explaining what's wrong with the search, which submits
+/// it as typed (and gets the full error) if picked.
+fn invalid_query(input: &str, err: &QueryError) -> Vec<serenity::AutocompleteChoice> {
+    vec![serenity::AutocompleteChoice::new(
+        truncate(&format!("Can't search: {err}"), limits::COMPONENT_LABEL, "..."),
+        truncate(input, limits::COMPONENT_LABEL, ""),
+    )]
+}
+
+/// Formats a map as `Song Name [uploader]`, within Discord's 100 characters.
+fn map_label(map: &Map) -> String {
+    let uploader = format!(" [{}]", truncate(&map.uploader.name, 40, "..."));
+    let name = truncate(
+        &map.name,
+        limits::COMPONENT_LABEL - uploader.chars().count(),
+        "...",
+    );
+
+    format!("{name}{uploader}")
+}
+
+async fn handle_code(bsr: String, ctx: Context<'_>) -> Result<Vec<MapAutocomplete>, Error> {
+    let res = ctx.data().beatsaver.map(&bsr).await?;
+
+    Ok(vec![MapAutocomplete {
+        name: map_label(&res),
+        id: bsr.clone(),
+    }])
+}
+
+async fn handle_hashes(
+    hashes: Vec<String>,
+    ctx: Context<'_>,
+) -> Result<Vec<MapAutocomplete>, Error> {
+    let maps = ctx.data().beatsaver.maps_by_hashes(&hashes).await?;
+
+    // picking one submits just its hash
+    Ok(hashes
+        .into_iter()
+        .filter_map(|hash| {
+            maps.get(&hash).map(|map| MapAutocomplete {
+                name: map_label(map),
+                id: hash,
+            })
+        })
+        .take(25)
+        .collect())
+}
+
+async fn handle_search(
+    query: SearchQuery,
+    ctx: Context<'_>,
+) -> Result<Vec<MapAutocomplete>, Error> {
+    let search_results = ctx.data().beatsaver.search(&query, 0).await?;
+
+    Ok(search_results
+        .iter()
+        .map(|res| MapAutocomplete {
+            name: map_label(res),
+            id: res.id.clone(),
+        })
+        .collect())
+}
@@ -0,0 +1,40 @@
+use futures::Stream;
+use log::warn;
+use poise::serenity_prelude::{self as serenity};
+
+use crate::Context;
+
+/// Suggests mappers this server is subscribed to.
+pub async fn autocomplete_subscription<'a>(
+    ctx: Context<'_>,
+    input: &'a str,
+) -> impl Stream<Item = serenity::AutocompleteChoice> + 'a {
+    let subscriptions = match ctx.guild_id() {
+        Some(guild) => ctx
+            .data()
+            .storage
+            .subscriptions()
+            .for_guild(guild)
+            .await
+            .unwrap_or_else(|err| {
+                warn!("Couldn't list subscriptions for autocomplete: {err:#}");
+                vec![]
+            }),
+        None => vec![],
+    };
+
+    let input = input.trim().to_lowercase();
+    let mut names: Vec<String> = subscriptions
+        .into_iter()
+        .map(|subscription| subscription.mapper_name)
+        .filter(|name| name.to_lowercase().contains(&input))
+        .collect();
+    names.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+    names.truncate(25);
+
+    futures::stream::iter(
+        names
+            .into_iter()
+            .map(|name| serenity::AutocompleteChoice::new(name.clone(), name)),
+    )
+}
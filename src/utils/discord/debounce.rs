@@ -0,0 +1,41 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use poise::serenity_prelude::UserId;
+
+/// Keeps track of each user's latest request, so older ones can be dropped
+/// while they're still typing.
+#[derive(Default)]
+pub struct Debouncer {
+    latest: Mutex<HashMap<UserId, u64>>,
+    counter: AtomicU64,
+}
+
+impl Debouncer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Waits out `delay`, then returns whether this is still the user's
+    /// latest request.
+    pub async fn settle(&self, user: UserId, delay: Duration) -> bool {
+        let ticket = self.counter.fetch_add(1, Ordering::Relaxed);
+        self.latest.lock().unwrap().insert(user, ticket);
+
+        tokio::time::sleep(delay).await;
+
+        let mut latest = self.latest.lock().unwrap();
+        if latest.get(&user) == Some(&ticket) {
+            latest.remove(&user);
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// A `.bplist` playlist, the format Beat Saber mods and BeatSaver use.
+///
+/// Only the fields we care about are here, anything else in an uploaded file
+/// is ignored.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Playlist {
+    #[serde(default)]
+    pub playlist_title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub playlist_author: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub playlist_description: Option<String>,
+    /// The cover, as a base64 data URI (or sometimes just the base64).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    #[serde(default)]
+    pub songs: Vec<PlaylistSong>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistSong {
+    /// The `!bsr` code.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(default)]
+    pub hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub song_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level_author_name: Option<String>,
+    /// Difficulties to highlight in game.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub difficulties: Vec<PlaylistDifficulty>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlaylistDifficulty {
+    pub characteristic: String,
+    pub name: String,
+}
+
+impl Playlist {
+    pub fn from_json(bytes: &[u8]) -> Result<Self, Error> {
+        // some tools save with a byte order mark
+        let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+
+        Ok(serde_json::from_slice(bytes)?)
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>, Error> {
+        Ok(serde_json::to_vec_pretty(self)?)
+    }
+
+    /// Sets the cover from raw image bytes.
+    pub fn set_image(&mut self, bytes: &[u8], mime: &str) {
+        self.image = Some(format!("data:{mime};base64,{}", STANDARD.encode(bytes)));
+    }
+
+    /// A file name for the playlist, made from its title.
+    pub fn file_name(&self) -> String {
+        let name: String = self
+            .playlist_title
+            .chars()
+            .map(|c| match c {
+                c if c.is_alphanumeric() || c == '-' || c == '_' => c,
+                _ => '_',
+            })
+            .take(64)
+            .collect();
+
+        match name.trim_matches('_') {
+            "" => "playlist.bplist".to_owned(),
+            name => format!("{name}.bplist"),
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Discord's embed limits, in characters.
+pub mod limits {
+    pub const TITLE: usize = 256;
+    pub const DESCRIPTION: usize = 4096;
+    pub const FIELD_NAME: usize = 256;
+    pub const FIELD_VALUE: usize = 1024;
+    pub const FOOTER: usize = 2048;
+    pub const AUTHOR: usize = 256;
+    /// Across every embed in one message.
+    pub const TOTAL: usize = 6000;
+    /// Select menu option labels, descriptions and values, and autocomplete
+    /// choice names.
+    pub const COMPONENT_LABEL: usize = 100;
+}
+
+const ZERO_WIDTH_SPACE: char = '\u{200B}';
+
+/// Stands in for field names and values once the budget runs out, since
+/// Discord rejects empty ones.
+const PLACEHOLDER: &str = "…";
+/// Held back from the total so a full embed's worth of fields (25 names and
+/// values) can still get a placeholder.
+const PLACEHOLDER_RESERVE: usize = 2 * 25;
+
+/// Characters that mean something in Discord markdown.
+const MARKDOWN_CHARS: &[char] = &[
+    '\\', '*', '_', '~', '`', '|', '>', '#', '-', '[', ']', '(', ')',
+];
+
+/// Cuts text down to at most `limit` characters, ending with `suffix` if
+/// anything was cut.
+///
+/// Cuts between graphemes, so accented letters and emoji don't get split.
+pub fn truncate(text: &str, limit: usize, suffix: &str) -> String {
+    if text.chars().count() <= limit {
+        return text.to_owned();
+    }
+
+    let suffix_len = suffix.chars().count();
+    if suffix_len >= limit {
+        return suffix.chars().take(limit).collect();
+    }
+
+    let budget = limit - suffix_len;
+    let mut truncated = String::new();
+    let mut used = 0;
+
+    for grapheme in text.graphemes(true) {
+        let len = grapheme.chars().count();
+        if used + len > budget {
+            break;
+        }
+        truncated.push_str(grapheme);
+        used += len;
+    }
+
+    truncated.truncate(truncated.trim_end().len());
+    // a lone backslash left at the end would escape the suffix instead
+    let backslashes = truncated.chars().rev().take_while(|c| *c == '\\').count();
+    if backslashes % 2 == 1 {
+        truncated.pop();
+    }
+    truncated.push_str(suffix);
+    truncated
+}
+
+/// Escapes Discord markdown so text shows up as typed.
+pub fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        if MARKDOWN_CHARS.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// Breaks up `@everyone`, `@here`, and user/role mentions so they can't ping.
+pub fn neutralise_mentions(text: &str) -> String {
+    text.replace('@', &format!("@{ZERO_WIDTH_SPACE}"))
+}
+
+/// Makes user-supplied text safe to put in an embed.
+pub fn sanitise(text: &str) -> String {
+    neutralise_mentions(&escape_markdown(text))
+}
+
+/// A flag emoji for a two-letter country code, or nothing if it isn't one.
+pub fn country_flag(code: &str) -> String {
+    match code.len() == 2 && code.chars().all(|c| c.is_ascii_alphabetic()) {
+        true => format!(":flag_{}:", code.to_lowercase()),
+        false => String::new(),
+    }
+}
+
+/// Keeps track of how much of the 6000 character total is left across the
+/// embeds of one message, and fits text into what's left.
+pub struct EmbedBudget {
+    remaining: usize,
+    /// What's left for placeholders, apart from `remaining`. Once it's gone
+    /// they come out of `remaining` like everything else.
+    reserve: usize,
+}
+
+impl Default for EmbedBudget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EmbedBudget {
+    pub fn new() -> Self {
+        Self {
+            remaining: limits::TOTAL - PLACEHOLDER_RESERVE,
+            reserve: PLACEHOLDER_RESERVE,
+        }
+    }
+
+    /// Fits text into `limit` and the remaining total, and counts it.
+    pub fn take(&mut self, text: &str, limit: usize) -> String {
+        let fitted = truncate(text, limit.min(self.remaining), "...");
+        self.remaining -= fitted.chars().count();
+        fitted
+    }
+
+    pub fn title(&mut self, text: &str) -> String {
+        self.take(text, limits::TITLE)
+    }
+
+    pub fn description(&mut self, text: &str) -> String {
+        self.take(text, limits::DESCRIPTION)
+    }
+
+    /// Never empty, unlike the others.
+    pub fn field_name(&mut self, text: &str) -> String {
+        self.take_field_part(text, limits::FIELD_NAME)
+    }
+
+    /// Never empty, unlike the others.
+    pub fn field_value(&mut self, text: &str) -> String {
+        self.take_field_part(text, limits::FIELD_VALUE)
+    }
+
+    pub fn footer(&mut self, text: &str) -> String {
+        self.take(text, limits::FOOTER)
+    }
+
+    /// Fits a whole field, returning `(name, value, inline)`.
+    pub fn field(&mut self, name: &str, value: &str, inline: bool) -> (String, String, bool) {
+        (self.field_name(name), self.field_value(value), inline)
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    fn take_field_part(&mut self, text: &str, limit: usize) -> String {
+        let fitted = self.take(text, limit);
+        if !fitted.is_empty() {
+            return fitted;
+        }
+
+        let cost = PLACEHOLDER.chars().count();
+        if self.reserve >= cost {
+            self.reserve -= cost;
+        } else {
+            self.remaining = self.remaining.saturating_sub(cost);
+        }
+        PLACEHOLDER.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_leaves_short_text_alone() {
+        assert_eq!(truncate("short", 10, "..."), "short");
+        assert_eq!(truncate("exactly10!", 10, "..."), "exactly10!");
+    }
+
+    #[test]
+    fn truncate_cuts_between_graphemes() {
+        // é as e + combining accent, and a family emoji made of several chars
+        let text = "cafe\u{301} 👨\u{200D}👩\u{200D}👧 end";
+        let cut = truncate(text, 9, "...");
+        assert!(cut.chars().count() <= 9);
+        assert_eq!(cut, "cafe\u{301}...");
+
+        // every cut point lands on a char boundary, or this would panic
+        for limit in 0..text.chars().count() {
+            let cut = truncate(text, limit, "...");
+            assert!(cut.chars().count() <= limit, "{cut:?} is over {limit}");
+        }
+    }
+
+    #[test]
+    fn truncate_with_limit_under_suffix() {
+        assert_eq!(truncate("something long", 2, "..."), "..");
+        assert_eq!(truncate("something long", 0, "..."), "");
+    }
+
+    #[test]
+    fn truncate_drops_dangling_escapes() {
+        let escaped = escape_markdown("ab*cdef");
+        assert_eq!(escaped, "ab\\*cdef");
+        assert_eq!(truncate(&escaped, 6, "..."), "ab...");
+
+        // an escaped backslash is a whole pair, so it stays
+        assert_eq!(truncate("a\\\\bcdef", 6, "..."), "a\\\\...");
+    }
+
+    #[test]
+    fn sanitise_escapes_and_breaks_mentions() {
+        assert_eq!(sanitise("**@everyone**"), "\\*\\*@\u{200B}everyone\\*\\*");
+    }
+
+    #[test]
+    fn budget_stops_at_the_total() {
+        let mut budget = EmbedBudget::new();
+        let long = "x".repeat(limits::DESCRIPTION * 2);
+
+        let mut used = 0;
+        for _ in 0..3 {
+            used += budget.description(&long).chars().count();
+        }
+        assert!(used <= limits::TOTAL);
+        assert_eq!(budget.description("more"), "");
+    }
+
+    #[test]
+    fn exhausted_budget_still_fills_fields() {
+        let mut budget = EmbedBudget::new();
+        let long = "x".repeat(limits::TOTAL);
+        let mut used = budget.description(&long).chars().count();
+        used += budget.description(&long).chars().count();
+        assert_eq!(budget.remaining(), 0);
+
+        for _ in 0..25 {
+            let (name, value, _) = budget.field("name", "value", true);
+            assert_eq!(name, PLACEHOLDER);
+            assert_eq!(value, PLACEHOLDER);
+            used += name.chars().count() + value.chars().count();
+        }
+        assert!(used <= limits::TOTAL);
+    }
+
+    #[test]
+    fn placeholders_past_the_reserve_count_against_the_rest() {
+        let mut budget = EmbedBudget::new();
+        let before = budget.remaining();
+
+        // empty text always gets a placeholder
+        for _ in 0..25 {
+            budget.field("", "", true);
+        }
+        assert_eq!(budget.remaining(), before);
+
+        budget.field("", "", true);
+        assert_eq!(budget.remaining(), before - 2 * PLACEHOLDER.chars().count());
+    }
+}